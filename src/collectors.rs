@@ -0,0 +1,314 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serenity::builder::*;
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+use tracing::info;
+
+use crate::commands;
+use crate::shards::Sharding;
+use crate::status::Status;
+use crate::store::Persisted;
+
+/// How often deadlines are checked
+const TICK: Duration = Duration::from_secs(10);
+
+/// A message with components that should stop being interactive at `expires_at`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Collector {
+    /// `NAME` of the command whose `on_expire` hook handles the message
+    pub command: String,
+    pub guild_id: Option<GuildId>,
+    pub channel_id: ChannelId,
+    pub message_id: MessageId,
+    /// Unix timestamp in seconds
+    pub expires_at: i64,
+}
+
+/// Live interactive messages and their deadlines
+pub struct Collectors {
+    entries: Mutex<HashMap<MessageId, Collector>>,
+    store: Persisted<Vec<Collector>>,
+}
+
+impl TypeMapKey for Collectors {
+    type Value = Arc<Collectors>;
+}
+
+impl Collectors {
+    pub fn load(sharding: &Sharding) -> Self {
+        let store: Persisted<Vec<Collector>> = Persisted::new("collectors");
+        let entries = store
+            .load(sharding)
+            .into_values()
+            .flatten()
+            .map(|collector| (collector.message_id, collector))
+            .collect();
+        Self {
+            entries: Mutex::new(entries),
+            store,
+        }
+    }
+
+    pub async fn get(ctx: &Context) -> Arc<Self> {
+        ctx.data
+            .read()
+            .await
+            .get::<Self>()
+            .cloned()
+            .expect("Collectors to be inserted into the client data")
+    }
+
+    fn save(&self, entries: &HashMap<MessageId, Collector>, guild_id: Option<GuildId>) {
+        self.store.save(
+            guild_id,
+            &entries
+                .values()
+                .filter(|collector| collector.guild_id == guild_id)
+                .cloned()
+                .collect(),
+        );
+    }
+
+    /// Start tracking `message`, or push its deadline back if it is already tracked.
+    ///
+    /// It is saved with the state of its guild, so `message.guild_id` has to be set. Messages
+    /// fetched over HTTP leave it out, see [`crate::commands::response_message`].
+    pub async fn track(&self, command: &str, message: &Message, ttl: Duration) {
+        let mut entries = self.entries.lock().await;
+        entries.insert(
+            message.id,
+            Collector {
+                command: command.to_string(),
+                guild_id: message.guild_id,
+                channel_id: message.channel_id,
+                message_id: message.id,
+                expires_at: Timestamp::now().unix_timestamp() + ttl.as_secs() as i64,
+            },
+        );
+        self.save(&entries, message.guild_id);
+    }
+
+    /// Push the deadline of a tracked message back, for when only its id is at hand
+    pub async fn extend(&self, message_id: MessageId, ttl: Duration) {
+        let mut entries = self.entries.lock().await;
+        let Some(collector) = entries.get_mut(&message_id) else {
+            return;
+        };
+        collector.expires_at = Timestamp::now().unix_timestamp() + ttl.as_secs() as i64;
+        let guild_id = collector.guild_id;
+        self.save(&entries, guild_id);
+    }
+
+    /// Stop tracking a message, e.g. because it was finished or removed
+    pub async fn untrack(&self, message_id: MessageId) {
+        let mut entries = self.entries.lock().await;
+        if let Some(collector) = entries.remove(&message_id) {
+            self.save(&entries, collector.guild_id);
+        }
+    }
+
+    async fn take_expired(&self) -> Vec<Collector> {
+        let now = Timestamp::now().unix_timestamp();
+        let mut entries = self.entries.lock().await;
+        let expired: Vec<_> = entries
+            .values()
+            .filter(|collector| collector.expires_at <= now)
+            .cloned()
+            .collect();
+        entries.retain(|_, collector| collector.expires_at > now);
+        let guilds: HashSet<_> = expired.iter().map(|collector| collector.guild_id).collect();
+        for guild_id in guilds {
+            self.save(&entries, guild_id);
+        }
+        expired
+    }
+}
+
+/// Expire tracked messages in the background for as long as the bot runs
+pub fn spawn(ctx: Context) {
+    tokio::spawn(async move {
+        let collectors = Collectors::get(&ctx).await;
+        let mut interval = tokio::time::interval(TICK);
+        loop {
+            interval.tick().await;
+            for collector in collectors.take_expired().await {
+                info!(
+                    "Collector for {} on message {} expired",
+                    collector.command, collector.message_id
+                );
+                let message_id = collector.message_id;
+                if let Err(err) = commands::handle_expire(ctx.clone(), collector).await {
+                    Status::get(&ctx)
+                        .await
+                        .record_error(format!("expiry of message {}", message_id), &err)
+                        .await;
+                }
+            }
+        }
+    });
+}
+
+/// Default expiry: leave the message as is but disable all of its components
+pub async fn disable_components(ctx: &Context, collector: &Collector) -> anyhow::Result<()> {
+    let mut message = collector
+        .channel_id
+        .message(ctx, collector.message_id)
+        .await?;
+    let components = disabled_components(&message.components);
+    message
+        .edit(ctx, EditMessage::new().components(components))
+        .await?;
+    Ok(())
+}
+
+/// `rows` with every button and select menu disabled.
+///
+/// A row is either buttons or a single select menu, rows that are neither cannot be rebuilt
+/// as they were and are left out as a whole.
+pub fn disabled_components(rows: &[ActionRow]) -> Vec<CreateActionRow> {
+    rows.iter().filter_map(disabled_row).collect()
+}
+
+fn disabled_row(row: &ActionRow) -> Option<CreateActionRow> {
+    match &row.components[..] {
+        [ActionRowComponent::SelectMenu(menu)] => {
+            Some(CreateActionRow::SelectMenu(disabled_select_menu(menu)?))
+        }
+        components => components
+            .iter()
+            .map(|component| match component {
+                ActionRowComponent::Button(button) => {
+                    Some(CreateButton::from(button.clone()).disabled(true))
+                }
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()
+            .filter(|buttons| !buttons.is_empty())
+            .map(CreateActionRow::Buttons),
+    }
+}
+
+fn disabled_select_menu(menu: &SelectMenu) -> Option<CreateSelectMenu> {
+    let kind = match menu.kind {
+        ComponentType::StringSelect => CreateSelectMenuKind::String {
+            options: menu.options.iter().map(select_menu_option).collect(),
+        },
+        ComponentType::UserSelect => CreateSelectMenuKind::User {
+            default_users: None,
+        },
+        ComponentType::RoleSelect => CreateSelectMenuKind::Role {
+            default_roles: None,
+        },
+        ComponentType::MentionableSelect => CreateSelectMenuKind::Mentionable {
+            default_users: None,
+            default_roles: None,
+        },
+        ComponentType::ChannelSelect => CreateSelectMenuKind::Channel {
+            channel_types: (!menu.channel_types.is_empty()).then(|| menu.channel_types.clone()),
+            default_channels: None,
+        },
+        _ => return None,
+    };
+    let mut select =
+        CreateSelectMenu::new(menu.custom_id.clone().unwrap_or_default(), kind).disabled(true);
+    if let Some(placeholder) = &menu.placeholder {
+        select = select.placeholder(placeholder);
+    }
+    if let Some(min_values) = menu.min_values {
+        select = select.min_values(min_values);
+    }
+    if let Some(max_values) = menu.max_values {
+        select = select.max_values(max_values);
+    }
+    Some(select)
+}
+
+fn select_menu_option(option: &SelectMenuOption) -> CreateSelectMenuOption {
+    let mut created =
+        CreateSelectMenuOption::new(&option.label, &option.value).default_selection(option.default);
+    if let Some(description) = &option.description {
+        created = created.description(description);
+    }
+    if let Some(emoji) = &option.emoji {
+        created = created.emoji(emoji.clone());
+    }
+    created
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    fn disabled(rows: Value) -> Value {
+        let rows: Vec<ActionRow> = serde_json::from_value(rows).unwrap();
+        serde_json::to_value(disabled_components(&rows)).unwrap()
+    }
+
+    fn button(custom_id: &str) -> Value {
+        json!({"type": 2, "style": 1, "label": custom_id, "custom_id": custom_id})
+    }
+
+    #[test]
+    fn buttons_are_disabled() {
+        let rows = disabled(json!([
+            {"type": 1, "components": [button("a"), button("b")]},
+        ]));
+        assert_eq!(rows[0]["components"].as_array().unwrap().len(), 2);
+        assert_eq!(rows[0]["components"][1]["custom_id"], "b");
+        assert_eq!(rows[0]["components"][1]["disabled"], true);
+    }
+
+    #[test]
+    fn select_menus_keep_their_kind() {
+        let rows = disabled(json!([
+            {"type": 1, "components": [{
+                "type": 3, "custom_id": "letters", "placeholder": "Pick", "max_values": 2,
+                "options": [
+                    {"label": "A", "value": "a", "description": "First", "default": true},
+                    {"label": "B", "value": "b", "emoji": {"name": "🅱️"}},
+                ],
+            }]},
+            {"type": 1, "components": [{"type": 5, "custom_id": "user"}]},
+            {"type": 1, "components": [{"type": 6, "custom_id": "role"}]},
+            {"type": 1, "components": [{"type": 7, "custom_id": "mentionable"}]},
+            {"type": 1, "components": [
+                {"type": 8, "custom_id": "channel", "channel_types": [0, 2]},
+            ]},
+        ]));
+        let kinds: Vec<_> = rows
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|row| row["components"][0]["type"].as_u64().unwrap())
+            .collect();
+        assert_eq!(kinds, [3, 5, 6, 7, 8]);
+        let letters = &rows[0]["components"][0];
+        assert_eq!(letters["disabled"], true);
+        assert_eq!(letters["placeholder"], "Pick");
+        assert_eq!(letters["max_values"], 2);
+        assert_eq!(letters["options"][0]["description"], "First");
+        assert_eq!(letters["options"][0]["default"], true);
+        assert_eq!(letters["options"][1]["emoji"]["name"], "🅱️");
+        assert_eq!(rows[4]["components"][0]["channel_types"], json!([0, 2]));
+        assert_eq!(rows[4]["components"][0]["disabled"], true);
+    }
+
+    #[test]
+    fn rows_that_cannot_be_rebuilt_are_left_out() {
+        let menu = json!({"type": 5, "custom_id": "user"});
+        let rows = disabled(json!([
+            {"type": 1, "components": [button("a"), menu]},
+            {"type": 1, "components": [menu, menu]},
+            {"type": 1, "components": []},
+            {"type": 1, "components": [button("b")]},
+        ]));
+        assert_eq!(rows.as_array().unwrap().len(), 1);
+        assert_eq!(rows[0]["components"][0]["custom_id"], "b");
+    }
+}
@@ -13,29 +13,210 @@ pub use serenity::utils::*;
 #[allow(unused_imports)]
 pub use tracing::{error, info};
 
+#[allow(unused_imports)]
+pub(crate) use crate::collectors::{self, Collector, Collectors};
+#[allow(unused_imports)]
+pub(crate) use crate::config::{Config, Settings};
+#[allow(unused_imports)]
+pub(crate) use crate::scheduler::{Job, Missed, Schedule, Scheduler};
+#[allow(unused_imports)]
+pub(crate) use crate::status::Status;
+
+pub mod admin;
+pub mod connect_four;
+pub mod game;
+pub mod hangman;
 pub mod hello;
 pub mod meow;
+pub mod minesweeper;
 pub mod purge;
+pub mod replay;
+pub mod roll;
+pub mod rps;
 pub mod smashorpass;
+pub mod stats;
 #[cfg(debug_assertions)]
 pub mod test;
 pub mod tictactoe;
+pub mod trivia;
+pub mod ultimate;
 pub mod week_planner;
+pub mod wordchain;
+
+/// Runs `$body` with `$command` aliased to the [`CustomCommand`] whose `NAME` is `$name`
+macro_rules! with_command {
+    ($name:expr, |$command:ident| $body:expr, _ => $fallback:expr) => {
+        match $name {
+            admin::Admin::NAME => {
+                type $command = admin::Admin;
+                $body
+            }
+            #[cfg(debug_assertions)]
+            test::Test::NAME => {
+                type $command = test::Test;
+                $body
+            }
+            connect_four::ConnectFour::NAME => {
+                type $command = connect_four::ConnectFour;
+                $body
+            }
+            hangman::Hangman::NAME => {
+                type $command = hangman::Hangman;
+                $body
+            }
+            hello::Hello::NAME => {
+                type $command = hello::Hello;
+                $body
+            }
+            meow::Meowify::NAME => {
+                type $command = meow::Meowify;
+                $body
+            }
+            minesweeper::Minesweeper::NAME => {
+                type $command = minesweeper::Minesweeper;
+                $body
+            }
+            purge::Purge::NAME => {
+                type $command = purge::Purge;
+                $body
+            }
+            replay::Replay::NAME => {
+                type $command = replay::Replay;
+                $body
+            }
+            roll::RollDice::NAME => {
+                type $command = roll::RollDice;
+                $body
+            }
+            rps::RockPaperScissors::NAME => {
+                type $command = rps::RockPaperScissors;
+                $body
+            }
+            rps::PlayRockPaperScissors::NAME => {
+                type $command = rps::PlayRockPaperScissors;
+                $body
+            }
+            smashorpass::SmashOrPass::NAME => {
+                type $command = smashorpass::SmashOrPass;
+                $body
+            }
+            stats::ShowStats::NAME => {
+                type $command = stats::ShowStats;
+                $body
+            }
+            stats::Leaderboard::NAME => {
+                type $command = stats::Leaderboard;
+                $body
+            }
+            tictactoe::TicTacToe::NAME => {
+                type $command = tictactoe::TicTacToe;
+                $body
+            }
+            tictactoe::PlayTicTacToe::NAME => {
+                type $command = tictactoe::PlayTicTacToe;
+                $body
+            }
+            tictactoe::TypeMove::NAME => {
+                type $command = tictactoe::TypeMove;
+                $body
+            }
+            trivia::Trivia::NAME => {
+                type $command = trivia::Trivia;
+                $body
+            }
+            ultimate::UltimateTicTacToe::NAME => {
+                type $command = ultimate::UltimateTicTacToe;
+                $body
+            }
+            week_planner::WeekPlanner::NAME => {
+                type $command = week_planner::WeekPlanner;
+                $body
+            }
+            wordchain::WordChain::NAME => {
+                type $command = wordchain::WordChain;
+                $body
+            }
+            _ => $fallback,
+        }
+    };
+}
 
 pub fn command_list() -> Vec<CreateCommand> {
     vec![
         #[cfg(debug_assertions)]
         test::Test::command(),
+        admin::Admin::command(),
+        connect_four::ConnectFour::command(),
+        hangman::Hangman::command(),
         hello::Hello::command(),
         meow::Meowify::command(),
+        minesweeper::Minesweeper::command(),
         purge::Purge::command(),
+        replay::Replay::command(),
+        roll::RollDice::command(),
+        rps::RockPaperScissors::command(),
+        rps::PlayRockPaperScissors::command(),
         smashorpass::SmashOrPass::command(),
+        stats::ShowStats::command(),
+        stats::Leaderboard::command(),
         tictactoe::TicTacToe::command(),
+        tictactoe::PlayTicTacToe::command(),
+        tictactoe::TypeMove::command(),
+        trivia::Trivia::command(),
+        ultimate::UltimateTicTacToe::command(),
         week_planner::WeekPlanner::command(),
+        wordchain::WordChain::command(),
     ]
 }
 
-pub async fn handle_interaction(ctx: Context, interaction: Interaction) -> Result<()> {
+/// The `NAME`s of all commands in [`command_list`]
+pub fn command_names() -> Vec<String> {
+    command_list().iter().map(command_name).collect()
+}
+
+fn command_name(command: &CreateCommand) -> String {
+    // The builder has no getters, but it serializes to the payload Discord receives
+    serde_json::to_value(command)
+        .ok()
+        .and_then(|json| json["name"].as_str().map(str::to_string))
+        .unwrap_or_default()
+}
+
+/// Set the commands of a guild to those enabled for it in `config`
+pub async fn register_guild(ctx: &Context, guild_id: GuildId, config: &Config) -> Result<()> {
+    let commands = command_list()
+        .into_iter()
+        .filter(|command| config.command_enabled(Some(guild_id), &command_name(command)))
+        .collect();
+    guild_id.set_commands(&ctx.http, commands).await?;
+    info!("Registered commands in guild {}", guild_id);
+    Ok(())
+}
+
+/// Replace the global commands with [`command_list`]
+pub async fn register_global(ctx: &Context) -> Result<()> {
+    for current in ctx.http.get_global_commands().await? {
+        if let Err(err) = ctx.http.delete_global_command(current.id).await {
+            error!("{}", anyhow::format_err!(err))
+        }
+    }
+    ctx.http.create_global_commands(&command_list()).await?;
+    info!("Registered global commands");
+    Ok(())
+}
+
+pub async fn handle_interaction(ctx: Context, mut interaction: Interaction) -> Result<()> {
+    // Messages in interactions leave out the guild, which collectors and games store them by
+    match &mut interaction {
+        Interaction::Component(component) => component.message.guild_id = component.guild_id,
+        Interaction::Modal(submit) => {
+            if let Some(message) = &mut submit.message {
+                message.guild_id = submit.guild_id;
+            }
+        }
+        _ => {}
+    }
+
     let name = match &interaction {
         Interaction::Command(command) => command.data.name.as_str(),
         Interaction::Component(component) => component
@@ -55,23 +236,65 @@ pub async fn handle_interaction(ctx: Context, interaction: Interaction) -> Resul
         _ => todo!(),
     };
 
-    match name {
-        #[cfg(debug_assertions)]
-        test::Test::NAME => test::Test::handle_interaction(ctx, interaction).await,
-        hello::Hello::NAME => hello::Hello::handle_interaction(ctx, interaction).await,
-        meow::Meowify::NAME => meow::Meowify::handle_interaction(ctx, interaction).await,
-        purge::Purge::NAME => purge::Purge::handle_interaction(ctx, interaction).await,
-        smashorpass::SmashOrPass::NAME => {
-            smashorpass::SmashOrPass::handle_interaction(ctx, interaction).await
-        }
-        tictactoe::TicTacToe::NAME => {
-            tictactoe::TicTacToe::handle_interaction(ctx, interaction).await
-        }
-        week_planner::WeekPlanner::NAME => {
-            week_planner::WeekPlanner::handle_interaction(ctx, interaction).await
+    if Status::get(&ctx).await.maintenance() && name != admin::Admin::NAME {
+        return notice(
+            &ctx,
+            &interaction,
+            "The bot is under maintenance, try again later",
+        )
+        .await;
+    }
+
+    if let Interaction::Command(command) = &interaction {
+        let config = Settings::get(&ctx).await.current();
+        if !config.command_enabled(command.guild_id, name) {
+            return notice(
+                &ctx,
+                &interaction,
+                "This command is disabled in this server",
+            )
+            .await;
         }
-        _ => Err(anyhow!("No handler found for {}:\n{:?}", name, interaction)),
     }
+
+    with_command!(name, |Cmd| Cmd::handle_interaction(ctx, interaction).await, _ => {
+        Err(anyhow!("No handler found for {}:\n{:?}", name, interaction))
+    })
+}
+
+/// Answer any kind of interaction with an ephemeral message
+async fn notice(ctx: &Context, interaction: &Interaction, content: &str) -> Result<()> {
+    let response = CreateInteractionResponse::Message(
+        CreateInteractionResponseMessage::new()
+            .content(content)
+            .ephemeral(true),
+    );
+    match interaction {
+        Interaction::Command(command) => command.create_response(ctx, response).await?,
+        Interaction::Component(component) => component.create_response(ctx, response).await?,
+        Interaction::Modal(submit) => submit.create_response(ctx, response).await?,
+        _ => {}
+    }
+    Ok(())
+}
+
+/// The response to `command` with its guild, which messages fetched over HTTP leave out
+pub async fn response_message(ctx: &Context, command: &CommandInteraction) -> Result<Message> {
+    let mut message = command.get_response(ctx).await?;
+    message.guild_id = command.guild_id;
+    Ok(message)
+}
+
+pub async fn handle_expire(ctx: Context, collector: Collector) -> Result<()> {
+    with_command!(collector.command.as_str(), |Cmd| Cmd::on_expire(ctx, collector).await, _ => {
+        collectors::disable_components(&ctx, &collector).await
+    })
+}
+
+pub async fn handle_job(ctx: Context, job: Job) -> Result<()> {
+    with_command!(job.command.as_str(), |Cmd| Cmd::run_job(ctx, job).await, _ => {
+        Err(anyhow!("No handler found for job {:?}", job))
+    })
 }
 
 #[allow(unused_variables)]
@@ -104,6 +327,16 @@ pub trait CustomCommand {
     async fn modal(ctx: Context, submit: ModalInteraction) -> Result<()> {
         Err(anyhow!("Modal not implemented for {}", Self::NAME))
     }
+
+    /// Called when a message tracked by [`Collectors`] for this command runs out of time
+    async fn on_expire(ctx: Context, collector: Collector) -> Result<()> {
+        collectors::disable_components(&ctx, &collector).await
+    }
+
+    /// Called when a [`Job`] this command added to the [`Scheduler`] is due
+    async fn run_job(ctx: Context, job: Job) -> Result<()> {
+        Err(anyhow!("Jobs not implemented for {}", Self::NAME))
+    }
 }
 
 // #[async_trait]
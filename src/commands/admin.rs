@@ -0,0 +1,312 @@
+use std::collections::BTreeSet;
+
+use crate::shards::ShardManagerKey;
+use crate::status::HandlerError;
+
+use super::*;
+
+pub struct Admin;
+
+/// Set at build time, e.g. `docker build --build-arg GIT_HASH=$(git rev-parse --short HEAD)`
+const GIT_HASH: &str = match option_env!("GIT_HASH") {
+    Some(hash) => hash,
+    None => "unknown",
+};
+
+/// Discord rejects messages longer than 2000 characters
+const MAX_CONTENT: usize = 2000;
+
+#[async_trait]
+impl CustomCommand for Admin {
+    const NAME: &'static str = "admin";
+
+    fn command() -> CreateCommand {
+        CreateCommand::new(Self::NAME)
+            .description("Bot administration, only for the bot owners")
+            .add_option(CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "status",
+                "Show uptime, version, shards and guilds",
+            ))
+            .add_option(CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "commands",
+                "Compare the registered commands with the commands of the bot",
+            ))
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "register",
+                    "Register the commands again",
+                )
+                .add_sub_option(CreateCommandOption::new(
+                    CommandOptionType::String,
+                    "guild",
+                    "Id of the guild, this guild when left out",
+                )),
+            )
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "maintenance",
+                    "Reject new interactions with a notice",
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Boolean,
+                        "enabled",
+                        "Whether maintenance mode is on",
+                    )
+                    .required(true),
+                ),
+            )
+            .add_option(CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "errors",
+                "Show the most recent handler errors",
+            ))
+            .add_option(CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "reload",
+                "Reload the config file",
+            ))
+            .to_owned()
+    }
+
+    async fn slash(ctx: Context, command: CommandInteraction) -> Result<()> {
+        let settings = Settings::get(&ctx).await;
+        if !settings.current().is_owner(command.user.id) {
+            command
+                .create_response(
+                    &ctx,
+                    CreateInteractionResponse::Message(
+                        CreateInteractionResponseMessage::new()
+                            .content("Only the bot owners can use this command")
+                            .ephemeral(true),
+                    ),
+                )
+                .await?;
+            return Ok(());
+        }
+
+        let options = command.data.options();
+        let Some(ResolvedOption {
+            name: subcommand,
+            value: ResolvedValue::SubCommand(sub_options),
+            ..
+        }) = options.first()
+        else {
+            bail!("No admin subcommand: {:?}", command.data.options);
+        };
+
+        command.defer_ephemeral(&ctx).await?;
+        let content = match *subcommand {
+            "status" => status(&ctx).await?,
+            "commands" => compare_commands(&ctx, &settings, command.guild_id).await?,
+            "register" => {
+                let guild_id = match sub_options.first().map(|option| &option.value) {
+                    Some(ResolvedValue::String(id)) => Some(GuildId::new(
+                        id.parse()
+                            .map_err(|_| anyhow!("Invalid guild id: {}", id))?,
+                    )),
+                    _ => command.guild_id,
+                };
+                match (&settings.dev_guild_ids, guild_id) {
+                    (Some(_), Some(guild_id)) => {
+                        register_guild(&ctx, guild_id, &settings.current()).await?;
+                        format!("Registered the commands in guild {}", guild_id)
+                    }
+                    (Some(_), None) => "Give a guild to register the commands in".to_string(),
+                    (None, _) => {
+                        register_global(&ctx).await?;
+                        "Commands are global, registered them globally again".to_string()
+                    }
+                }
+            }
+            "maintenance" => {
+                let enabled = matches!(
+                    sub_options.first().map(|option| &option.value),
+                    Some(ResolvedValue::Boolean(true))
+                );
+                Status::get(&ctx).await.set_maintenance(enabled);
+                info!("Maintenance mode set to {} by {}", enabled, command.user.id);
+                if enabled {
+                    "Maintenance mode is on, new interactions are rejected".to_string()
+                } else {
+                    "Maintenance mode is off".to_string()
+                }
+            }
+            "errors" => {
+                let errors = Status::get(&ctx).await.errors().await;
+                if errors.is_empty() {
+                    "No errors since the bot started".to_string()
+                } else {
+                    let mut content = String::new();
+                    for error in errors {
+                        let entry = error_entry(&error, &error.message);
+                        if content.len() + entry.len() > MAX_CONTENT {
+                            // The newest error is always shown, cut short if need be
+                            if content.is_empty() {
+                                content = truncated_error_entry(&error);
+                            }
+                            break;
+                        }
+                        content.push_str(&entry);
+                    }
+                    content
+                }
+            }
+            "reload" => match settings.reload(&ctx).await {
+                Ok(guilds) if guilds.is_empty() => "Config reloaded".to_string(),
+                Ok(guilds) => format!(
+                    "Config reloaded, registered commands again in {} guild(s)",
+                    guilds.len()
+                ),
+                Err(err) => format!("Kept the current config: {:#}", err),
+            },
+            _ => bail!("Unknown admin subcommand: {}", subcommand),
+        };
+        command
+            .edit_response(&ctx, EditInteractionResponse::new().content(content))
+            .await?;
+        Ok(())
+    }
+}
+
+/// An entry of `/admin errors` with `message` in place of the message of `error`
+fn error_entry(error: &HandlerError, message: &str) -> String {
+    MessageBuilder::default()
+        .push(format!("<t:{}:T> ", error.at.unix_timestamp()))
+        .push_bold_line_safe(&error.source)
+        .push_codeblock_safe(message, None)
+        .build()
+}
+
+/// The entry of an error whose message alone is too long for a message, cut to fit
+fn truncated_error_entry(error: &HandlerError) -> String {
+    let mut message = error.message.clone();
+    loop {
+        let entry = error_entry(error, &format!("{}…", message));
+        let excess = entry.len().saturating_sub(MAX_CONTENT);
+        if excess == 0 || message.is_empty() {
+            return entry;
+        }
+        // Escaping may change the length, so cut and measure again
+        let mut end = message.len().saturating_sub(excess);
+        while !message.is_char_boundary(end) {
+            end -= 1;
+        }
+        message.truncate(end);
+    }
+}
+
+async fn status(ctx: &Context) -> Result<String> {
+    let status = Status::get(ctx).await;
+    let uptime = Timestamp::now().unix_timestamp() - status.started_at.unix_timestamp();
+    let mut msg = MessageBuilder::default();
+    msg.push_bold("Uptime: ")
+        .push_line(format!(
+            "{}d {}h {}m (since <t:{}:f>)",
+            uptime / 86400,
+            uptime % 86400 / 3600,
+            uptime % 3600 / 60,
+            status.started_at.unix_timestamp()
+        ))
+        .push_bold("Version: ")
+        .push_line(format!("{} ({})", env!("CARGO_PKG_VERSION"), GIT_HASH))
+        .push_bold("Guilds: ")
+        .push_line(status.guild_count().await.to_string())
+        .push_bold("Maintenance: ")
+        .push_line(if status.maintenance() { "on" } else { "off" })
+        .push_bold_line("Shards:");
+
+    let shard_manager = ctx
+        .data
+        .read()
+        .await
+        .get::<ShardManagerKey>()
+        .cloned()
+        .ok_or(anyhow!("No shard manager in the client data"))?;
+    let runners = shard_manager.runners.lock().await;
+    let mut shards: Vec<_> = runners.iter().collect();
+    shards.sort_by_key(|(id, _)| id.0);
+    for (id, runner) in shards {
+        msg.push_mono(id.0.to_string()).push_line(format!(
+            " {}, {}",
+            runner.stage,
+            runner
+                .latency
+                .map_or("no heartbeat yet".to_string(), |latency| format!(
+                    "{} ms",
+                    latency.as_millis()
+                ))
+        ));
+    }
+    Ok(msg.build())
+}
+
+/// List the differences between what Discord has registered and [`command_list`]
+async fn compare_commands(
+    ctx: &Context,
+    settings: &Settings,
+    guild_id: Option<GuildId>,
+) -> Result<String> {
+    let config = settings.current();
+    let (scope, expected, registered) = match (&settings.dev_guild_ids, guild_id) {
+        (Some(dev_guild_ids), Some(guild_id)) if dev_guild_ids.contains(&guild_id) => (
+            "this guild",
+            command_names()
+                .into_iter()
+                .filter(|name| config.command_enabled(Some(guild_id), name))
+                .collect::<BTreeSet<_>>(),
+            guild_id.get_commands(&ctx.http).await?,
+        ),
+        (Some(_), _) => return Ok("Commands are not registered in this guild".to_string()),
+        (None, _) => (
+            "global",
+            command_names().into_iter().collect(),
+            ctx.http.get_global_commands().await?,
+        ),
+    };
+    let registered: BTreeSet<_> = registered.into_iter().map(|command| command.name).collect();
+
+    let missing: Vec<_> = expected.difference(&registered).cloned().collect();
+    let unexpected: Vec<_> = registered.difference(&expected).cloned().collect();
+    let mut msg = MessageBuilder::default();
+    msg.push_line(format!(
+        "{} commands registered ({})",
+        registered.len(),
+        scope
+    ));
+    if missing.is_empty() && unexpected.is_empty() {
+        msg.push("Registered commands match the command list");
+    }
+    if !missing.is_empty() {
+        msg.push_bold("Missing: ")
+            .push_line_safe(missing.join(", "));
+    }
+    if !unexpected.is_empty() {
+        msg.push_bold("Unexpected: ")
+            .push_line_safe(unexpected.join(", "));
+    }
+    Ok(msg.build())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn long_errors_are_cut_to_fit() {
+        let error = HandlerError {
+            at: Timestamp::now(),
+            source: "command ping".to_string(),
+            message: "é```".repeat(1000),
+        };
+        let entry = truncated_error_entry(&error);
+        assert!(entry.len() <= MAX_CONTENT);
+        assert!(entry.len() > MAX_CONTENT - 10);
+        assert!(entry.contains("command ping"));
+        assert!(entry.contains('…'));
+    }
+}
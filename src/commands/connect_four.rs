@@ -0,0 +1,531 @@
+use std::time::Duration;
+
+use crate::engine::connect_four::{Board, COLUMNS, ROWS};
+use crate::engine::{Game, Outcome, Player};
+
+use super::game::{self, GameRecord, Games, Players, Position};
+use super::*;
+
+/// How long a game may go without a move before the board is disabled
+const GAME_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+const REMOVE_ID: &str = const_format::formatcp!("{}_{}", ConnectFour::NAME, "_remove");
+const EXPORT_ID: &str = const_format::formatcp!("{}_{}", ConnectFour::NAME, "_export");
+
+const RED_EMOJI: char = '🔴';
+const YELLOW_EMOJI: char = '🟡';
+const EMPTY_EMOJI: char = '⚫';
+const RED_WINNING_EMOJI: char = '🟥';
+const YELLOW_WINNING_EMOJI: char = '🟨';
+
+/// Challenge a user to Connect Four, the rules are in [`crate::engine::connect_four`]
+pub struct ConnectFour;
+
+/// The opponent plays red and drops the first disc
+fn player_user(players: &Players, player: Player) -> UserId {
+    match player {
+        Player::First => players.opponent,
+        Player::Second => players.challenger,
+    }
+}
+
+fn disc(player: Option<Player>) -> char {
+    match player {
+        Some(Player::First) => RED_EMOJI,
+        Some(Player::Second) => YELLOW_EMOJI,
+        None => EMPTY_EMOJI,
+    }
+}
+
+/// The board as an emoji grid, the winning line drawn as squares
+fn grid(board: &Board) -> String {
+    let line = board.winning_line().unwrap_or_default();
+    let mut grid = String::new();
+    for row in 0..ROWS {
+        for column in 0..COLUMNS {
+            let player = board.get(row, column);
+            grid.push(match player {
+                Some(Player::First) if line.contains(&(row, column)) => RED_WINNING_EMOJI,
+                Some(Player::Second) if line.contains(&(row, column)) => YELLOW_WINNING_EMOJI,
+                player => disc(player),
+            });
+        }
+        grid.push('\n');
+    }
+    grid
+}
+
+/// Read the board back from the emoji grid in the message content
+fn parse_board(content: &str) -> Result<Board> {
+    let rows: Vec<[Option<Player>; COLUMNS]> = content
+        .lines()
+        .filter_map(|line| {
+            let cells: Vec<Option<Player>> = line
+                .chars()
+                .map(|emoji| match emoji {
+                    RED_EMOJI | RED_WINNING_EMOJI => Some(Some(Player::First)),
+                    YELLOW_EMOJI | YELLOW_WINNING_EMOJI => Some(Some(Player::Second)),
+                    EMPTY_EMOJI => Some(None),
+                    _ => None,
+                })
+                .collect::<Option<_>>()?;
+            cells.try_into().ok()
+        })
+        .collect();
+    let cells = rows.try_into().map_err(|rows: Vec<_>| {
+        anyhow!("Expected {} rows on the board, found {}", ROWS, rows.len())
+    })?;
+    Ok(Board::with_cells(cells))
+}
+
+/// Rows from the top separated by `/`, like `.......` with `R` and `Y` for the discs
+fn encode_board(board: &Board) -> String {
+    (0..ROWS)
+        .map(|row| {
+            (0..COLUMNS)
+                .map(|column| match board.get(row, column) {
+                    Some(Player::First) => 'R',
+                    Some(Player::Second) => 'Y',
+                    None => '.',
+                })
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn decode_board(value: &str) -> Result<Board> {
+    let rows = value
+        .split('/')
+        .map(|cells| {
+            let cells = cells
+                .chars()
+                .map(|cell| match cell {
+                    'R' => Ok(Some(Player::First)),
+                    'Y' => Ok(Some(Player::Second)),
+                    '.' => Ok(None),
+                    _ => Err(anyhow!("Unknown disc '{}' in Connect Four state", cell)),
+                })
+                .collect::<Result<Vec<_>>>()?;
+            cells
+                .try_into()
+                .map_err(|_| anyhow!("Malformed Connect Four state: {}", value))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let cells = rows
+        .try_into()
+        .map_err(|_| anyhow!("Malformed Connect Four state: {}", value))?;
+    Ok(Board::with_cells(cells))
+}
+
+#[async_trait]
+impl CustomCommand for ConnectFour {
+    const NAME: &'static str = "ConnectFour";
+
+    fn command() -> CreateCommand {
+        CreateCommand::new(Self::NAME)
+            .kind(CommandType::User)
+            .to_owned()
+    }
+
+    async fn slash(ctx: Context, command: CommandInteraction) -> Result<()> {
+        let Some(ResolvedTarget::User(target, _)) = command.data.target() else {
+            bail!("No user for the user command ConnectFour");
+        };
+        if target.bot {
+            command
+                .create_response(
+                    &ctx,
+                    CreateInteractionResponse::Message(
+                        CreateInteractionResponseMessage::new()
+                            .content("You cannot challenge a bot to Connect Four")
+                            .ephemeral(true),
+                    ),
+                )
+                .await?;
+            return Ok(());
+        }
+
+        let board = Board::default();
+        let content = MessageBuilder::default()
+            .mention(&target.id)
+            .push_line(" has been challenged to Connect Four!")
+            .push(grid(&board))
+            .push(disc(Some(board.to_move())).to_string())
+            .mention(&target.id)
+            .push("'s turn")
+            .build();
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content(content)
+                        .components(create_components(&board)),
+                ),
+            )
+            .await?;
+        let message = response_message(&ctx, &command).await?;
+        let players = Players {
+            challenger: command.user.id,
+            opponent: target.id,
+        };
+        let mut record = GameRecord::new(Self::NAME, &message, &players);
+        record.state = encode_board(&board);
+        Games::get(&ctx).await.insert(record).await;
+        Collectors::get(&ctx)
+            .await
+            .track(Self::NAME, &message, GAME_TTL)
+            .await;
+        Ok(())
+    }
+
+    async fn component(ctx: Context, interaction: ComponentInteraction) -> Result<()> {
+        let games = Games::get(&ctx).await;
+        let record = games.find(interaction.message.id).await;
+        let players = match &record {
+            Some(record) => record.players(),
+            None => Players::from_message(&interaction.message)?,
+        };
+        // Anyone may export the moves
+        if interaction.data.custom_id == EXPORT_ID {
+            let content = match &record {
+                Some(record) => MessageBuilder::default()
+                    .push_codeblock_safe(notation(record)?, None)
+                    .build(),
+                None => "No moves were recorded for this game".to_string(),
+            };
+            return game::reply_ephemeral(&ctx, &interaction, content).await;
+        }
+        if !players.contains(interaction.user.id) {
+            return game::reply_ephemeral(&ctx, &interaction, "You are not part of this game")
+                .await;
+        }
+        if interaction.data.custom_id == REMOVE_ID {
+            return game::remove(&ctx, &interaction).await;
+        }
+
+        let column: usize = interaction
+            .data
+            .custom_id
+            .split_once("_")
+            .ok_or(anyhow!("Invalid customId, does not have a '_'"))?
+            .1
+            .parse()?;
+        if column >= COLUMNS {
+            bail!("Invalid customId, there is no column {}", column);
+        }
+        // Games started before they were stored are read from the message
+        if record.is_none() {
+            let board = parse_board(&interaction.message.content)?;
+            let mut record = GameRecord::new(Self::NAME, &interaction.message, &players);
+            record.initial = encode_board(&board);
+            record.state = record.initial.clone();
+            games.insert_new(record).await;
+        }
+        let user_id = interaction.user.id;
+        let dropped = games
+            .update(interaction.message.id, |record| {
+                drop_disc(record, user_id, column)
+            })
+            .await
+            .transpose()?;
+        let (board, turn) = match dropped {
+            Some(Dropped::Played { board, turn }) => (board, turn),
+            // The game ended with a click sent at the same time, or was removed
+            Some(Dropped::Over) | None => {
+                interaction
+                    .create_response(&ctx, CreateInteractionResponse::Acknowledge)
+                    .await?;
+                return Ok(());
+            }
+            Some(Dropped::ColumnFull) => {
+                return game::reply_ephemeral(&ctx, &interaction, "That column is full").await
+            }
+            Some(Dropped::NotYourTurn) => {
+                return game::reply_ephemeral(&ctx, &interaction, "Its not your turn").await
+            }
+        };
+        let outcome = board.outcome();
+
+        let mut msg = MessageBuilder::default();
+        // Preserve first line
+        msg.push_line(
+            interaction
+                .message
+                .content
+                .split_once("\n")
+                .unwrap_or_default()
+                .0,
+        )
+        .push(grid(&board));
+        match outcome {
+            Some(Outcome::Draw) => msg.push("The game is a tie"),
+            Some(Outcome::Win(winner)) => msg
+                .push(disc(Some(winner)).to_string())
+                .mention(&turn)
+                .push(" is the winner!"),
+            None => msg
+                .push(disc(Some(board.to_move())).to_string())
+                .mention(&player_user(&players, board.to_move()))
+                .push("'s turn"),
+        };
+        interaction
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::UpdateMessage(
+                    CreateInteractionResponseMessage::new()
+                        .content(msg.build())
+                        .components(create_components(&board)),
+                ),
+            )
+            .await?;
+        let collectors = Collectors::get(&ctx).await;
+        if outcome.is_some() {
+            collectors.untrack(interaction.message.id).await;
+        } else {
+            collectors
+                .track(Self::NAME, &interaction.message, GAME_TTL)
+                .await;
+        }
+        Ok(())
+    }
+
+    async fn on_expire(ctx: Context, collector: Collector) -> Result<()> {
+        let mut message = collector
+            .channel_id
+            .message(&ctx, collector.message_id)
+            .await?;
+        // Keep the header and the board
+        let board = message
+            .content
+            .rsplit_once("\n")
+            .map_or(message.content.as_str(), |(board, _)| board);
+        let content = MessageBuilder::default()
+            .push_line(board)
+            .push("The game has expired")
+            .build();
+        let components = collectors::disabled_components(&message.components);
+        message
+            .edit(
+                &ctx,
+                EditMessage::new().content(content).components(components),
+            )
+            .await?;
+        Games::get(&ctx).await.remove(collector.message_id).await;
+        Ok(())
+    }
+}
+
+/// What became of a disc dropped by a player
+#[derive(Debug)]
+enum Dropped {
+    /// The game was over before the disc was dropped
+    Over,
+    ColumnFull,
+    NotYourTurn,
+    /// The board after the move and the player that made it
+    Played {
+        board: Board,
+        turn: UserId,
+    },
+}
+
+/// Drop a disc of `user_id` into `column` of the stored game, finishing the game when the
+/// disc decides it. The record is left alone when the disc cannot be dropped
+fn drop_disc(record: &mut GameRecord, user_id: UserId, column: usize) -> Result<Dropped> {
+    let mut board = decode_board(&record.state)?;
+    if board.outcome().is_some() {
+        return Ok(Dropped::Over);
+    }
+    if board.column_full(column) {
+        return Ok(Dropped::ColumnFull);
+    }
+    let turn = player_user(&record.players(), board.to_move());
+    if user_id != turn {
+        return Ok(Dropped::NotYourTurn);
+    }
+    board.apply(column)?;
+    record.moves.push(column.to_string());
+    record.state = encode_board(&board);
+    if board.outcome().is_some() {
+        record.finish();
+    }
+    Ok(Dropped::Played { board, turn })
+}
+
+/// The board after the first `moves` moves of the record
+fn position(record: &GameRecord, moves: usize) -> Result<Board> {
+    let mut board = if record.initial.is_empty() {
+        Board::default()
+    } else {
+        decode_board(&record.initial)?
+    };
+    for column in record.moves.iter().take(moves) {
+        board.apply(column.parse()?)?;
+    }
+    Ok(board)
+}
+
+/// The moves of a game as column numbers from 1, see [`game::notation`]
+pub(super) fn notation(record: &GameRecord) -> Result<String> {
+    let board = position(record, record.moves.len())?;
+    let moves = record
+        .moves
+        .iter()
+        .map(|column| Ok((column.parse::<usize>()? + 1).to_string()))
+        .collect::<Result<Vec<_>>>()?;
+    let result = match board.outcome() {
+        None => "*",
+        Some(Outcome::Draw) => "1/2-1/2",
+        Some(Outcome::Win(Player::First)) => "1-0",
+        Some(Outcome::Win(Player::Second)) => "0-1",
+    };
+    let mut title = ConnectFour::NAME.to_string();
+    if !record.initial.is_empty() {
+        title.push_str(&format!(" from {}", record.initial));
+    }
+    Ok(game::notation(&title, &moves, result))
+}
+
+/// The board after the first `moves` moves of the record for `/replay`
+pub(super) fn replay(record: &GameRecord, moves: usize) -> Result<Position> {
+    let board = position(record, moves)?;
+    let mut content = MessageBuilder::default();
+    content.push_bold_line("Connect Four");
+    match moves.checked_sub(1).and_then(|i| record.moves.get(i)) {
+        Some(column) => content
+            .push(disc(Some(board.to_move().other())).to_string())
+            .push(format!(" in column {}", column.parse::<usize>()? + 1)),
+        None => content.push("Start"),
+    };
+    match board.outcome() {
+        Some(Outcome::Draw) => content.push(", the game is a tie"),
+        Some(Outcome::Win(_)) => content.push(" wins"),
+        None => &mut content,
+    };
+    content.push_line("").push(grid(&board));
+    Ok(Position {
+        content: content.build(),
+        components: Vec::new(),
+    })
+}
+
+/// One button per column, split over two rows as an action row fits five buttons
+fn create_components(board: &Board) -> Vec<CreateActionRow> {
+    let over = board.outcome().is_some();
+    let line = board.winning_line().unwrap_or_default();
+    let buttons: Vec<CreateButton> = (0..COLUMNS)
+        .map(|column| {
+            CreateButton::new(format!("{}_{}", ConnectFour::NAME, column))
+                .label((column + 1).to_string())
+                .disabled(board.column_full(column) || over)
+                .style(if line.iter().any(|&(_, col)| col == column) {
+                    ButtonStyle::Success
+                } else {
+                    ButtonStyle::Secondary
+                })
+        })
+        .collect();
+    buttons
+        .chunks(4)
+        .map(|row| CreateActionRow::Buttons(row.to_vec()))
+        .chain(std::iter::once(CreateActionRow::Buttons(vec![
+            CreateButton::new(EXPORT_ID)
+                .label("Export")
+                .style(ButtonStyle::Secondary),
+            CreateButton::new(REMOVE_ID)
+                .label("Remove")
+                .style(ButtonStyle::Danger),
+        ])))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    const CHALLENGER: UserId = UserId::new(1);
+    const OPPONENT: UserId = UserId::new(2);
+
+    /// A game with `columns` played, the opponent dropped the first disc
+    fn record(columns: &[usize]) -> GameRecord {
+        let mut board = Board::default();
+        for &column in columns {
+            board.apply(column).unwrap();
+        }
+        GameRecord {
+            command: ConnectFour::NAME.to_string(),
+            guild_id: None,
+            channel_id: None,
+            message_id: MessageId::new(3),
+            challenger: CHALLENGER,
+            opponent: OPPONENT,
+            options: BTreeMap::new(),
+            initial: String::new(),
+            moves: columns.iter().map(|column| column.to_string()).collect(),
+            state: encode_board(&board),
+            finished_at: None,
+        }
+    }
+
+    #[test]
+    fn players_take_turns() {
+        let mut game = record(&[]);
+        assert!(matches!(
+            drop_disc(&mut game, CHALLENGER, 3).unwrap(),
+            Dropped::NotYourTurn
+        ));
+        assert!(game.moves.is_empty());
+        assert!(matches!(
+            drop_disc(&mut game, OPPONENT, 3).unwrap(),
+            Dropped::Played { turn: OPPONENT, .. }
+        ));
+        assert!(matches!(
+            drop_disc(&mut game, OPPONENT, 3).unwrap(),
+            Dropped::NotYourTurn
+        ));
+        assert!(matches!(
+            drop_disc(&mut game, CHALLENGER, 3).unwrap(),
+            Dropped::Played {
+                turn: CHALLENGER,
+                ..
+            }
+        ));
+        assert_eq!(game.moves, ["3", "3"]);
+        assert_eq!(
+            decode_board(&game.state).unwrap().get(4, 3),
+            Some(Player::Second)
+        );
+    }
+
+    #[test]
+    fn full_columns_are_refused() {
+        let mut game = record(&[2; ROWS]);
+        let before = game.state.clone();
+        assert!(matches!(
+            drop_disc(&mut game, OPPONENT, 2).unwrap(),
+            Dropped::ColumnFull
+        ));
+        assert_eq!(game.state, before);
+        assert_eq!(game.moves.len(), ROWS);
+    }
+
+    #[test]
+    fn the_winning_disc_finishes_the_game() {
+        let mut game = record(&[0, 0, 1, 1, 2, 2]);
+        assert!(matches!(
+            drop_disc(&mut game, OPPONENT, 3).unwrap(),
+            Dropped::Played { .. }
+        ));
+        assert!(game.finished_at.is_some());
+        // A click sent at the same time as the winning one changes nothing
+        assert!(matches!(
+            drop_disc(&mut game, CHALLENGER, 4).unwrap(),
+            Dropped::Over
+        ));
+        assert_eq!(game.moves.len(), 7);
+    }
+}
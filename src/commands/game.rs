@@ -0,0 +1,325 @@
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::shards::Sharding;
+use crate::store::Persisted;
+
+use super::*;
+
+/// How many finished games are kept per guild for `/replay`
+const MAX_HISTORY: usize = 50;
+
+/// A game played on the buttons of a message, the source of truth for its players and moves
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GameRecord {
+    /// `NAME` of the command the game belongs to
+    pub command: String,
+    pub guild_id: Option<GuildId>,
+    /// `None` for games stored before the channel was
+    #[serde(default)]
+    pub channel_id: Option<ChannelId>,
+    pub message_id: MessageId,
+    pub challenger: UserId,
+    pub opponent: UserId,
+    /// Settings chosen when the game started, like the board size
+    #[serde(default)]
+    pub options: BTreeMap<String, String>,
+    /// The board before the first move in [`GameRecord::moves`], in the notation of the
+    /// game. Empty for an empty board, only games started before they were stored have another
+    #[serde(default)]
+    pub initial: String,
+    /// Every move in the order it was played, in the notation of the game
+    pub moves: Vec<String>,
+    /// The board after the last move, in the notation of the game
+    pub state: String,
+    /// Unix timestamp in seconds, `None` while the game is being played
+    #[serde(default)]
+    pub finished_at: Option<i64>,
+}
+
+impl GameRecord {
+    pub fn new(command: &str, message: &Message, players: &Players) -> Self {
+        Self {
+            command: command.to_string(),
+            guild_id: message.guild_id,
+            channel_id: Some(message.channel_id),
+            message_id: message.id,
+            challenger: players.challenger,
+            opponent: players.opponent,
+            options: BTreeMap::new(),
+            initial: String::new(),
+            moves: Vec::new(),
+            state: String::new(),
+            finished_at: None,
+        }
+    }
+
+    /// Mark the game as finished, [`Games::update`] keeps it for `/replay` like [`Games::finish`]
+    pub fn finish(&mut self) {
+        self.finished_at = Some(Timestamp::now().unix_timestamp());
+    }
+
+    pub fn players(&self) -> Players {
+        Players {
+            challenger: self.challenger,
+            opponent: self.opponent,
+        }
+    }
+}
+
+/// Games that are being played and the most recent finished games of every guild
+pub struct Games {
+    entries: Mutex<HashMap<MessageId, GameRecord>>,
+    store: Persisted<Vec<GameRecord>>,
+}
+
+impl TypeMapKey for Games {
+    type Value = Arc<Games>;
+}
+
+impl Games {
+    pub fn load(sharding: &Sharding) -> Self {
+        let store: Persisted<Vec<GameRecord>> = Persisted::new("games");
+        let entries = store
+            .load(sharding)
+            .into_values()
+            .flatten()
+            .map(|game| (game.message_id, game))
+            .collect();
+        Self {
+            entries: Mutex::new(entries),
+            store,
+        }
+    }
+
+    pub async fn get(ctx: &Context) -> Arc<Self> {
+        ctx.data
+            .read()
+            .await
+            .get::<Self>()
+            .cloned()
+            .expect("Games to be inserted into the client data")
+    }
+
+    fn save(&self, entries: &HashMap<MessageId, GameRecord>, guild_id: Option<GuildId>) {
+        self.store.save(
+            guild_id,
+            &entries
+                .values()
+                .filter(|game| game.guild_id == guild_id)
+                .cloned()
+                .collect(),
+        );
+    }
+
+    pub async fn find(&self, message_id: MessageId) -> Option<GameRecord> {
+        self.entries.lock().await.get(&message_id).cloned()
+    }
+
+    /// The games of `command` being played in a channel, the most recent first
+    pub async fn running(&self, command: &str, channel_id: ChannelId) -> Vec<GameRecord> {
+        let mut games: Vec<_> = self
+            .entries
+            .lock()
+            .await
+            .values()
+            .filter(|game| {
+                game.command == command
+                    && game.channel_id == Some(channel_id)
+                    && game.finished_at.is_none()
+            })
+            .cloned()
+            .collect();
+        games.sort_by_key(|game| std::cmp::Reverse(game.message_id));
+        games
+    }
+
+    /// The games of `command` that `user_id` is playing in a channel, the most recent first
+    pub async fn playing(
+        &self,
+        command: &str,
+        channel_id: ChannelId,
+        user_id: UserId,
+    ) -> Vec<GameRecord> {
+        let mut games = self.running(command, channel_id).await;
+        games.retain(|game| game.players().contains(user_id));
+        games
+    }
+
+    /// Add a game or replace its record after a move
+    pub async fn insert(&self, game: GameRecord) {
+        let mut entries = self.entries.lock().await;
+        let guild_id = game.guild_id;
+        entries.insert(game.message_id, game);
+        self.save(&entries, guild_id);
+    }
+
+    /// Store a game read from its message, unless a move played at the same time stored it
+    /// already
+    pub async fn insert_new(&self, game: GameRecord) {
+        let mut entries = self.entries.lock().await;
+        let guild_id = game.guild_id;
+        if let Entry::Vacant(entry) = entries.entry(game.message_id) {
+            entry.insert(game);
+            self.save(&entries, guild_id);
+        }
+    }
+
+    /// Change a game where it is stored, so moves played at the same time are all kept.
+    ///
+    /// Returns what `change` returned, or `None` without calling it when the game is gone,
+    /// e.g. because it was removed in the meantime.
+    pub async fn update<T>(
+        &self,
+        message_id: MessageId,
+        change: impl FnOnce(&mut GameRecord) -> T,
+    ) -> Option<T> {
+        let mut entries = self.entries.lock().await;
+        let game = entries.get_mut(&message_id)?;
+        let guild_id = game.guild_id;
+        let result = change(game);
+        if game.finished_at.is_some() {
+            Self::drop_old_games(&mut entries, guild_id);
+        }
+        self.save(&entries, guild_id);
+        Some(result)
+    }
+
+    /// Keep a finished game for `/replay`, dropping the oldest finished games of its guild
+    pub async fn finish(&self, mut game: GameRecord) {
+        let mut entries = self.entries.lock().await;
+        let guild_id = game.guild_id;
+        game.finish();
+        entries.insert(game.message_id, game);
+        Self::drop_old_games(&mut entries, guild_id);
+        self.save(&entries, guild_id);
+    }
+
+    /// Drop the oldest finished games of a guild beyond [`MAX_HISTORY`]
+    fn drop_old_games(entries: &mut HashMap<MessageId, GameRecord>, guild_id: Option<GuildId>) {
+        let mut finished: Vec<_> = entries
+            .values()
+            .filter(|game| game.guild_id == guild_id)
+            .filter_map(|game| Some((game.finished_at?, game.message_id)))
+            .collect();
+        if finished.len() > MAX_HISTORY {
+            finished.sort();
+            for (_, message_id) in &finished[..finished.len() - MAX_HISTORY] {
+                entries.remove(message_id);
+            }
+        }
+    }
+
+    /// Forget a game, e.g. because it was removed or expired, and return its last record
+    pub async fn remove(&self, message_id: MessageId) -> Option<GameRecord> {
+        let mut entries = self.entries.lock().await;
+        let game = entries.remove(&message_id)?;
+        self.save(&entries, game.guild_id);
+        Some(game)
+    }
+}
+
+/// The two users of a game played on the buttons of a message
+#[derive(Debug, Clone, Copy)]
+pub struct Players {
+    /// The user that used the command
+    pub challenger: UserId,
+    /// The user that was challenged, mentioned in the message
+    pub opponent: UserId,
+}
+
+impl Players {
+    /// For messages without a [`GameRecord`], read from the interaction metadata and the
+    /// mentions of the game message
+    pub fn from_message(message: &Message) -> Result<Self> {
+        let Some(ref interaction_metadata) = message.interaction_metadata else {
+            bail!("There was no interaction on the message");
+        };
+        let MessageInteractionMetadata::Command(MessageCommandInteractionMetadata {
+            user: challenger,
+            ..
+        }) = interaction_metadata.as_ref()
+        else {
+            bail!("There was no interaction on the message");
+        };
+
+        // Challenging yourself only mentions you once
+        let opponent = if message.mentions.len() == 1 {
+            message
+                .mentions
+                .first()
+                .ok_or(anyhow!("There was no mentions"))?
+        } else {
+            message
+                .mentions
+                .iter()
+                .find(|&user| user != challenger)
+                .ok_or(anyhow!(
+                    "There was no mentions other than the player who created the interaction"
+                ))?
+        };
+        Ok(Self {
+            challenger: challenger.id,
+            opponent: opponent.id,
+        })
+    }
+
+    pub fn contains(&self, user_id: UserId) -> bool {
+        user_id == self.challenger || user_id == self.opponent
+    }
+}
+
+/// A position of a recorded game, shown by `/replay`
+pub struct Position {
+    /// Describes the last move, games drawn as text add the board
+    pub content: String,
+    /// The board for games drawn as buttons, all disabled
+    pub components: Vec<CreateActionRow>,
+}
+
+/// Moves numbered in pairs followed by the result, like `1. b2 a1 2. c3 1-0`.
+///
+/// The result is `1-0` when the first player won, `0-1` when the second player won,
+/// `1/2-1/2` for a tie and `*` while the game is being played.
+pub fn notation(title: &str, moves: &[String], result: &str) -> String {
+    let mut notation = title.to_string();
+    for (i, pair) in moves.chunks(2).enumerate() {
+        notation.push_str(&format!(" {}. {}", i + 1, pair.join(" ")));
+    }
+    notation.push(' ');
+    notation.push_str(result);
+    notation
+}
+
+/// Answer only the user that clicked
+pub async fn reply_ephemeral(
+    ctx: &Context,
+    interaction: &ComponentInteraction,
+    content: impl Into<String>,
+) -> Result<()> {
+    interaction
+        .create_response(
+            ctx,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content(content)
+                    .ephemeral(true),
+            ),
+        )
+        .await?;
+    Ok(())
+}
+
+/// Delete the game message and forget the game
+pub async fn remove(ctx: &Context, interaction: &ComponentInteraction) -> Result<()> {
+    interaction.message.delete(ctx).await?;
+    Collectors::get(ctx)
+        .await
+        .untrack(interaction.message.id)
+        .await;
+    Games::get(ctx).await.remove(interaction.message.id).await;
+    reply_ephemeral(ctx, interaction, "The game has been removed").await
+}
@@ -0,0 +1,730 @@
+use std::time::Duration;
+
+use rand::seq::IndexedRandom;
+
+use crate::messages::MessageHandler;
+
+use super::game::{self, GameRecord, Games, Players};
+use super::*;
+
+/// How long a game may go without a guess before it ends
+const GAME_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+const REMOVE_ID: &str = const_format::formatcp!("{}_{}", Hangman::NAME, "_remove");
+/// The modal where the starter writes the word
+const WORD_ID: &str = const_format::formatcp!("{}_{}", Hangman::NAME, "_word");
+/// The letter select menus, followed by `_<first letter of the menu>`
+const GUESS_ID: &str = const_format::formatcp!("{}_{}", Hangman::NAME, "_guess");
+
+/// Words for games where the starter does not write one, one per line
+const WORDS: &str = include_str!("hangman/words.txt");
+
+/// Longest word or phrase a starter may write
+const MAX_WORD_LENGTH: usize = 40;
+
+/// One drawing for every number of wrong guesses, the last one ends the game
+const GALLOWS: [&str; 7] = [
+    "  +---+\n  |   |\n      |\n      |\n      |\n      |\n=========",
+    "  +---+\n  |   |\n  O   |\n      |\n      |\n      |\n=========",
+    "  +---+\n  |   |\n  O   |\n  |   |\n      |\n      |\n=========",
+    "  +---+\n  |   |\n  O   |\n /|   |\n      |\n      |\n=========",
+    "  +---+\n  |   |\n  O   |\n /|\\  |\n      |\n      |\n=========",
+    "  +---+\n  |   |\n  O   |\n /|\\  |\n /    |\n      |\n=========",
+    "  +---+\n  |   |\n  O   |\n /|\\  |\n / \\  |\n      |\n=========",
+];
+
+/// Wrong guesses that hang the man
+const MAX_MISSES: usize = GALLOWS.len() - 1;
+
+/// Guess a word letter by letter, by typing letters in the channel or picking them from the
+/// select menus of the game message
+pub struct Hangman;
+
+/// A game read from its [`GameRecord`]
+struct Round {
+    starter: UserId,
+    word: String,
+    /// Whether the starter wrote the word, who then may not guess
+    written: bool,
+    /// Letters in the order they were guessed together with who guessed them
+    guesses: Vec<(UserId, char)>,
+}
+
+impl Round {
+    fn new(starter: UserId, word: String, written: bool) -> Self {
+        Self {
+            starter,
+            word,
+            written,
+            guesses: Vec::new(),
+        }
+    }
+
+    fn of(record: &GameRecord) -> Result<Self> {
+        let guesses = record
+            .moves
+            .iter()
+            .map(|guess| {
+                let (user, letter) = guess
+                    .split_once(':')
+                    .ok_or(anyhow!("Malformed hangman guess: {}", guess))?;
+                let letter = letter
+                    .chars()
+                    .next()
+                    .ok_or(anyhow!("Hangman guess without a letter: {}", guess))?;
+                Ok((UserId::new(user.parse()?), letter))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            starter: record.challenger,
+            word: record
+                .options
+                .get("word")
+                .ok_or(anyhow!("Hangman game without a word"))?
+                .clone(),
+            written: record.options.contains_key("written"),
+            guesses,
+        })
+    }
+
+    fn guessed(&self, letter: char) -> bool {
+        self.guesses.iter().any(|&(_, guess)| guess == letter)
+    }
+
+    /// The wrong guesses in the order they were made
+    fn misses(&self) -> Vec<char> {
+        self.guesses
+            .iter()
+            .map(|&(_, letter)| letter)
+            .filter(|&letter| !self.word.contains(letter))
+            .collect()
+    }
+
+    /// The word with a `_` for every letter that was not guessed yet
+    fn masked(&self) -> String {
+        self.word
+            .chars()
+            .map(|c| match c {
+                'a'..='z' if !self.guessed(c) => "_".to_string(),
+                c => c.to_uppercase().to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn solved(&self) -> bool {
+        self.word
+            .chars()
+            .all(|c| !c.is_ascii_lowercase() || self.guessed(c))
+    }
+
+    fn hanged(&self) -> bool {
+        self.misses().len() >= MAX_MISSES
+    }
+
+    fn over(&self) -> bool {
+        self.solved() || self.hanged()
+    }
+
+    /// How many letters of the word every player revealed, in the order they first did
+    fn found(&self) -> Vec<(UserId, usize)> {
+        let mut found: Vec<(UserId, usize)> = Vec::new();
+        for &(user, letter) in &self.guesses {
+            let count = self.word.matches(letter).count();
+            if count == 0 {
+                continue;
+            }
+            match found.iter_mut().find(|(player, _)| *player == user) {
+                Some((_, total)) => *total += count,
+                None => found.push((user, count)),
+            }
+        }
+        found
+    }
+}
+
+#[async_trait]
+impl CustomCommand for Hangman {
+    const NAME: &'static str = "hangman";
+
+    fn command() -> CreateCommand {
+        CreateCommand::new(Self::NAME)
+            .description("Guess a word letter by letter before the man is hanged")
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "start",
+                    "Start a game of hangman in this channel",
+                )
+                .add_sub_option(CreateCommandOption::new(
+                    CommandOptionType::Boolean,
+                    "own_word",
+                    "Write the word for the others to guess instead of getting a random one",
+                )),
+            )
+            .to_owned()
+    }
+
+    async fn slash(ctx: Context, command: CommandInteraction) -> Result<()> {
+        let options = command.data.options();
+        let Some(ResolvedOption {
+            name: "start",
+            value: ResolvedValue::SubCommand(sub_options),
+            ..
+        }) = options.first()
+        else {
+            bail!("No hangman subcommand: {:?}", command.data.options);
+        };
+
+        if let Some(running) = running(&ctx, command.channel_id).await {
+            let content = MessageBuilder::default()
+                .push("A game of hangman is already being played here: ")
+                .push(
+                    running
+                        .message_id
+                        .link(command.channel_id, command.guild_id),
+                )
+                .build();
+            command
+                .create_response(
+                    &ctx,
+                    CreateInteractionResponse::Message(
+                        CreateInteractionResponseMessage::new()
+                            .content(content)
+                            .ephemeral(true),
+                    ),
+                )
+                .await?;
+            return Ok(());
+        }
+
+        let own_word = matches!(
+            sub_options.first().map(|option| &option.value),
+            Some(ResolvedValue::Boolean(true))
+        );
+        if own_word {
+            command
+                .create_response(
+                    &ctx,
+                    CreateInteractionResponse::Modal(
+                        CreateModal::new(WORD_ID, "Hangman").components(vec![
+                            CreateActionRow::InputText(
+                                CreateInputText::new(InputTextStyle::Short, "Word", "word")
+                                    .placeholder("Letters only, spaces and hyphens are shown")
+                                    .max_length(MAX_WORD_LENGTH as u16)
+                                    .required(true),
+                            ),
+                        ]),
+                    ),
+                )
+                .await?;
+            return Ok(());
+        }
+
+        let word = WORDS
+            .lines()
+            .collect::<Vec<_>>()
+            .choose(&mut rand::rng())
+            .ok_or(anyhow!("The hangman word list is empty"))?
+            .to_string();
+        let round = Round::new(command.user.id, word, false);
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content(content(&round))
+                        .components(create_components(&round)),
+                ),
+            )
+            .await?;
+        let message = response_message(&ctx, &command).await?;
+        begin(&ctx, &message, &round).await
+    }
+
+    /// The word written by the starter
+    async fn modal(ctx: Context, submit: ModalInteraction) -> Result<()> {
+        let written = submit
+            .data
+            .components
+            .iter()
+            .flat_map(|row| &row.components)
+            .find_map(|component| match component {
+                ActionRowComponent::InputText(input) => input.value.clone(),
+                _ => None,
+            })
+            .unwrap_or_default();
+        let word = match check_word(&written) {
+            Ok(word) => word,
+            Err(reason) => {
+                submit
+                    .create_response(
+                        &ctx,
+                        CreateInteractionResponse::Message(
+                            CreateInteractionResponseMessage::new()
+                                .content(reason)
+                                .ephemeral(true),
+                        ),
+                    )
+                    .await?;
+                return Ok(());
+            }
+        };
+
+        // Another game may have started while the word was written
+        if running(&ctx, submit.channel_id).await.is_some() {
+            submit
+                .create_response(
+                    &ctx,
+                    CreateInteractionResponse::Message(
+                        CreateInteractionResponseMessage::new()
+                            .content("A game of hangman is already being played here")
+                            .ephemeral(true),
+                    ),
+                )
+                .await?;
+            return Ok(());
+        }
+
+        let round = Round::new(submit.user.id, word, true);
+        submit
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content(content(&round))
+                        .components(create_components(&round)),
+                ),
+            )
+            .await?;
+        let mut message = submit.get_response(&ctx).await?;
+        message.guild_id = submit.guild_id;
+        begin(&ctx, &message, &round).await
+    }
+
+    async fn component(ctx: Context, interaction: ComponentInteraction) -> Result<()> {
+        let Some(record) = Games::get(&ctx).await.find(interaction.message.id).await else {
+            return game::reply_ephemeral(&ctx, &interaction, "The game is no longer recorded")
+                .await;
+        };
+
+        if interaction.data.custom_id == REMOVE_ID {
+            if interaction.user.id != record.challenger {
+                return game::reply_ephemeral(
+                    &ctx,
+                    &interaction,
+                    "Only the player who started the game can remove it",
+                )
+                .await;
+            }
+            return game::remove(&ctx, &interaction).await;
+        }
+
+        let ComponentInteractionDataKind::StringSelect { values } = &interaction.data.kind else {
+            bail!("Unknown hangman component: {}", interaction.data.custom_id);
+        };
+        let letter = values
+            .first()
+            .and_then(|value| value.chars().next())
+            .ok_or(anyhow!("No letter picked"))?;
+        match guess(&ctx, record.message_id, interaction.user.id, letter).await? {
+            Ok((round, _)) => {
+                interaction
+                    .create_response(
+                        &ctx,
+                        CreateInteractionResponse::UpdateMessage(
+                            CreateInteractionResponseMessage::new()
+                                .content(content(&round))
+                                .components(create_components(&round)),
+                        ),
+                    )
+                    .await?;
+                Ok(())
+            }
+            Err(reason) => game::reply_ephemeral(&ctx, &interaction, reason).await,
+        }
+    }
+
+    async fn on_expire(ctx: Context, collector: Collector) -> Result<()> {
+        let mut message = collector
+            .channel_id
+            .message(&ctx, collector.message_id)
+            .await?;
+        let games = Games::get(&ctx).await;
+        // Keep everything but the last line, which says what to do
+        let board = message
+            .content
+            .rsplit_once("\n")
+            .map_or(message.content.as_str(), |(board, _)| board);
+        let mut content = MessageBuilder::default();
+        content.push_line(board).push("The game has expired");
+        if let Some(word) = games
+            .find(collector.message_id)
+            .await
+            .and_then(|record| record.options.get("word").cloned())
+        {
+            content.push(", the word was ").push_bold_safe(word);
+        }
+        let components = collectors::disabled_components(&message.components);
+        message
+            .edit(
+                &ctx,
+                EditMessage::new()
+                    .content(content.build())
+                    .components(components),
+            )
+            .await?;
+        games.remove(collector.message_id).await;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl MessageHandler for Hangman {
+    /// A message of a single letter is a guess in the game of its channel
+    async fn message(ctx: &Context, message: &Message) -> Result<()> {
+        let mut chars = message.content.trim().chars();
+        let (Some(letter), None) = (chars.next(), chars.next()) else {
+            return Ok(());
+        };
+        if !letter.is_ascii_alphabetic() {
+            return Ok(());
+        }
+        let Some(record) = running(ctx, message.channel_id).await else {
+            return Ok(());
+        };
+
+        let letter = letter.to_ascii_lowercase();
+        // Letters that cannot be guessed are just chat
+        if let Ok((round, correct)) =
+            guess(ctx, record.message_id, message.author.id, letter).await?
+        {
+            message
+                .channel_id
+                .edit_message(
+                    ctx,
+                    record.message_id,
+                    EditMessage::new()
+                        .content(content(&round))
+                        .components(create_components(&round)),
+                )
+                .await?;
+            message
+                .react(ctx, if correct { '✅' } else { '❌' })
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+/// The game being played in a channel
+async fn running(ctx: &Context, channel_id: ChannelId) -> Option<GameRecord> {
+    Games::get(ctx)
+        .await
+        .running(Hangman::NAME, channel_id)
+        .await
+        .into_iter()
+        .next()
+}
+
+/// The lowercase word, or why it cannot be played
+fn check_word(value: &str) -> std::result::Result<String, &'static str> {
+    let word = value
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase();
+    if !word
+        .chars()
+        .all(|c| c.is_ascii_lowercase() || matches!(c, ' ' | '-' | '\''))
+    {
+        return Err("The word may only have the letters a to z, spaces, hyphens and apostrophes");
+    }
+    if word.chars().filter(char::is_ascii_lowercase).count() < 3 {
+        return Err("The word needs at least three letters");
+    }
+    if word.len() > MAX_WORD_LENGTH {
+        return Err("The word is too long");
+    }
+    Ok(word)
+}
+
+/// Store the game posted as `message`
+async fn begin(ctx: &Context, message: &Message, round: &Round) -> Result<()> {
+    let players = Players {
+        challenger: round.starter,
+        opponent: round.starter,
+    };
+    let mut record = GameRecord::new(Hangman::NAME, message, &players);
+    record
+        .options
+        .insert("word".to_string(), round.word.clone());
+    if round.written {
+        record.options.insert("written".to_string(), String::new());
+    }
+    Games::get(ctx).await.insert(record).await;
+    Collectors::get(ctx)
+        .await
+        .track(Hangman::NAME, message, GAME_TTL)
+        .await;
+    Ok(())
+}
+
+/// Guess `letter` for `user` in the game posted as `message_id`. The game after the guess and
+/// whether the letter is in the word, or why it cannot be guessed
+async fn guess(
+    ctx: &Context,
+    message_id: MessageId,
+    user: UserId,
+    letter: char,
+) -> Result<std::result::Result<(Round, bool), String>> {
+    let Some(guessed) = Games::get(ctx)
+        .await
+        .update(message_id, |record| guess_letter(record, user, letter))
+        .await
+        .transpose()?
+    else {
+        return Ok(Err("The game is no longer recorded".to_string()));
+    };
+    if let Ok((round, _)) = &guessed {
+        let collectors = Collectors::get(ctx).await;
+        if round.over() {
+            collectors.untrack(message_id).await;
+        } else {
+            collectors.extend(message_id, GAME_TTL).await;
+        }
+    }
+    Ok(guessed)
+}
+
+/// Add the guess to `record`, which stays as it is when the letter cannot be guessed
+fn guess_letter(
+    record: &mut GameRecord,
+    user: UserId,
+    letter: char,
+) -> Result<std::result::Result<(Round, bool), String>> {
+    let round = Round::of(record)?;
+    if round.over() {
+        return Ok(Err("The game is over".to_string()));
+    }
+    if round.written && user == round.starter {
+        return Ok(Err("You wrote the word, let the others guess".to_string()));
+    }
+    if round.guessed(letter) {
+        return Ok(Err(format!(
+            "{} was already guessed",
+            letter.to_ascii_uppercase()
+        )));
+    }
+
+    record.moves.push(format!("{}:{}", user, letter));
+    let round = Round::of(record)?;
+    if round.over() {
+        record.finish();
+    }
+    let correct = round.word.contains(letter);
+    Ok(Ok((round, correct)))
+}
+
+/// The gallows, the word and who found which letters
+fn content(round: &Round) -> String {
+    let misses = round.misses();
+    let mut msg = MessageBuilder::default();
+    msg.push("**Hangman** started by ").mention(&round.starter);
+    if round.written {
+        msg.push(" with their own word");
+    }
+    msg.push_line("")
+        .push_codeblock(
+            format!("{}\n\n{}", GALLOWS[misses.len()], round.masked()),
+            None,
+        )
+        .push(format!("Misses ({} of {}): ", misses.len(), MAX_MISSES));
+    if misses.is_empty() {
+        msg.push_line("none");
+    } else {
+        msg.push_line(
+            misses
+                .iter()
+                .map(|letter| letter.to_ascii_uppercase().to_string())
+                .collect::<Vec<_>>()
+                .join(", "),
+        );
+    }
+
+    let found = round.found();
+    if !found.is_empty() {
+        msg.push("Letters found: ");
+        for (i, (user, count)) in found.iter().enumerate() {
+            if i > 0 {
+                msg.push(", ");
+            }
+            msg.mention(user).push(format!(" {}", count));
+        }
+        msg.push_line("");
+    }
+
+    if round.solved() {
+        let (last, _) = round
+            .guesses
+            .last()
+            .copied()
+            .unwrap_or((round.starter, ' '));
+        msg.mention(&last)
+            .push(" found the last letter, the word was ")
+            .push_bold_safe(&round.word);
+    } else if round.hanged() {
+        msg.push("The man was hanged, the word was ")
+            .push_bold_safe(&round.word);
+    } else {
+        msg.push("Guess a letter by typing it in this channel or picking it below");
+    }
+    msg.build()
+}
+
+/// Two select menus for the letters that were not guessed and a row for Remove
+fn create_components(round: &Round) -> Vec<CreateActionRow> {
+    // Select menus have at most 25 options, one less than the alphabet
+    let menus = [('a', 'm'), ('n', 'z')].map(|(first, last)| {
+        let options: Vec<_> = (first..=last)
+            .filter(|&letter| !round.guessed(letter))
+            .map(|letter| {
+                CreateSelectMenuOption::new(
+                    letter.to_ascii_uppercase().to_string(),
+                    letter.to_string(),
+                )
+            })
+            .collect();
+        let disabled = round.over() || options.is_empty();
+        // A select menu needs an option even when every letter of it was guessed
+        let options = if options.is_empty() {
+            vec![CreateSelectMenuOption::new("No letters left", "-")]
+        } else {
+            options
+        };
+        CreateActionRow::SelectMenu(
+            CreateSelectMenu::new(
+                format!("{}_{}", GUESS_ID, first),
+                CreateSelectMenuKind::String { options },
+            )
+            .placeholder(format!(
+                "Guess a letter from {} to {}",
+                first.to_ascii_uppercase(),
+                last.to_ascii_uppercase()
+            ))
+            .disabled(disabled),
+        )
+    });
+    menus
+        .into_iter()
+        .chain(std::iter::once(CreateActionRow::Buttons(vec![
+            CreateButton::new(REMOVE_ID)
+                .label("Remove")
+                .style(ButtonStyle::Danger),
+        ])))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STARTER: UserId = UserId::new(1);
+    const PLAYER: UserId = UserId::new(2);
+
+    fn record(word: &str, written: bool) -> GameRecord {
+        let mut record = GameRecord {
+            command: Hangman::NAME.to_string(),
+            guild_id: None,
+            channel_id: None,
+            message_id: MessageId::new(3),
+            challenger: STARTER,
+            opponent: STARTER,
+            options: Default::default(),
+            initial: String::new(),
+            moves: Vec::new(),
+            state: String::new(),
+            finished_at: None,
+        };
+        record.options.insert("word".to_string(), word.to_string());
+        if written {
+            record.options.insert("written".to_string(), String::new());
+        }
+        record
+    }
+
+    #[test]
+    fn words_are_checked() {
+        assert_eq!(
+            check_word("  Jack-in-the  Box "),
+            Ok("jack-in-the box".to_string())
+        );
+        assert_eq!(check_word("rock 'n' roll"), Ok("rock 'n' roll".to_string()));
+        assert!(check_word("café").is_err());
+        assert!(check_word("r2d2").is_err());
+        assert!(check_word("a-b").is_err());
+        assert!(check_word(&"a".repeat(MAX_WORD_LENGTH + 1)).is_err());
+        assert!(check_word(&"a".repeat(MAX_WORD_LENGTH)).is_ok());
+    }
+
+    #[test]
+    fn guesses_reveal_the_word() {
+        let mut round = Round::new(STARTER, "ice-cream".to_string(), false);
+        assert_eq!(round.masked(), "_ _ _ - _ _ _ _ _");
+        round.guesses = vec![(PLAYER, 'e'), (STARTER, 'x'), (STARTER, 'c')];
+        assert_eq!(round.masked(), "_ C E - C _ E _ _");
+        assert_eq!(round.misses(), vec!['x']);
+        assert_eq!(round.found(), vec![(PLAYER, 2), (STARTER, 2)]);
+        assert!(!round.over());
+        for letter in ['i', 'r', 'a', 'm'] {
+            round.guesses.push((PLAYER, letter));
+        }
+        assert!(round.solved());
+        assert_eq!(round.found(), vec![(PLAYER, 6), (STARTER, 2)]);
+    }
+
+    #[test]
+    fn misses_hang_the_man() {
+        let mut round = Round::new(STARTER, "sun".to_string(), false);
+        for letter in "abcdef".chars() {
+            assert!(!round.hanged());
+            round.guesses.push((PLAYER, letter));
+        }
+        assert!(round.hanged());
+        assert!(round.over());
+        assert!(!round.solved());
+    }
+
+    #[test]
+    fn guesses_are_stored_in_the_record() {
+        let mut game = record("sun", true);
+        assert_eq!(
+            guess_letter(&mut game, STARTER, 's')
+                .unwrap()
+                .err()
+                .unwrap(),
+            "You wrote the word, let the others guess"
+        );
+        assert!(game.moves.is_empty());
+        let (_, correct) = guess_letter(&mut game, PLAYER, 's').unwrap().unwrap();
+        assert!(correct);
+        assert_eq!(
+            guess_letter(&mut game, PLAYER, 's').unwrap().err().unwrap(),
+            "S was already guessed"
+        );
+        let (_, correct) = guess_letter(&mut game, PLAYER, 'x').unwrap().unwrap();
+        assert!(!correct);
+        assert_eq!(game.moves, ["2:s", "2:x"]);
+        assert!(game.finished_at.is_none());
+
+        guess_letter(&mut game, PLAYER, 'u').unwrap().unwrap();
+        let (round, _) = guess_letter(&mut game, PLAYER, 'n').unwrap().unwrap();
+        assert!(round.solved());
+        assert!(game.finished_at.is_some());
+        assert_eq!(
+            guess_letter(&mut game, PLAYER, 'a').unwrap().err().unwrap(),
+            "The game is over"
+        );
+        assert_eq!(Round::of(&game).unwrap().guesses.len(), 4);
+    }
+}
@@ -0,0 +1,765 @@
+use std::fmt::Display;
+use std::time::Duration;
+
+use rand::seq::IndexedRandom;
+
+use crate::engine::minesweeper::{Cell, Minefield, Status};
+
+use super::game::{self, GameRecord, Games, Players};
+use super::*;
+
+/// How long a game may go without a move before it ends
+const GAME_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+const UP_ID: &str = const_format::formatcp!("{}_{}", Minesweeper::NAME, "_up");
+const DOWN_ID: &str = const_format::formatcp!("{}_{}", Minesweeper::NAME, "_down");
+const LEFT_ID: &str = const_format::formatcp!("{}_{}", Minesweeper::NAME, "_left");
+const RIGHT_ID: &str = const_format::formatcp!("{}_{}", Minesweeper::NAME, "_right");
+/// Switches between opening and flagging tiles
+const MODE_ID: &str = const_format::formatcp!("{}_{}", Minesweeper::NAME, "_mode");
+
+const FLAG_EMOJI: &str = "🚩";
+const OPEN_EMOJI: &str = "⛏️";
+/// Hidden cells, and open cells by the number of mines around them
+const HIDDEN_EMOJI: &str = "⬛";
+const NUMBER_EMOJIS: [&str; 9] = ["⬜", "1️⃣", "2️⃣", "3️⃣", "4️⃣", "5️⃣", "6️⃣", "7️⃣", "8️⃣"];
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+enum Size {
+    #[default]
+    Easy,
+    Medium,
+    Hard,
+}
+
+impl Size {
+    const ALL: [Size; 3] = [Size::Easy, Size::Medium, Size::Hard];
+
+    /// Width, height and number of mines
+    fn board(&self) -> (usize, usize, usize) {
+        match self {
+            Size::Easy => (8, 8, 10),
+            Size::Medium => (10, 10, 18),
+            Size::Hard => (12, 12, 30),
+        }
+    }
+
+    fn description(&self) -> String {
+        let (width, height, mines) = self.board();
+        format!(
+            "{}, {}×{} with {} mines",
+            self.label(),
+            width,
+            height,
+            mines
+        )
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            Size::Easy => "Easy",
+            Size::Medium => "Medium",
+            Size::Hard => "Hard",
+        }
+    }
+}
+
+impl TryFrom<&str> for Size {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> std::result::Result<Self, Self::Error> {
+        match value {
+            "easy" => Ok(Self::Easy),
+            "medium" => Ok(Self::Medium),
+            "hard" => Ok(Self::Hard),
+            _ => Err(anyhow!("Could not parse Size from String: {}", value)),
+        }
+    }
+}
+
+impl Display for Size {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.label().to_lowercase())
+    }
+}
+
+/// The part of the board that is shown as buttons
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+enum View {
+    /// 4 rows of 5 tiles with the controls in a row below
+    #[default]
+    Wide,
+    /// 5 rows of 4 tiles with the controls in a column beside
+    Tall,
+}
+
+impl View {
+    const ALL: [View; 2] = [View::Wide, View::Tall];
+
+    fn rows(&self) -> usize {
+        match self {
+            View::Wide => 4,
+            View::Tall => 5,
+        }
+    }
+
+    fn columns(&self) -> usize {
+        match self {
+            View::Wide => 5,
+            View::Tall => 4,
+        }
+    }
+
+    fn description(&self) -> &'static str {
+        match self {
+            View::Wide => "Wide, 5 columns and 4 rows",
+            View::Tall => "Tall, 4 columns and 5 rows",
+        }
+    }
+}
+
+impl TryFrom<&str> for View {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> std::result::Result<Self, Self::Error> {
+        match value {
+            "wide" => Ok(Self::Wide),
+            "tall" => Ok(Self::Tall),
+            _ => Err(anyhow!("Could not parse View from String: {}", value)),
+        }
+    }
+}
+
+impl Display for View {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            View::Wide => write!(f, "wide"),
+            View::Tall => write!(f, "tall"),
+        }
+    }
+}
+
+/// Find the mines of a board without opening them. Played on buttons that show part of the
+/// board, or as a grid of spoilers in a single message
+pub struct Minesweeper;
+
+#[async_trait]
+impl CustomCommand for Minesweeper {
+    const NAME: &'static str = "minesweeper";
+
+    fn command() -> CreateCommand {
+        CreateCommand::new(Self::NAME)
+            .description("Open every cell of a minefield without hitting a mine")
+            .add_option(Size::ALL.into_iter().fold(
+                CreateCommandOption::new(
+                    CommandOptionType::String,
+                    "size",
+                    "How large the board is, easy when left out",
+                ),
+                |option, size| option.add_string_choice(size.description(), size.to_string()),
+            ))
+            .add_option(CreateCommandOption::new(
+                CommandOptionType::Boolean,
+                "spoilers",
+                "Post the board as a grid of spoilers that anyone can open, instead of buttons",
+            ))
+            .add_option(View::ALL.into_iter().fold(
+                CreateCommandOption::new(
+                    CommandOptionType::String,
+                    "view",
+                    "The shape of the part of the board shown as buttons, wide when left out",
+                ),
+                |option, view| option.add_string_choice(view.description(), view.to_string()),
+            ))
+            .to_owned()
+    }
+
+    async fn slash(ctx: Context, command: CommandInteraction) -> Result<()> {
+        let mut size = Size::default();
+        let mut spoilers = false;
+        let mut view = View::default();
+        for option in command.data.options() {
+            match (option.name, option.value) {
+                ("size", ResolvedValue::String(value)) => size = value.try_into()?,
+                ("spoilers", ResolvedValue::Boolean(value)) => spoilers = value,
+                ("view", ResolvedValue::String(value)) => view = value.try_into()?,
+                _ => {}
+            }
+        }
+        let (width, height, mines) = size.board();
+
+        if spoilers {
+            let field = Minefield::random(width, height, mines, None, &mut rand::rng())?;
+            command
+                .create_response(
+                    &ctx,
+                    CreateInteractionResponse::Message(
+                        CreateInteractionResponseMessage::new().content(spoiler_grid(&field)),
+                    ),
+                )
+                .await?;
+            return Ok(());
+        }
+
+        let sweep = Sweep {
+            player: command.user.id,
+            field: Minefield::new(width, height, &[])?,
+            mine_count: mines,
+            mines: None,
+            moves: Vec::new(),
+            view,
+            row: 0,
+            column: 0,
+            flagging: false,
+        };
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content(sweep.content())
+                        .components(sweep.components())
+                        .allowed_mentions(CreateAllowedMentions::new()),
+                ),
+            )
+            .await?;
+        let message = response_message(&ctx, &command).await?;
+        let players = Players {
+            challenger: command.user.id,
+            opponent: command.user.id,
+        };
+        let mut record = GameRecord::new(Self::NAME, &message, &players);
+        sweep.save(&mut record);
+        Games::get(&ctx).await.insert(record).await;
+        Collectors::get(&ctx)
+            .await
+            .track(Self::NAME, &message, GAME_TTL)
+            .await;
+        Ok(())
+    }
+
+    async fn component(ctx: Context, interaction: ComponentInteraction) -> Result<()> {
+        let games = Games::get(&ctx).await;
+        let user_id = interaction.user.id;
+        let swept = games
+            .update(interaction.message.id, |record| {
+                click(record, user_id, &interaction.data.custom_id)
+            })
+            .await
+            .transpose()?;
+        let sweep = match swept {
+            Some(Swept::Played(sweep)) => sweep,
+            Some(Swept::Over) | None => {
+                return game::reply_ephemeral(&ctx, &interaction, "The game is over").await
+            }
+            Some(Swept::NotYours) => {
+                return game::reply_ephemeral(
+                    &ctx,
+                    &interaction,
+                    "This is not your minefield, start your own with /minesweeper",
+                )
+                .await
+            }
+            Some(Swept::Refused(reason)) => {
+                return game::reply_ephemeral(&ctx, &interaction, reason).await
+            }
+        };
+
+        let playing = sweep.field.status() == Status::Playing;
+        let collectors = Collectors::get(&ctx).await;
+        if !playing {
+            // Single player games have no stats or replays, so they are not kept
+            games.remove(interaction.message.id).await;
+            collectors.untrack(interaction.message.id).await;
+        }
+        interaction
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::UpdateMessage(
+                    CreateInteractionResponseMessage::new()
+                        .content(sweep.content())
+                        .components(sweep.components())
+                        .allowed_mentions(CreateAllowedMentions::new()),
+                ),
+            )
+            .await?;
+        if playing {
+            collectors
+                .track(Self::NAME, &interaction.message, GAME_TTL)
+                .await;
+        }
+        Ok(())
+    }
+
+    async fn on_expire(ctx: Context, collector: Collector) -> Result<()> {
+        let mut message = collector
+            .channel_id
+            .message(&ctx, collector.message_id)
+            .await?;
+        // Keep everything but the last line, which says what to do
+        let board = message
+            .content
+            .rsplit_once("\n")
+            .map_or(message.content.as_str(), |(board, _)| board);
+        let content = MessageBuilder::default()
+            .push_line(board)
+            .push("The game has expired")
+            .build();
+        let components = collectors::disabled_components(&message.components);
+        message
+            .edit(
+                &ctx,
+                EditMessage::new().content(content).components(components),
+            )
+            .await?;
+        Games::get(&ctx).await.remove(collector.message_id).await;
+        Ok(())
+    }
+}
+
+/// What a click did to the stored minefield
+enum Swept {
+    /// The game ended before, e.g. with a click sent at the same time
+    Over,
+    NotYours,
+    /// Why the cell cannot be opened or flagged, for the player
+    Refused(String),
+    Played(Sweep),
+}
+
+/// Apply a click of `user_id` on the button `custom_id` to the stored game, finishing it when
+/// the click decides it. The record is left alone when the click is refused
+fn click(record: &mut GameRecord, user_id: UserId, custom_id: &str) -> Result<Swept> {
+    if record.finished_at.is_some() {
+        return Ok(Swept::Over);
+    }
+    if user_id != record.challenger {
+        return Ok(Swept::NotYours);
+    }
+    let mut sweep = Sweep::from_record(record)?;
+    match custom_id {
+        UP_ID => sweep.scroll(-1, 0),
+        DOWN_ID => sweep.scroll(1, 0),
+        LEFT_ID => sweep.scroll(0, -1),
+        RIGHT_ID => sweep.scroll(0, 1),
+        MODE_ID => sweep.flagging = !sweep.flagging,
+        id => {
+            let Some(cell) = id
+                .strip_prefix(Minesweeper::NAME)
+                .and_then(|cell| cell.strip_prefix('_'))
+                .and_then(|cell| cell.parse().ok())
+            else {
+                bail!("Unknown minesweeper customId: {}", id);
+            };
+            if let Err(err) = sweep.play(cell) {
+                return Ok(Swept::Refused(err.to_string()));
+            }
+        }
+    }
+    sweep.save(record);
+    if sweep.field.status() != Status::Playing {
+        record.finish();
+    }
+    Ok(Swept::Played(sweep))
+}
+
+/// A game on buttons, read from its [`GameRecord`]
+struct Sweep {
+    player: UserId,
+    /// The board after every move
+    field: Minefield,
+    mine_count: usize,
+    /// Laid when the first cell is opened, so that cell is never a mine
+    mines: Option<Vec<usize>>,
+    /// `f<cell>` for flags and `o<cell>` for opened cells
+    moves: Vec<String>,
+    view: View,
+    /// The top left cell of the view
+    row: usize,
+    column: usize,
+    /// Whether clicked tiles are flagged instead of opened
+    flagging: bool,
+}
+
+impl Sweep {
+    fn from_record(record: &GameRecord) -> Result<Self> {
+        let option = |key: &str| {
+            record
+                .options
+                .get(key)
+                .ok_or(anyhow!("Minesweeper game without {}", key))
+        };
+        let mines = match record.state.as_str() {
+            "" => None,
+            mines => Some(
+                mines
+                    .split(',')
+                    .map(|mine| Ok(mine.parse()?))
+                    .collect::<Result<Vec<usize>>>()?,
+            ),
+        };
+        let mut sweep = Self {
+            player: record.challenger,
+            field: Minefield::new(option("width")?.parse()?, option("height")?.parse()?, &[])?,
+            mine_count: option("mines")?.parse()?,
+            mines,
+            moves: record.moves.clone(),
+            view: option("view")?.as_str().try_into()?,
+            row: option("row")?.parse()?,
+            column: option("column")?.parse()?,
+            flagging: record.options.contains_key("flagging"),
+        };
+        sweep.field = sweep.replay()?;
+        Ok(sweep)
+    }
+
+    fn save(&self, record: &mut GameRecord) {
+        let options = [
+            ("width", self.field.width().to_string()),
+            ("height", self.field.height().to_string()),
+            ("mines", self.mine_count.to_string()),
+            ("view", self.view.to_string()),
+            ("row", self.row.to_string()),
+            ("column", self.column.to_string()),
+        ];
+        for (key, value) in options {
+            record.options.insert(key.to_string(), value);
+        }
+        if self.flagging {
+            record
+                .options
+                .insert("flagging".to_string(), "true".to_string());
+        } else {
+            record.options.remove("flagging");
+        }
+        record.state = self.mines.as_ref().map_or(String::new(), |mines| {
+            mines
+                .iter()
+                .map(|mine| mine.to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+        });
+        record.moves = self.moves.clone();
+    }
+
+    /// The board with its mines after every move
+    fn replay(&self) -> Result<Minefield> {
+        let mut field = Minefield::new(
+            self.field.width(),
+            self.field.height(),
+            self.mines.as_deref().unwrap_or_default(),
+        )?;
+        for action in &self.moves {
+            match action.split_at_checked(1) {
+                Some(("f", cell)) => {
+                    field.toggle_flag(cell.parse()?)?;
+                }
+                Some(("o", cell)) => {
+                    field.reveal(cell.parse()?)?;
+                }
+                _ => bail!("Malformed minesweeper move: {}", action),
+            }
+        }
+        Ok(field)
+    }
+
+    /// Open or flag a cell, fails with a message for the player when that is not possible
+    fn play(&mut self, cell: usize) -> Result<()> {
+        if self.flagging {
+            self.field.toggle_flag(cell)?;
+            self.moves.push(format!("f{}", cell));
+            return Ok(());
+        }
+        if self.mines.is_none() {
+            let field = Minefield::random(
+                self.field.width(),
+                self.field.height(),
+                self.mine_count,
+                Some(cell),
+                &mut rand::rng(),
+            )?;
+            self.mines = Some(field.mines());
+            self.field = self.replay()?;
+        }
+        self.field.reveal(cell)?;
+        self.moves.push(format!("o{}", cell));
+        Ok(())
+    }
+
+    fn rows(&self) -> usize {
+        self.view.rows().min(self.field.height())
+    }
+
+    fn columns(&self) -> usize {
+        self.view.columns().min(self.field.width())
+    }
+
+    /// Move the view by most of its size, keeping one line of the old view in sight
+    fn scroll(&mut self, rows: isize, columns: isize) {
+        let step = |position: usize, direction: isize, shown: usize, size: usize| {
+            let step = shown.saturating_sub(1).max(1);
+            if direction < 0 {
+                position.saturating_sub(step)
+            } else {
+                (position + step).min(size - shown)
+            }
+        };
+        if rows != 0 {
+            self.row = step(self.row, rows, self.rows(), self.field.height());
+        }
+        if columns != 0 {
+            self.column = step(self.column, columns, self.columns(), self.field.width());
+        }
+    }
+
+    fn content(&self) -> String {
+        let mut msg = MessageBuilder::default();
+        msg.mention(&self.player).push_line(format!(
+            " is sweeping a {}×{} minefield with {} mines",
+            self.field.width(),
+            self.field.height(),
+            self.mine_count
+        ));
+        let status = self.field.status();
+        if status == Status::Playing {
+            msg.push_line(format!(
+                "{} {} of {} flagged, showing rows {}-{} and columns {}-{}",
+                FLAG_EMOJI,
+                self.field.flag_count(),
+                self.mine_count,
+                self.row + 1,
+                self.row + self.rows(),
+                column_name(self.column),
+                column_name(self.column + self.columns() - 1),
+            ));
+        } else {
+            // The whole board once the game is over
+            for row in 0..self.field.height() {
+                let line: String = (0..self.field.width())
+                    .map(|column| emoji(self.field.cell(self.field.index(row, column))))
+                    .collect();
+                msg.push_line(line);
+            }
+        }
+        match status {
+            Status::Playing if self.flagging => msg.push(format!(
+                "Tap tiles to flag them, switch back to opening them with {}",
+                OPEN_EMOJI
+            )),
+            Status::Playing => msg.push(format!(
+                "Tap tiles to open them, switch to flagging them with {}",
+                FLAG_EMOJI
+            )),
+            Status::Won => msg.push("🎉 The minefield is cleared!"),
+            Status::Lost => msg.push("💥 Boom! The game is lost"),
+        };
+        msg.build()
+    }
+
+    /// The tiles of the view and the buttons to scroll it, like [`tictactoe`](super::tictactoe)
+    /// tiles with a row or column of controls
+    fn components(&self) -> Vec<CreateActionRow> {
+        let playing = self.field.status() == Status::Playing;
+        let control = |id: &str, emoji: &str, enabled: bool| {
+            CreateButton::new(id)
+                .emoji(ReactionType::Unicode(emoji.to_string()))
+                .style(ButtonStyle::Primary)
+                .disabled(!playing || !enabled)
+        };
+        let up = control(UP_ID, "⬆️", self.row > 0);
+        let down = control(DOWN_ID, "⬇️", self.row + self.rows() < self.field.height());
+        let left = control(LEFT_ID, "⬅️", self.column > 0);
+        let right = control(
+            RIGHT_ID,
+            "➡️",
+            self.column + self.columns() < self.field.width(),
+        );
+        let mode = control(
+            MODE_ID,
+            if self.flagging {
+                OPEN_EMOJI
+            } else {
+                FLAG_EMOJI
+            },
+            true,
+        )
+        .style(ButtonStyle::Secondary);
+
+        let mut rows: Vec<Vec<CreateButton>> = (self.row..self.row + self.rows())
+            .map(|row| {
+                (self.column..self.column + self.columns())
+                    .map(|column| self.tile(self.field.index(row, column), playing))
+                    .collect()
+            })
+            .collect();
+        match self.view {
+            View::Wide => rows.push(vec![left, up, down, right, mode]),
+            View::Tall => {
+                for (row, button) in rows.iter_mut().zip([up, left, mode, right, down]) {
+                    row.push(button);
+                }
+            }
+        }
+        rows.into_iter().map(CreateActionRow::Buttons).collect()
+    }
+
+    fn tile(&self, cell: usize, playing: bool) -> CreateButton {
+        let state = self.field.cell(cell);
+        CreateButton::new(format!("{}_{}", Minesweeper::NAME, cell))
+            .emoji(ReactionType::Unicode(emoji(state).to_string()))
+            .style(match state {
+                Cell::Exploded => ButtonStyle::Danger,
+                _ => ButtonStyle::Secondary,
+            })
+            .disabled(!playing || matches!(state, Cell::Open(_)))
+    }
+}
+
+/// Columns are lettered like the squares of TicTacToe
+fn column_name(column: usize) -> char {
+    (b'A' + column as u8) as char
+}
+
+fn emoji(cell: Cell) -> &'static str {
+    match cell {
+        Cell::Hidden => HIDDEN_EMOJI,
+        Cell::Flagged => FLAG_EMOJI,
+        Cell::Open(mines) => NUMBER_EMOJIS[mines as usize],
+        Cell::Mine => "💣",
+        Cell::Exploded => "💥",
+        Cell::WrongFlag => "❌",
+    }
+}
+
+/// Every cell behind a spoiler, except for one without mines around it to start from
+fn spoiler_grid(field: &Minefield) -> String {
+    let mines = field.mines();
+    let cells = field.width() * field.height();
+    let empty: Vec<usize> = (0..cells)
+        .filter(|&cell| !mines.contains(&cell) && field.adjacent(cell) == 0)
+        .collect();
+    let start = empty.choose(&mut rand::rng()).copied();
+
+    let mut content = MessageBuilder::default();
+    content.push_line(format!(
+        "Minesweeper, {}×{} with {} mines. Open the spoilers without hitting a 💣",
+        field.width(),
+        field.height(),
+        field.mine_count()
+    ));
+    for row in 0..field.height() {
+        let line: String = (0..field.width())
+            .map(|column| {
+                let cell = field.index(row, column);
+                let emoji = if mines.contains(&cell) {
+                    "💣"
+                } else {
+                    NUMBER_EMOJIS[field.adjacent(cell) as usize]
+                };
+                if Some(cell) == start {
+                    emoji.to_string()
+                } else {
+                    format!("||{}||", emoji)
+                }
+            })
+            .collect();
+        content.push_line(line);
+    }
+    content.build()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    const PLAYER: UserId = UserId::new(1);
+
+    /// A wide 5×4 game with a mine in the last cell
+    fn record() -> GameRecord {
+        let sweep = Sweep {
+            player: PLAYER,
+            field: Minefield::new(5, 4, &[]).unwrap(),
+            mine_count: 1,
+            mines: Some(vec![19]),
+            moves: Vec::new(),
+            view: View::Wide,
+            row: 0,
+            column: 0,
+            flagging: false,
+        };
+        let mut record = GameRecord {
+            command: Minesweeper::NAME.to_string(),
+            guild_id: None,
+            channel_id: None,
+            message_id: MessageId::new(2),
+            challenger: PLAYER,
+            opponent: PLAYER,
+            options: BTreeMap::new(),
+            initial: String::new(),
+            moves: Vec::new(),
+            state: String::new(),
+            finished_at: None,
+        };
+        sweep.save(&mut record);
+        record
+    }
+
+    fn cell_id(cell: usize) -> String {
+        format!("{}_{}", Minesweeper::NAME, cell)
+    }
+
+    #[test]
+    fn only_the_player_clicks() {
+        let mut game = record();
+        assert!(matches!(
+            click(&mut game, UserId::new(3), &cell_id(0)).unwrap(),
+            Swept::NotYours
+        ));
+        assert!(game.moves.is_empty());
+    }
+
+    #[test]
+    fn refused_clicks_leave_the_game_alone() {
+        let mut game = record();
+        // Next to the mine, so nothing else opens
+        assert!(matches!(
+            click(&mut game, PLAYER, &cell_id(18)).unwrap(),
+            Swept::Played(_)
+        ));
+        let before = game.moves.clone();
+        assert!(matches!(
+            click(&mut game, PLAYER, &cell_id(18)).unwrap(),
+            Swept::Refused(reason) if reason == "The cell is already open"
+        ));
+        assert_eq!(game.moves, before);
+        assert!(click(&mut game, PLAYER, "Minesweeper_nope").is_err());
+    }
+
+    #[test]
+    fn modes_and_flags_are_kept() {
+        let mut game = record();
+        click(&mut game, PLAYER, MODE_ID).unwrap();
+        assert!(game.options.contains_key("flagging"));
+        click(&mut game, PLAYER, &cell_id(19)).unwrap();
+        assert_eq!(game.moves, ["f19"]);
+        assert!(game.finished_at.is_none());
+    }
+
+    #[test]
+    fn a_mine_ends_the_game() {
+        let mut game = record();
+        let Swept::Played(sweep) = click(&mut game, PLAYER, &cell_id(19)).unwrap() else {
+            panic!("The mine was not opened");
+        };
+        assert_eq!(sweep.field.status(), Status::Lost);
+        assert!(game.finished_at.is_some());
+        // A click sent at the same time finds the game over
+        assert!(matches!(
+            click(&mut game, PLAYER, &cell_id(0)).unwrap(),
+            Swept::Over
+        ));
+        assert_eq!(game.moves, ["o19"]);
+    }
+}
@@ -0,0 +1,130 @@
+use super::connect_four::ConnectFour;
+use super::game::{self, GameRecord, Games, Position};
+use super::tictactoe::TicTacToe;
+use super::ultimate::UltimateTicTacToe;
+use super::*;
+
+/// Step through a recorded game one move at a time
+pub struct Replay;
+
+#[async_trait]
+impl CustomCommand for Replay {
+    const NAME: &'static str = "replay";
+
+    fn command() -> CreateCommand {
+        CreateCommand::new(Self::NAME)
+            .description("Step through a game move by move")
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::String,
+                    "game",
+                    "Link or id of the game message",
+                )
+                .required(true),
+            )
+            .to_owned()
+    }
+
+    async fn slash(ctx: Context, command: CommandInteraction) -> Result<()> {
+        let Some(ResolvedValue::String(game)) = command
+            .data
+            .options()
+            .first()
+            .map(|option| option.value.clone())
+        else {
+            bail!("No game for the replay command");
+        };
+        // The message id is the last part of a message link
+        let record = match game
+            .rsplit('/')
+            .next()
+            .and_then(|id| id.trim().parse().ok())
+        {
+            Some(message_id) => Games::get(&ctx)
+                .await
+                .find(MessageId::new(message_id))
+                .await
+                .filter(|record| record.guild_id == command.guild_id),
+            None => None,
+        };
+        let response = match record {
+            Some(record) => render(&record, 0)?,
+            None => CreateInteractionResponseMessage::new()
+                .content("There is no recorded game for that message")
+                .ephemeral(true),
+        };
+        command
+            .create_response(&ctx, CreateInteractionResponse::Message(response))
+            .await?;
+        Ok(())
+    }
+
+    async fn component(ctx: Context, interaction: ComponentInteraction) -> Result<()> {
+        // replay_<message id>_<moves>_<prev or next>
+        let mut parts = interaction.data.custom_id.split('_').skip(1);
+        let (Some(message_id), Some(moves)) = (parts.next(), parts.next()) else {
+            bail!("Malformed replay customId: {}", interaction.data.custom_id);
+        };
+        let Some(record) = Games::get(&ctx)
+            .await
+            .find(MessageId::new(message_id.parse()?))
+            .await
+            .filter(|record| record.guild_id == interaction.guild_id)
+        else {
+            return game::reply_ephemeral(&ctx, &interaction, "The game is no longer recorded")
+                .await;
+        };
+        interaction
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::UpdateMessage(render(&record, moves.parse()?)?),
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+/// The game after the first `moves` moves with buttons to step back and forth
+fn render(record: &GameRecord, moves: usize) -> Result<CreateInteractionResponseMessage> {
+    let moves = moves.min(record.moves.len());
+    let Position {
+        content,
+        mut components,
+    } = match record.command.as_str() {
+        TicTacToe::NAME => tictactoe::replay(record, moves)?,
+        ConnectFour::NAME => connect_four::replay(record, moves)?,
+        UltimateTicTacToe::NAME => ultimate::replay(record, moves)?,
+        command => bail!("Games of {} cannot be replayed", command),
+    };
+    let header = MessageBuilder::default()
+        .mention(&record.opponent)
+        .push(" against ")
+        .mention(&record.challenger)
+        .push_line(format!(", move {} of {}", moves, record.moves.len()))
+        .build();
+    components.push(CreateActionRow::Buttons(vec![
+        CreateButton::new(format!(
+            "{}_{}_{}_prev",
+            Replay::NAME,
+            record.message_id,
+            moves.saturating_sub(1)
+        ))
+        .label("Previous")
+        .style(ButtonStyle::Secondary)
+        .disabled(moves == 0),
+        CreateButton::new(format!(
+            "{}_{}_{}_next",
+            Replay::NAME,
+            record.message_id,
+            moves + 1
+        ))
+        .label("Next")
+        .style(ButtonStyle::Secondary)
+        .disabled(moves >= record.moves.len()),
+    ]));
+    Ok(CreateInteractionResponseMessage::new()
+        .content(header + &content)
+        // Replays should not ping the players
+        .allowed_mentions(CreateAllowedMentions::new())
+        .components(components))
+}
@@ -0,0 +1,152 @@
+use crate::engine::dice::{self, Die, Roll};
+
+use super::*;
+
+/// Rolls the notation of the button again, followed by `_<notation>`
+const REROLL_ID: &str = const_format::formatcp!("{}_{}", RollDice::NAME, "_reroll");
+
+/// Longest notation, so it fits in the custom id of the Reroll button
+const MAX_NOTATION: u16 = 80;
+
+/// Longest message Discord accepts, longer rolls are shown without their dice
+const MAX_CONTENT: usize = 2000;
+
+/// Roll dice written in dice notation, like `4d6kh3` or `2d20+5`
+pub struct RollDice;
+
+#[async_trait]
+impl CustomCommand for RollDice {
+    const NAME: &'static str = "roll";
+
+    fn command() -> CreateCommand {
+        CreateCommand::new(Self::NAME)
+            .description("Roll dice, like 4d6kh3, 2d20+5, d20adv or 6d6!")
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::String,
+                    "dice",
+                    "Dice notation, roll several at once by separating them with commas",
+                )
+                .max_length(MAX_NOTATION)
+                .required(true),
+            )
+            .to_owned()
+    }
+
+    async fn slash(ctx: Context, command: CommandInteraction) -> Result<()> {
+        let Some(ResolvedValue::String(notation)) = command
+            .data
+            .options()
+            .first()
+            .map(|option| option.value.clone())
+        else {
+            bail!("No dice for the roll command");
+        };
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(roll(command.user.id, notation, false)),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn component(ctx: Context, interaction: ComponentInteraction) -> Result<()> {
+        let Some(notation) = interaction
+            .data
+            .custom_id
+            .strip_prefix(REROLL_ID)
+            .and_then(|notation| notation.strip_prefix('_'))
+        else {
+            bail!("Unknown roll customId: {}", interaction.data.custom_id);
+        };
+        // A new message, so the earlier roll stays visible
+        interaction
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(roll(interaction.user.id, notation, true)),
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+/// The rolls of `notation` with a button to roll it again, or why it cannot be rolled
+fn roll(user: UserId, notation: &str, reroll: bool) -> CreateInteractionResponseMessage {
+    let expressions = match dice::parse(notation) {
+        Ok(expressions) => expressions,
+        Err(err) => {
+            return CreateInteractionResponseMessage::new()
+                .content(
+                    MessageBuilder::default()
+                        .push("Could not roll ")
+                        .push_mono_safe(notation)
+                        .push(format!(": {}", err))
+                        .build(),
+                )
+                .ephemeral(true)
+        }
+    };
+    let mut rng = rand::rng();
+    let rolls: Vec<Roll> = expressions
+        .iter()
+        .map(|expression| expression.roll(&mut rng))
+        .collect();
+
+    let header = MessageBuilder::default()
+        .push("🎲 ")
+        .mention(&user)
+        .push(if reroll { " rerolled " } else { " rolled " })
+        .push_mono_safe(notation)
+        .build();
+    let mut content = lines(&header, &rolls, true);
+    if content.chars().count() > MAX_CONTENT {
+        content = lines(&header, &rolls, false);
+    }
+    CreateInteractionResponseMessage::new()
+        .content(content)
+        .allowed_mentions(CreateAllowedMentions::new())
+        .button(
+            CreateButton::new(format!("{}_{}", REROLL_ID, notation))
+                .label("Reroll")
+                .emoji(ReactionType::Unicode("🎲".to_string()))
+                .style(ButtonStyle::Secondary),
+        )
+}
+
+/// A line for every expression with the total, and every die when `breakdown` is set
+fn lines(header: &str, rolls: &[Roll], breakdown: bool) -> String {
+    let mut msg = MessageBuilder::default();
+    msg.push(header);
+    for roll in rolls {
+        msg.push_line("").push_mono(&roll.expression).push(": ");
+        if breakdown {
+            for (i, term) in roll.terms.iter().enumerate() {
+                match (i, term.negative) {
+                    (0, true) => msg.push("-"),
+                    (0, false) => &mut msg,
+                    (_, true) => msg.push(" - "),
+                    (_, false) => msg.push(" + "),
+                };
+                if term.dice.is_empty() {
+                    msg.push(term.value.to_string());
+                } else {
+                    let dice: Vec<_> = term.dice.iter().map(die).collect();
+                    msg.push(format!("[{}]", dice.join(", ")));
+                }
+            }
+            msg.push(" = ");
+        }
+        msg.push_bold(roll.total.to_string());
+    }
+    msg.build()
+}
+
+/// Dropped dice are struck through and exploded dice marked with `!`
+fn die(die: &Die) -> String {
+    match (die.kept, die.exploded) {
+        (false, _) => format!("~~{}~~", die.value),
+        (true, true) => format!("{}!", die.value),
+        (true, false) => die.value.to_string(),
+    }
+}
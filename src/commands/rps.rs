@@ -0,0 +1,645 @@
+use std::fmt::Display;
+use std::time::Duration;
+
+use super::game::{self, GameRecord, Games, Players};
+use super::stats::{Outcome, Stats};
+use super::*;
+
+/// How long the players have to pick before the game ends
+const GAME_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+const REMOVE_ID: &str = const_format::formatcp!("{}_{}", RockPaperScissors::NAME, "_remove");
+/// Opens the hidden buttons of a player
+const PICK_ID: &str = const_format::formatcp!("{}_{}", RockPaperScissors::NAME, "_pick");
+/// The hidden buttons, followed by `_<game message id>_<hand>`
+const HAND_ID: &str = const_format::formatcp!("{}_{}", RockPaperScissors::NAME, "_hand");
+
+/// Series lengths that can be chosen, a single game when none is
+const SERIES: [u8; 3] = [3, 5, 7];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Hand {
+    Rock,
+    Paper,
+    Scissors,
+}
+
+impl Hand {
+    const ALL: [Hand; 3] = [Hand::Rock, Hand::Paper, Hand::Scissors];
+
+    fn beats(&self, other: Hand) -> bool {
+        matches!(
+            (self, other),
+            (Hand::Rock, Hand::Scissors)
+                | (Hand::Paper, Hand::Rock)
+                | (Hand::Scissors, Hand::Paper)
+        )
+    }
+
+    fn emoji(&self) -> &'static str {
+        match self {
+            Hand::Rock => "🪨",
+            Hand::Paper => "📄",
+            Hand::Scissors => "✂️",
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            Hand::Rock => "Rock",
+            Hand::Paper => "Paper",
+            Hand::Scissors => "Scissors",
+        }
+    }
+}
+
+impl TryFrom<&str> for Hand {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> std::result::Result<Self, Self::Error> {
+        match value {
+            "rock" => Ok(Self::Rock),
+            "paper" => Ok(Self::Paper),
+            "scissors" => Ok(Self::Scissors),
+            _ => Err(anyhow!("Could not parse Hand from String: {}", value)),
+        }
+    }
+}
+
+impl Display for Hand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.label().to_lowercase())
+    }
+}
+
+/// Challenge a user to rock paper scissors, both pick in secret and the picks are shown together
+pub struct RockPaperScissors;
+
+#[async_trait]
+impl CustomCommand for RockPaperScissors {
+    const NAME: &'static str = "RockPaperScissors";
+
+    fn command() -> CreateCommand {
+        CreateCommand::new(Self::NAME)
+            .kind(CommandType::User)
+            .to_owned()
+    }
+
+    async fn slash(ctx: Context, command: CommandInteraction) -> Result<()> {
+        let Some(ResolvedTarget::User(target, _)) = command.data.target() else {
+            bail!("No user for the user command RockPaperScissors");
+        };
+        challenge(&ctx, &command, target, 1).await
+    }
+
+    async fn component(ctx: Context, interaction: ComponentInteraction) -> Result<()> {
+        // The hidden buttons are on an ephemeral message and carry the game message
+        if interaction.data.custom_id.starts_with(HAND_ID) {
+            return pick(&ctx, &interaction).await;
+        }
+
+        let Some(record) = Games::get(&ctx).await.find(interaction.message.id).await else {
+            return game::reply_ephemeral(&ctx, &interaction, "The game is no longer recorded")
+                .await;
+        };
+        if !record.players().contains(interaction.user.id) {
+            return game::reply_ephemeral(&ctx, &interaction, "You are not part of this game")
+                .await;
+        }
+        if interaction.data.custom_id == REMOVE_ID {
+            return game::remove(&ctx, &interaction).await;
+        }
+
+        if let Some(hand) = picked(&record, interaction.user.id)? {
+            let content = format!(
+                "You picked {} {}, waiting for your opponent",
+                hand.emoji(),
+                hand.label()
+            );
+            return game::reply_ephemeral(&ctx, &interaction, content).await;
+        }
+        let buttons = Hand::ALL
+            .into_iter()
+            .map(|hand| {
+                CreateButton::new(format!("{}_{}_{}", HAND_ID, interaction.message.id, hand))
+                    .label(hand.label())
+                    .emoji(ReactionType::Unicode(hand.emoji().to_string()))
+                    .style(ButtonStyle::Primary)
+            })
+            .collect();
+        interaction
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content("Only you can see this, what do you pick?")
+                        .ephemeral(true)
+                        .components(vec![CreateActionRow::Buttons(buttons)]),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn on_expire(ctx: Context, collector: Collector) -> Result<()> {
+        let mut message = collector
+            .channel_id
+            .message(&ctx, collector.message_id)
+            .await?;
+        // Keep everything but the status line
+        let rounds = message
+            .content
+            .rsplit_once("\n")
+            .map_or(message.content.as_str(), |(rounds, _)| rounds);
+        let content = MessageBuilder::default()
+            .push_line(rounds)
+            .push("The game has expired")
+            .build();
+        let components = collectors::disabled_components(&message.components);
+        message
+            .edit(
+                &ctx,
+                EditMessage::new().content(content).components(components),
+            )
+            .await?;
+        Games::get(&ctx).await.remove(collector.message_id).await;
+        Ok(())
+    }
+}
+
+/// Slash command version of [`RockPaperScissors`] that can start a series
+pub struct PlayRockPaperScissors;
+
+#[async_trait]
+impl CustomCommand for PlayRockPaperScissors {
+    const NAME: &'static str = "rockpaperscissors";
+
+    fn command() -> CreateCommand {
+        CreateCommand::new(Self::NAME)
+            .description("Challenge someone to rock paper scissors")
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::User, "opponent", "Who to challenge")
+                    .required(true),
+            )
+            .add_option(SERIES.into_iter().fold(
+                CreateCommandOption::new(
+                    CommandOptionType::Integer,
+                    "series",
+                    "Play until someone wins most of the games, a single game when left out",
+                ),
+                |option, games| option.add_int_choice(format!("Best of {}", games), games as i32),
+            ))
+            .to_owned()
+    }
+
+    async fn slash(ctx: Context, command: CommandInteraction) -> Result<()> {
+        let mut target = None;
+        let mut games = 1;
+        for option in command.data.options() {
+            match (option.name, option.value) {
+                ("opponent", ResolvedValue::User(user, _)) => target = Some(user.clone()),
+                ("series", ResolvedValue::Integer(value)) => games = value.try_into()?,
+                _ => {}
+            }
+        }
+        let target = target.ok_or(anyhow!("No opponent for the rockpaperscissors command"))?;
+        challenge(&ctx, &command, &target, games).await
+    }
+}
+
+/// Post the game against `target`, won by the first to win most of `games`
+async fn challenge(
+    ctx: &Context,
+    command: &CommandInteraction,
+    target: &User,
+    games: u8,
+) -> Result<()> {
+    let refusal = if target.bot {
+        Some("You cannot challenge a bot to rock paper scissors")
+    } else if target.id == command.user.id {
+        Some("You cannot play rock paper scissors against yourself")
+    } else {
+        None
+    };
+    if let Some(refusal) = refusal {
+        command
+            .create_response(
+                ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content(refusal)
+                        .ephemeral(true),
+                ),
+            )
+            .await?;
+        return Ok(());
+    }
+
+    let players = Players {
+        challenger: command.user.id,
+        opponent: target.id,
+    };
+    command
+        .create_response(
+            ctx,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content(content(
+                        &players,
+                        games,
+                        &[],
+                        &[players.challenger, players.opponent],
+                    ))
+                    .components(create_components(false)),
+            ),
+        )
+        .await?;
+    let message = response_message(ctx, command).await?;
+    let mut record = GameRecord::new(RockPaperScissors::NAME, &message, &players);
+    record
+        .options
+        .insert("games".to_string(), games.to_string());
+    Games::get(ctx).await.insert(record).await;
+    Collectors::get(ctx)
+        .await
+        .track(RockPaperScissors::NAME, &message, GAME_TTL)
+        .await;
+    Ok(())
+}
+
+/// A hidden button was clicked, the round is decided when both players picked
+async fn pick(ctx: &Context, interaction: &ComponentInteraction) -> Result<()> {
+    let mut parts = interaction.data.custom_id.rsplit('_');
+    let (Some(hand), Some(message_id)) = (parts.next(), parts.next()) else {
+        bail!("Malformed hand customId: {}", interaction.data.custom_id);
+    };
+    let hand = Hand::try_from(hand)?;
+    let message_id = MessageId::new(message_id.parse()?);
+    let answer = |content: String| {
+        CreateInteractionResponse::UpdateMessage(
+            CreateInteractionResponseMessage::new()
+                .content(content)
+                .components(Vec::new()),
+        )
+    };
+
+    let picked = Games::get(ctx)
+        .await
+        .update(message_id, |record| play(record, interaction.user.id, hand))
+        .await
+        .transpose()?;
+    let (content, winner) = match picked {
+        Some(Picked::Counted { content, winner }) => (content, winner),
+        Some(Picked::NotPlaying) => {
+            return game::reply_ephemeral(ctx, interaction, "You are not part of this game").await
+        }
+        Some(Picked::Already(picked)) => {
+            let content = format!("You already picked {} {}", picked.emoji(), picked.label());
+            interaction.create_response(ctx, answer(content)).await?;
+            return Ok(());
+        }
+        Some(Picked::Over) | None => {
+            interaction
+                .create_response(ctx, answer("The game is over".to_string()))
+                .await?;
+            return Ok(());
+        }
+    };
+    let collectors = Collectors::get(ctx).await;
+    if let Some((winner, loser)) = winner {
+        Stats::get(ctx)
+            .await
+            .record(
+                interaction.guild_id,
+                RockPaperScissors::NAME,
+                winner,
+                loser,
+                Outcome::Win,
+            )
+            .await;
+        collectors.untrack(message_id).await;
+    }
+
+    let mut message = interaction.channel_id.message(ctx, message_id).await?;
+    message.guild_id = interaction.guild_id;
+    message
+        .edit(
+            ctx,
+            EditMessage::new()
+                .content(content)
+                .components(create_components(winner.is_some())),
+        )
+        .await?;
+    if winner.is_none() {
+        collectors
+            .track(RockPaperScissors::NAME, &message, GAME_TTL)
+            .await;
+    }
+    let content = format!("You picked {} {}", hand.emoji(), hand.label());
+    interaction.create_response(ctx, answer(content)).await?;
+    Ok(())
+}
+
+/// What became of a pick, decided while the game store is locked
+enum Picked {
+    /// The message content after the pick, and the winner and loser when the series is over
+    Counted {
+        content: String,
+        winner: Option<(UserId, UserId)>,
+    },
+    Already(Hand),
+    NotPlaying,
+    Over,
+}
+
+/// Hide the pick of `user_id` in `record` and play the round once both picked. The record
+/// stays as it is unless the pick counts
+fn play(record: &mut GameRecord, user_id: UserId, hand: Hand) -> Result<Picked> {
+    if record.finished_at.is_some() {
+        return Ok(Picked::Over);
+    }
+    let players = record.players();
+    if !players.contains(user_id) {
+        return Ok(Picked::NotPlaying);
+    }
+    if let Some(picked) = picked(record, user_id)? {
+        return Ok(Picked::Already(picked));
+    }
+    record.options.insert(pick_key(user_id), hand.to_string());
+
+    // Both picked, the round is played
+    let mut winner = None;
+    if let (Some(challenger), Some(opponent)) = (
+        picked(record, players.challenger)?,
+        picked(record, players.opponent)?,
+    ) {
+        record.moves.push(format!("{}:{}", challenger, opponent));
+        record.options.remove(&pick_key(players.challenger));
+        record.options.remove(&pick_key(players.opponent));
+        winner = series_winner(record)?.map(|winner| {
+            let loser = if winner == players.challenger {
+                players.opponent
+            } else {
+                players.challenger
+            };
+            (winner, loser)
+        });
+    }
+    let content = record_content(record)?;
+    if winner.is_some() {
+        record.finish();
+    }
+    Ok(Picked::Counted { content, winner })
+}
+
+/// The option that holds the hidden pick of a player until the round is played
+fn pick_key(user_id: UserId) -> String {
+    format!("pick_{}", user_id)
+}
+
+fn picked(record: &GameRecord, user_id: UserId) -> Result<Option<Hand>> {
+    record
+        .options
+        .get(&pick_key(user_id))
+        .map(|hand| Hand::try_from(hand.as_str()))
+        .transpose()
+}
+
+/// The hands of the challenger and the opponent in every round played
+fn rounds(record: &GameRecord) -> Result<Vec<(Hand, Hand)>> {
+    record
+        .moves
+        .iter()
+        .map(|round| {
+            let (challenger, opponent) = round
+                .split_once(':')
+                .ok_or(anyhow!("Malformed rock paper scissors round: {}", round))?;
+            Ok((challenger.try_into()?, opponent.try_into()?))
+        })
+        .collect()
+}
+
+/// Rounds won by the challenger and by the opponent
+fn score(rounds: &[(Hand, Hand)]) -> (u8, u8) {
+    rounds
+        .iter()
+        .fold((0, 0), |(challenger, opponent), (first, second)| {
+            if first.beats(*second) {
+                (challenger + 1, opponent)
+            } else if second.beats(*first) {
+                (challenger, opponent + 1)
+            } else {
+                (challenger, opponent)
+            }
+        })
+}
+
+/// Number of games of the series, 1 for a single game
+fn series_length(record: &GameRecord) -> Result<u8> {
+    record
+        .options
+        .get("games")
+        .map_or(Ok(1), |games| Ok(games.parse()?))
+}
+
+fn series_winner(record: &GameRecord) -> Result<Option<UserId>> {
+    Ok(winner(
+        &record.players(),
+        series_length(record)?,
+        &rounds(record)?,
+    ))
+}
+
+/// The player who won most of the `games` of the series, once someone did
+fn winner(players: &Players, games: u8, rounds: &[(Hand, Hand)]) -> Option<UserId> {
+    let needed = games / 2 + 1;
+    match score(rounds) {
+        (challenger, _) if challenger >= needed => Some(players.challenger),
+        (_, opponent) if opponent >= needed => Some(players.opponent),
+        _ => None,
+    }
+}
+
+fn record_content(record: &GameRecord) -> Result<String> {
+    let players = record.players();
+    let waiting: Vec<_> = [players.challenger, players.opponent]
+        .into_iter()
+        .filter(|&user| !record.options.contains_key(&pick_key(user)))
+        .collect();
+    Ok(content(
+        &players,
+        series_length(record)?,
+        &rounds(record)?,
+        &waiting,
+    ))
+}
+
+/// The challenge, every round played, the score of a series and who still has to pick
+fn content(players: &Players, games: u8, rounds: &[(Hand, Hand)], waiting: &[UserId]) -> String {
+    let mut msg = MessageBuilder::default();
+    msg.mention(&players.opponent)
+        .push(" has been challenged to rock paper scissors by ")
+        .mention(&players.challenger);
+    if games > 1 {
+        msg.push(format!(", best of {}", games));
+    }
+    msg.push_line("!");
+
+    for (i, (challenger, opponent)) in rounds.iter().enumerate() {
+        msg.push(format!("Round {}: ", i + 1))
+            .mention(&players.challenger)
+            .push(format!(" {} vs {} ", challenger.emoji(), opponent.emoji()))
+            .mention(&players.opponent);
+        if challenger.beats(*opponent) {
+            msg.push(", ")
+                .mention(&players.challenger)
+                .push_line(" wins");
+        } else if opponent.beats(*challenger) {
+            msg.push(", ").mention(&players.opponent).push_line(" wins");
+        } else {
+            msg.push_line(", a tie");
+        }
+    }
+    if games > 1 && !rounds.is_empty() {
+        let (challenger, opponent) = score(rounds);
+        msg.push("Score ")
+            .mention(&players.challenger)
+            .push(format!(" {} - {} ", challenger, opponent))
+            .mention(&players.opponent)
+            .push_line("");
+    }
+
+    match winner(players, games, rounds) {
+        Some(winner) => msg.mention(&winner).push(" is the winner!"),
+        None => {
+            msg.push("Pick in secret with the button, waiting for ");
+            for (i, user) in waiting.iter().enumerate() {
+                if i > 0 {
+                    msg.push(" and ");
+                }
+                msg.mention(user);
+            }
+            &mut msg
+        }
+    };
+    msg.build()
+}
+
+fn create_components(finished: bool) -> Vec<CreateActionRow> {
+    let mut buttons = Vec::new();
+    if !finished {
+        buttons.push(
+            CreateButton::new(PICK_ID)
+                .label("Pick")
+                .style(ButtonStyle::Primary),
+        );
+    }
+    buttons.push(
+        CreateButton::new(REMOVE_ID)
+            .label("Remove")
+            .style(ButtonStyle::Danger),
+    );
+    vec![CreateActionRow::Buttons(buttons)]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use Hand::*;
+
+    const CHALLENGER: UserId = UserId::new(1);
+    const OPPONENT: UserId = UserId::new(2);
+
+    fn players() -> Players {
+        Players {
+            challenger: CHALLENGER,
+            opponent: OPPONENT,
+        }
+    }
+
+    /// A best of `games` series with `moves` played
+    fn record(games: u8, moves: &[&str]) -> GameRecord {
+        GameRecord {
+            command: RockPaperScissors::NAME.to_string(),
+            guild_id: None,
+            channel_id: None,
+            message_id: MessageId::new(3),
+            challenger: CHALLENGER,
+            opponent: OPPONENT,
+            options: BTreeMap::from([("games".to_string(), games.to_string())]),
+            initial: String::new(),
+            moves: moves.iter().map(|round| round.to_string()).collect(),
+            state: String::new(),
+            finished_at: None,
+        }
+    }
+
+    #[test]
+    fn ties_do_not_count() {
+        assert_eq!(score(&[(Rock, Rock), (Paper, Paper)]), (0, 0));
+        assert_eq!(
+            score(&[(Rock, Scissors), (Scissors, Scissors), (Rock, Paper)]),
+            (1, 1)
+        );
+        assert_eq!(winner(&players(), 1, &[(Paper, Paper)]), None);
+    }
+
+    #[test]
+    fn a_majority_of_the_games_wins() {
+        let won = [(Paper, Rock), (Scissors, Paper)];
+        assert_eq!(winner(&players(), 1, &won[..1]), Some(CHALLENGER));
+        assert_eq!(winner(&players(), 3, &won[..1]), None);
+        assert_eq!(winner(&players(), 3, &won), Some(CHALLENGER));
+        assert_eq!(winner(&players(), 5, &won), None);
+        let lost = [
+            (Rock, Paper),
+            (Rock, Rock),
+            (Paper, Scissors),
+            (Rock, Paper),
+        ];
+        assert_eq!(winner(&players(), 5, &lost), Some(OPPONENT));
+        assert_eq!(winner(&players(), 7, &lost), None);
+    }
+
+    #[test]
+    fn rounds_are_parsed() {
+        assert_eq!(
+            rounds(&record(3, &["rock:paper", "scissors:scissors"])).unwrap(),
+            vec![(Rock, Paper), (Scissors, Scissors)]
+        );
+        assert!(rounds(&record(3, &["rock"])).is_err());
+        assert!(rounds(&record(3, &["rock:lizard"])).is_err());
+        assert!(rounds(&record(3, &["rock:paper:scissors"])).is_err());
+    }
+
+    #[test]
+    fn the_round_is_played_once_both_picked() {
+        let mut game = record(3, &["rock:scissors"]);
+        let Picked::Counted { winner, .. } = play(&mut game, CHALLENGER, Paper).unwrap() else {
+            panic!("The pick should count");
+        };
+        assert_eq!(winner, None);
+        assert_eq!(game.moves.len(), 1);
+        assert!(matches!(
+            play(&mut game, CHALLENGER, Rock).unwrap(),
+            Picked::Already(Paper)
+        ));
+        assert!(matches!(
+            play(&mut game, UserId::new(4), Rock).unwrap(),
+            Picked::NotPlaying
+        ));
+        let Picked::Counted { winner, .. } = play(&mut game, OPPONENT, Rock).unwrap() else {
+            panic!("The pick should count");
+        };
+        assert_eq!(winner, Some((CHALLENGER, OPPONENT)));
+        assert_eq!(game.moves, ["rock:scissors", "paper:rock"]);
+        assert!(game.options.keys().all(|key| !key.starts_with("pick_")));
+        assert!(game.finished_at.is_some());
+        assert!(matches!(
+            play(&mut game, OPPONENT, Rock).unwrap(),
+            Picked::Over
+        ));
+    }
+}
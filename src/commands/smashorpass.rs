@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use super::*;
 
 pub struct SmashOrPass;
@@ -43,17 +45,57 @@ impl CustomCommand for SmashOrPass {
             .await
             .unwrap();
 
-        let response = command.get_response(&ctx).await?;
+        let response = response_message(&ctx, &command).await?;
         println!("response in callback {:?}", &response);
-        let smash_react = response.react(&ctx, '🥵').await;
-        let pass_react = response.react(&ctx, '😒').await;
+        let smash_react = response.react(&ctx, SMASH).await;
+        let pass_react = response.react(&ctx, PASS).await;
 
         match (smash_react, pass_react) {
             (Ok(_), Ok(_)) => {
                 println!("Added reactions to 'smashorpass' message");
+                Collectors::get(&ctx)
+                    .await
+                    .track(Self::NAME, &response, VOTE_TTL)
+                    .await;
                 Ok(())
             }
             _ => Err(anyhow!("Failed to reactions to 'smashorpass' message")),
         }
     }
+
+    async fn on_expire(ctx: Context, collector: Collector) -> Result<()> {
+        let mut message = collector
+            .channel_id
+            .message(&ctx, collector.message_id)
+            .await?;
+        // The bot's own reactions are part of the count
+        let votes = |emoji: char| {
+            message
+                .reactions
+                .iter()
+                .find(|reaction| reaction.reaction_type.unicode_eq(&emoji.to_string()))
+                .map_or(0, |reaction| reaction.count.saturating_sub(1))
+        };
+        let (smash, pass) = (votes(SMASH), votes(PASS));
+        let content = MessageBuilder::default()
+            .push_line(&message.content)
+            .push("Voting closed: ")
+            .push_bold(match smash.cmp(&pass) {
+                std::cmp::Ordering::Greater => "Smash",
+                std::cmp::Ordering::Less => "Pass",
+                std::cmp::Ordering::Equal => "Undecided",
+            })
+            .push(format!(" ({} {} / {} {})", SMASH, smash, PASS, pass))
+            .build();
+        message
+            .edit(&ctx, EditMessage::new().content(content))
+            .await?;
+        Ok(())
+    }
 }
+
+const SMASH: char = '🥵';
+const PASS: char = '😒';
+
+/// How long votes are counted before the result is announced
+const VOTE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
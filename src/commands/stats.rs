@@ -0,0 +1,529 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::shards::Sharding;
+use crate::store::Persisted;
+
+use super::game;
+use super::rps::RockPaperScissors;
+use super::tictactoe::TicTacToe;
+use super::*;
+
+/// Rating of players without games
+const INITIAL_RATING: f64 = 1000.0;
+/// How much a single game can move a rating
+const K_FACTOR: f64 = 32.0;
+/// Players per leaderboard page
+const PAGE_SIZE: usize = 10;
+/// Opponents listed under head-to-head in `/stats`
+const HEAD_TO_HEAD_LIMIT: usize = 5;
+
+/// Games with a leaderboard, the `NAME` of the game command and its subcommand
+const GAMES: [(&str, &str); 2] = [
+    (TicTacToe::NAME, "tictactoe"),
+    (RockPaperScissors::NAME, "rockpaperscissors"),
+];
+
+/// How a game between two users ended
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+    Win,
+    Tie,
+    /// The loser gave up or ran out of time
+    Forfeit,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlayerStats {
+    pub wins: u32,
+    pub losses: u32,
+    pub ties: u32,
+    /// Games given up, not counted in `losses`
+    pub forfeits: u32,
+    pub rating: f64,
+    /// Positive while winning, negative while losing, zero after a tie
+    pub streak: i32,
+    pub best_streak: u32,
+    pub opponents: HashMap<UserId, HeadToHead>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HeadToHead {
+    pub wins: u32,
+    pub losses: u32,
+    pub ties: u32,
+}
+
+impl PlayerStats {
+    fn new() -> Self {
+        Self {
+            rating: INITIAL_RATING,
+            ..Default::default()
+        }
+    }
+
+    pub fn games(&self) -> u32 {
+        self.wins + self.losses + self.ties + self.forfeits
+    }
+
+    pub fn win_rate(&self) -> f64 {
+        match self.games() {
+            0 => 0.0,
+            games => self.wins as f64 / games as f64,
+        }
+    }
+
+    /// Apply a game that ended with `score`, 1 for a win, 0.5 for a tie and 0 for a loss
+    fn update(&mut self, opponent: UserId, opponent_rating: f64, score: f64, forfeit: bool) {
+        let expected = 1.0 / (1.0 + 10f64.powf((opponent_rating - self.rating) / 400.0));
+        self.rating += K_FACTOR * (score - expected);
+
+        let head_to_head = self.opponents.entry(opponent).or_default();
+        if score == 1.0 {
+            self.wins += 1;
+            head_to_head.wins += 1;
+            self.streak = self.streak.max(0) + 1;
+            self.best_streak = self.best_streak.max(self.streak as u32);
+        } else if score == 0.0 {
+            if forfeit {
+                self.forfeits += 1;
+            } else {
+                self.losses += 1;
+            }
+            head_to_head.losses += 1;
+            self.streak = self.streak.min(0) - 1;
+        } else {
+            self.ties += 1;
+            head_to_head.ties += 1;
+            self.streak = 0;
+        }
+    }
+}
+
+/// Players of every game in a guild, by the `NAME` of the game command
+type GuildStats = HashMap<String, HashMap<UserId, PlayerStats>>;
+
+/// Game results and ratings per guild
+pub struct Stats {
+    guilds: Mutex<HashMap<Option<GuildId>, GuildStats>>,
+    store: Persisted<GuildStats>,
+}
+
+impl TypeMapKey for Stats {
+    type Value = Arc<Stats>;
+}
+
+impl Stats {
+    pub fn load(sharding: &Sharding) -> Self {
+        let store: Persisted<GuildStats> = Persisted::new("stats");
+        Self {
+            guilds: Mutex::new(store.load(sharding)),
+            store,
+        }
+    }
+
+    pub async fn get(ctx: &Context) -> Arc<Self> {
+        ctx.data
+            .read()
+            .await
+            .get::<Self>()
+            .cloned()
+            .expect("Stats to be inserted into the client data")
+    }
+
+    /// Record a game of `game` won by `winner`, or a tie when the outcome is [`Outcome::Tie`]
+    pub async fn record(
+        &self,
+        guild_id: Option<GuildId>,
+        game: &str,
+        winner: UserId,
+        loser: UserId,
+        outcome: Outcome,
+    ) {
+        // Games against yourself say nothing about how well you play
+        if winner == loser {
+            return;
+        }
+        let mut guilds = self.guilds.lock().await;
+        let guild = guilds.entry(guild_id).or_default();
+        let players = guild.entry(game.to_string()).or_default();
+        let winner_rating = players
+            .get(&winner)
+            .map_or(INITIAL_RATING, |stats| stats.rating);
+        let loser_rating = players
+            .get(&loser)
+            .map_or(INITIAL_RATING, |stats| stats.rating);
+        let (winner_score, loser_score) = match outcome {
+            Outcome::Win | Outcome::Forfeit => (1.0, 0.0),
+            Outcome::Tie => (0.5, 0.5),
+        };
+        players
+            .entry(winner)
+            .or_insert_with(PlayerStats::new)
+            .update(loser, loser_rating, winner_score, false);
+        players
+            .entry(loser)
+            .or_insert_with(PlayerStats::new)
+            .update(
+                winner,
+                winner_rating,
+                loser_score,
+                outcome == Outcome::Forfeit,
+            );
+        self.store.save(guild_id, guild);
+    }
+
+    async fn player(
+        &self,
+        guild_id: Option<GuildId>,
+        game: &str,
+        user_id: UserId,
+    ) -> Option<PlayerStats> {
+        self.guilds
+            .lock()
+            .await
+            .get(&guild_id)?
+            .get(game)?
+            .get(&user_id)
+            .cloned()
+    }
+
+    /// Every player of `game` in the guild, best rating first
+    async fn ranking(&self, guild_id: Option<GuildId>, game: &str) -> Vec<(UserId, PlayerStats)> {
+        let guilds = self.guilds.lock().await;
+        let mut players: Vec<_> = guilds
+            .get(&guild_id)
+            .and_then(|guild| guild.get(game))
+            .map(|players| {
+                players
+                    .iter()
+                    .map(|(&user_id, stats)| (user_id, stats.clone()))
+                    .collect()
+            })
+            .unwrap_or_default();
+        players.sort_by(|(_, a), (_, b)| b.rating.total_cmp(&a.rating));
+        players
+    }
+}
+
+pub struct ShowStats;
+
+#[async_trait]
+impl CustomCommand for ShowStats {
+    const NAME: &'static str = "stats";
+
+    fn command() -> CreateCommand {
+        CreateCommand::new(Self::NAME)
+            .description("Show the game statistics of a player")
+            .add_option(CreateCommandOption::new(
+                CommandOptionType::User,
+                "user",
+                "Whose statistics to show, yours when left out",
+            ))
+            .to_owned()
+    }
+
+    async fn slash(ctx: Context, command: CommandInteraction) -> Result<()> {
+        let user_id = match command.data.options().first().map(|option| &option.value) {
+            Some(ResolvedValue::User(user, _)) => user.id,
+            _ => command.user.id,
+        };
+        let stats = Stats::get(&ctx).await;
+
+        let mut msg = MessageBuilder::default();
+        msg.push("Statistics of ").mention(&user_id).push_line("");
+        let mut any = false;
+        for (game, _) in GAMES {
+            let Some(player) = stats.player(command.guild_id, game, user_id).await else {
+                continue;
+            };
+            any = true;
+            msg.push_bold_line(game)
+                .push_line(format!(
+                    "Rating {:.0}, {} games: {} won, {} lost, {} tied, {} forfeited",
+                    player.rating,
+                    player.games(),
+                    player.wins,
+                    player.losses,
+                    player.ties,
+                    player.forfeits
+                ))
+                .push_line(format!(
+                    "Win rate {:.0}%, current streak {}, best winning streak {}",
+                    player.win_rate() * 100.0,
+                    match player.streak {
+                        0 => "none".to_string(),
+                        streak if streak > 0 => format!("{} won", streak),
+                        streak => format!("{} lost", -streak),
+                    },
+                    player.best_streak
+                ));
+            let mut opponents: Vec<_> = player.opponents.iter().collect();
+            opponents.sort_by_key(|(_, record)| {
+                std::cmp::Reverse(record.wins + record.losses + record.ties)
+            });
+            for (opponent, record) in opponents.into_iter().take(HEAD_TO_HEAD_LIMIT) {
+                msg.push("- against ").mention(opponent).push_line(format!(
+                    ": {} won, {} lost, {} tied",
+                    record.wins, record.losses, record.ties
+                ));
+            }
+        }
+        if !any {
+            msg.push("No games played yet");
+        }
+
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content(msg.build())
+                        .allowed_mentions(CreateAllowedMentions::new()),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+pub struct Leaderboard;
+
+#[async_trait]
+impl CustomCommand for Leaderboard {
+    const NAME: &'static str = "leaderboard";
+
+    fn command() -> CreateCommand {
+        GAMES
+            .into_iter()
+            .fold(
+                CreateCommand::new(Self::NAME).description("Show the best rated players"),
+                |command, (game, subcommand)| {
+                    command.add_option(CreateCommandOption::new(
+                        CommandOptionType::SubCommand,
+                        subcommand,
+                        format!("Ratings for {}", game),
+                    ))
+                },
+            )
+            .to_owned()
+    }
+
+    async fn slash(ctx: Context, command: CommandInteraction) -> Result<()> {
+        let subcommand = command
+            .data
+            .options()
+            .first()
+            .map(|option| option.name)
+            .ok_or(anyhow!("No leaderboard subcommand"))?;
+        let (game, _) = GAMES
+            .into_iter()
+            .find(|(_, name)| *name == subcommand)
+            .ok_or(anyhow!("Unknown leaderboard: {}", subcommand))?;
+        let response = leaderboard(&ctx, command.guild_id, game, 0).await;
+        command
+            .create_response(&ctx, CreateInteractionResponse::Message(response))
+            .await?;
+        Ok(())
+    }
+
+    async fn component(ctx: Context, interaction: ComponentInteraction) -> Result<()> {
+        // leaderboard_<game>_<page>_<prev or next>
+        let mut parts = interaction.data.custom_id.split('_').skip(1);
+        let (Some(game), Some(page)) = (parts.next(), parts.next()) else {
+            bail!(
+                "Malformed leaderboard customId: {}",
+                interaction.data.custom_id
+            );
+        };
+        let Some((game, _)) = GAMES.into_iter().find(|(name, _)| *name == game) else {
+            return game::reply_ephemeral(&ctx, &interaction, "That leaderboard no longer exists")
+                .await;
+        };
+        let response = leaderboard(&ctx, interaction.guild_id, game, page.parse()?).await;
+        interaction
+            .create_response(&ctx, CreateInteractionResponse::UpdateMessage(response))
+            .await?;
+        Ok(())
+    }
+}
+
+async fn leaderboard(
+    ctx: &Context,
+    guild_id: Option<GuildId>,
+    game: &str,
+    page: usize,
+) -> CreateInteractionResponseMessage {
+    let ranking = Stats::get(ctx).await.ranking(guild_id, game).await;
+    let pages = ranking.len().div_ceil(PAGE_SIZE).max(1);
+    let page = page.min(pages - 1);
+
+    let mut msg = MessageBuilder::default();
+    msg.push_bold_line(format!("{} leaderboard", game));
+    if ranking.is_empty() {
+        msg.push("No games played yet");
+    }
+    for (rank, (user_id, player)) in ranking
+        .iter()
+        .enumerate()
+        .skip(page * PAGE_SIZE)
+        .take(PAGE_SIZE)
+    {
+        msg.push(format!("{}. ", rank + 1))
+            .mention(user_id)
+            .push_line(format!(
+                " {:.0} ({} games, {:.0}% won)",
+                player.rating,
+                player.games(),
+                player.win_rate() * 100.0
+            ));
+    }
+    if pages > 1 {
+        msg.push_italic(format!("Page {} of {}", page + 1, pages));
+    }
+
+    CreateInteractionResponseMessage::new()
+        .content(msg.build())
+        .allowed_mentions(CreateAllowedMentions::new())
+        .components(vec![CreateActionRow::Buttons(vec![
+            CreateButton::new(format!(
+                "{}_{}_{}_prev",
+                Leaderboard::NAME,
+                game,
+                page.saturating_sub(1)
+            ))
+            .label("Previous")
+            .style(ButtonStyle::Secondary)
+            .disabled(page == 0),
+            CreateButton::new(format!("{}_{}_{}_next", Leaderboard::NAME, game, page + 1))
+                .label("Next")
+                .style(ButtonStyle::Secondary)
+                .disabled(page + 1 >= pages),
+        ])])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GAME: &str = TicTacToe::NAME;
+    const ALICE: UserId = UserId::new(1);
+    const BOB: UserId = UserId::new(2);
+
+    fn stats() -> Stats {
+        Stats {
+            guilds: Mutex::new(HashMap::new()),
+            store: Persisted::new("stats"),
+        }
+    }
+
+    async fn player(stats: &Stats, user_id: UserId) -> PlayerStats {
+        stats.player(None, GAME, user_id).await.unwrap()
+    }
+
+    fn assert_rating(player: &PlayerStats, rating: f64) {
+        assert!(
+            (player.rating - rating).abs() < 0.01,
+            "{} instead of {}",
+            player.rating,
+            rating
+        );
+    }
+
+    #[test]
+    fn expected_score_decides_the_rating_change() {
+        // A player rated 200 lower is expected to score 0.24, so a tie gains 32 * 0.26
+        let mut player = PlayerStats::new();
+        player.update(BOB, 1200.0, 0.5, false);
+        assert_rating(&player, 1008.31);
+        assert_eq!((player.ties, player.streak), (1, 0));
+        assert_eq!(player.opponents[&BOB].ties, 1);
+    }
+
+    #[tokio::test]
+    async fn equal_players_exchange_half_of_k() {
+        let stats = stats();
+        stats.record(None, GAME, ALICE, BOB, Outcome::Win).await;
+        assert_rating(
+            &player(&stats, ALICE).await,
+            INITIAL_RATING + K_FACTOR / 2.0,
+        );
+        assert_rating(&player(&stats, BOB).await, INITIAL_RATING - K_FACTOR / 2.0);
+
+        // The favourite gains less for beating the same player again
+        stats.record(None, GAME, ALICE, BOB, Outcome::Win).await;
+        assert_rating(&player(&stats, ALICE).await, 1030.53);
+        assert_rating(&player(&stats, BOB).await, 969.47);
+    }
+
+    #[tokio::test]
+    async fn ties_move_ratings_towards_each_other() {
+        let stats = stats();
+        stats.record(None, GAME, ALICE, BOB, Outcome::Win).await;
+        stats.record(None, GAME, BOB, ALICE, Outcome::Tie).await;
+        let (alice, bob) = (player(&stats, ALICE).await, player(&stats, BOB).await);
+        assert!(alice.rating < INITIAL_RATING + K_FACTOR / 2.0);
+        assert!(bob.rating > INITIAL_RATING - K_FACTOR / 2.0);
+        // Ratings only move between the players
+        assert_rating(&alice, 2.0 * INITIAL_RATING - bob.rating);
+        assert_eq!((alice.ties, bob.ties), (1, 1));
+        assert_eq!((alice.streak, bob.streak), (0, 0));
+        assert_eq!(alice.best_streak, 1);
+    }
+
+    #[tokio::test]
+    async fn a_loss_breaks_the_streak() {
+        let stats = stats();
+        for _ in 0..3 {
+            stats.record(None, GAME, ALICE, BOB, Outcome::Win).await;
+        }
+        assert_eq!(player(&stats, ALICE).await.streak, 3);
+        assert_eq!(player(&stats, BOB).await.streak, -3);
+        stats.record(None, GAME, BOB, ALICE, Outcome::Win).await;
+        let (alice, bob) = (player(&stats, ALICE).await, player(&stats, BOB).await);
+        assert_eq!((alice.streak, alice.best_streak), (-1, 3));
+        assert_eq!((bob.streak, bob.best_streak), (1, 1));
+    }
+
+    #[tokio::test]
+    async fn a_forfeit_counts_as_a_loss() {
+        let stats = stats();
+        stats.record(None, GAME, ALICE, BOB, Outcome::Forfeit).await;
+        let (alice, bob) = (player(&stats, ALICE).await, player(&stats, BOB).await);
+        assert_eq!((alice.wins, alice.streak), (1, 1));
+        assert_eq!((bob.forfeits, bob.losses, bob.streak), (1, 0, -1));
+        assert_eq!(bob.games(), 1);
+        assert_eq!(bob.opponents[&ALICE].losses, 1);
+        // Rated like any other loss
+        assert_rating(&bob, INITIAL_RATING - K_FACTOR / 2.0);
+    }
+
+    #[tokio::test]
+    async fn head_to_head_is_kept_per_opponent() {
+        let stats = stats();
+        let carol = UserId::new(3);
+        stats.record(None, GAME, ALICE, BOB, Outcome::Win).await;
+        stats.record(None, GAME, BOB, ALICE, Outcome::Win).await;
+        stats.record(None, GAME, ALICE, BOB, Outcome::Tie).await;
+        stats.record(None, GAME, ALICE, carol, Outcome::Win).await;
+        let alice = player(&stats, ALICE).await;
+        let bob = &alice.opponents[&BOB];
+        assert_eq!((bob.wins, bob.losses, bob.ties), (1, 1, 1));
+        assert_eq!(alice.opponents[&carol].wins, 1);
+        assert_eq!(alice.games(), 4);
+        let bob = &player(&stats, BOB).await.opponents[&ALICE];
+        assert_eq!((bob.wins, bob.losses, bob.ties), (1, 1, 1));
+        assert_eq!(player(&stats, carol).await.opponents[&ALICE].losses, 1);
+    }
+
+    #[tokio::test]
+    async fn games_against_yourself_are_not_counted() {
+        let stats = stats();
+        stats.record(None, GAME, ALICE, ALICE, Outcome::Win).await;
+        assert!(stats.player(None, GAME, ALICE).await.is_none());
+    }
+}
@@ -1,263 +1,2058 @@
 use std::collections::HashMap;
 use std::fmt::Display;
+use std::time::Duration;
 
+use serde::{Deserialize, Serialize};
+
+use crate::engine::tictactoe::Board;
+use crate::engine::{self, Game, Player};
+
+use super::game::{self, GameRecord, Games, Players, Position};
+use super::stats::{Outcome, Stats};
 use super::*;
 
-#[derive(Debug, Default)]
+mod ai;
+
+use ai::Difficulty;
+
+#[derive(Debug)]
 pub struct TicTacToe {
+    variant: Variant,
     state: HashMap<Coord, Tile>,
     winning: Option<Winning>,
     next_turn: Player,
 }
 
-#[derive(Default, Debug, Clone, Copy, PartialEq)]
+/// X moves first
+fn next_player(variant: Variant, state: &HashMap<Coord, Tile>) -> Player {
+    variant.board(state).to_move()
+}
 
-enum Player {
-    #[default]
-    Opponent,
+/// Who the challenger wants to move first
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+enum FirstPlayer {
     Challenger,
+    Opponent,
+    #[default]
+    Random,
+}
+
+impl FirstPlayer {
+    const ALL: [FirstPlayer; 3] = [
+        FirstPlayer::Challenger,
+        FirstPlayer::Opponent,
+        FirstPlayer::Random,
+    ];
+
+    /// Shown to the challenger when choosing
+    fn description(&self) -> &'static str {
+        match self {
+            FirstPlayer::Challenger => "Me",
+            FirstPlayer::Opponent => "My opponent",
+            FirstPlayer::Random => "Random",
+        }
+    }
+
+    fn pick(&self, players: &Players) -> UserId {
+        match self {
+            FirstPlayer::Challenger => players.challenger,
+            FirstPlayer::Opponent => players.opponent,
+            FirstPlayer::Random if rand::random() => players.challenger,
+            FirstPlayer::Random => players.opponent,
+        }
+    }
+}
+
+impl TryFrom<&str> for FirstPlayer {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> std::result::Result<Self, Self::Error> {
+        match value {
+            "challenger" => Ok(Self::Challenger),
+            "opponent" => Ok(Self::Opponent),
+            "random" => Ok(Self::Random),
+            _ => Err(anyhow!(
+                "Could not parse FirstPlayer from String: {}",
+                value
+            )),
+        }
+    }
+}
+
+impl Display for FirstPlayer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            FirstPlayer::Challenger => "challenger",
+            FirstPlayer::Opponent => "opponent",
+            FirstPlayer::Random => "random",
+        })
+    }
+}
+
+/// The users playing X and O
+#[derive(Debug, Clone, Copy)]
+struct Seats {
+    first: UserId,
+    second: UserId,
+}
+
+impl Seats {
+    fn new(players: &Players, first: UserId) -> Self {
+        let second = if first == players.challenger {
+            players.opponent
+        } else {
+            players.challenger
+        };
+        Self { first, second }
+    }
+
+    /// Games started before the first player could be chosen were opened by the opponent
+    fn of(record: &GameRecord) -> Self {
+        let first = record
+            .options
+            .get("first")
+            .and_then(|id| id.parse().ok())
+            .map_or(record.opponent, UserId::new);
+        Self::new(&record.players(), first)
+    }
+
+    fn user(&self, player: Player) -> UserId {
+        match player {
+            Player::First => self.first,
+            Player::Second => self.second,
+        }
+    }
+}
+
+impl TicTacToe {
+    fn new(variant: Variant, mut state: HashMap<Coord, Tile>, clicked_tile: Option<Coord>) -> Self {
+        if let Some(clicked) = clicked_tile {
+            state.insert(clicked, next_player(variant, &state).into());
+        }
+        Self {
+            next_turn: next_player(variant, &state),
+            winning: calculate_winner(variant, &state),
+            variant,
+            state,
+        }
+    }
+}
+
+#[async_trait]
+impl CustomCommand for TicTacToe {
+    const NAME: &'static str = "TicTacToe";
+    fn command() -> CreateCommand {
+        CreateCommand::new(Self::NAME)
+            .kind(CommandType::User)
+            .to_owned()
+    }
+
+    async fn slash(ctx: Context, command: CommandInteraction) -> Result<()> {
+        if let Some(ResolvedTarget::User(target, _)) = command.data.target() {
+            challenge(
+                &ctx,
+                &command,
+                target,
+                Variant::default(),
+                Difficulty::default(),
+                FirstPlayer::default(),
+                None,
+            )
+            .await
+        } else {
+            Err(anyhow!("No user for the user command tictactoe"))
+        }
+    }
+
+    async fn component(ctx: Context, interaction: ComponentInteraction) -> Result<()> {
+        // Answers to an undo request come from an ephemeral message
+        for (prefix, approved) in [(APPROVE_UNDO_ID, true), (REJECT_UNDO_ID, false)] {
+            if interaction.data.custom_id.starts_with(prefix) {
+                return answer_undo(&ctx, &interaction, approved).await;
+            }
+        }
+
+        let games = Games::get(&ctx).await;
+        let record = games.find(interaction.message.id).await;
+        let players = match &record {
+            Some(record) => record.players(),
+            None => Players::from_message(&interaction.message)?,
+        };
+
+        // Anyone may export the moves
+        if interaction.data.custom_id == EXPORT_ID {
+            let content = match &record {
+                Some(record) => MessageBuilder::default()
+                    .push_codeblock_safe(notation(record)?, None)
+                    .build(),
+                None => "No moves were recorded for this game".to_string(),
+            };
+            return game::reply_ephemeral(&ctx, &interaction, content).await;
+        }
+
+        if !players.contains(interaction.user.id) {
+            return game::reply_ephemeral(&ctx, &interaction, "You are not part of this game")
+                .await;
+        }
+
+        if interaction.data.custom_id == ACCEPT_ID || interaction.data.custom_id == DECLINE_ID {
+            return answer_invitation(&ctx, &interaction, record).await;
+        }
+
+        if interaction.data.custom_id == REMATCH_ID {
+            return rematch(&ctx, &interaction).await;
+        }
+
+        if interaction.data.custom_id == UNDO_ID {
+            return undo(&ctx, &interaction).await;
+        }
+
+        if interaction.data.custom_id == TYPE_ID {
+            interaction
+                .create_response(
+                    &ctx,
+                    CreateInteractionResponse::Modal(
+                        CreateModal::new(TYPE_ID, "Your move").components(vec![
+                            CreateActionRow::InputText(
+                                CreateInputText::new(InputTextStyle::Short, "Square", "square")
+                                    .placeholder("Like b2 or top left")
+                                    .required(true),
+                            ),
+                        ]),
+                    ),
+                )
+                .await?;
+            return Ok(());
+        }
+
+        if interaction.data.custom_id == REMOVE_ID {
+            // Removing a game that is being played gives it up
+            if let Some(record) = &record {
+                if record.finished_at.is_none()
+                    && !record.moves.is_empty()
+                    && record_difficulty(record)?.is_none()
+                {
+                    let winner = if interaction.user.id == players.challenger {
+                        players.opponent
+                    } else {
+                        players.challenger
+                    };
+                    Stats::get(&ctx)
+                        .await
+                        .record(
+                            interaction.guild_id,
+                            Self::NAME,
+                            winner,
+                            interaction.user.id,
+                            Outcome::Forfeit,
+                        )
+                        .await;
+                }
+            }
+            cancel_turn_timer(&ctx, interaction.message.id).await;
+            return game::remove(&ctx, &interaction).await;
+        }
+
+        let (variant, clicked_coord) = parse_tile_id(&interaction.data.custom_id)?;
+        // Games started before they were stored are read from the message
+        if record.is_none() {
+            let state = scrape_state(&interaction.message.components);
+            games
+                .insert_new(legacy_record(
+                    &interaction.message,
+                    &players,
+                    variant,
+                    &state,
+                ))
+                .await;
+        }
+        match play(
+            &ctx,
+            &interaction.message,
+            interaction.user.id,
+            clicked_coord,
+        )
+        .await?
+        {
+            Ok(update) => {
+                interaction
+                    .create_response(
+                        &ctx,
+                        CreateInteractionResponse::UpdateMessage(update.response()),
+                    )
+                    .await?;
+                Ok(())
+            }
+            Err(reason) => game::reply_ephemeral(&ctx, &interaction, reason).await,
+        }
+    }
+
+    /// A move typed into the modal of the Type move button
+    async fn modal(ctx: Context, submit: ModalInteraction) -> Result<()> {
+        let Some(message) = &submit.message else {
+            bail!("The TicTacToe modal was not opened from a game message");
+        };
+        let square = submit
+            .data
+            .components
+            .iter()
+            .flat_map(|row| &row.components)
+            .find_map(|component| match component {
+                ActionRowComponent::InputText(input) => input.value.clone(),
+                _ => None,
+            })
+            .unwrap_or_default();
+        let record = Games::get(&ctx).await.find(message.id).await;
+        let response = match typed_move(&ctx, message, record, submit.user.id, &square).await? {
+            Ok(update) => CreateInteractionResponse::UpdateMessage(update.response()),
+            Err(reason) => CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content(reason)
+                    .ephemeral(true),
+            ),
+        };
+        submit.create_response(&ctx, response).await?;
+        Ok(())
+    }
+
+    async fn on_expire(ctx: Context, collector: Collector) -> Result<()> {
+        let mut message = collector
+            .channel_id
+            .message(&ctx, collector.message_id)
+            .await?;
+        let games = Games::get(&ctx).await;
+        let invitation = games
+            .find(collector.message_id)
+            .await
+            .is_some_and(|record| is_invitation(&record));
+        let (status, components) = if invitation {
+            ("The challenge expired without an answer", Vec::new())
+        } else {
+            (
+                "The game has expired",
+                collectors::disabled_components(&message.components),
+            )
+        };
+        let content = MessageBuilder::default()
+            .push_line(message.content.split_once("\n").unwrap_or_default().0)
+            .push(status)
+            .build();
+        message
+            .edit(
+                &ctx,
+                EditMessage::new().content(content).components(components),
+            )
+            .await?;
+        games.remove(collector.message_id).await;
+        cancel_turn_timer(&ctx, collector.message_id).await;
+        Ok(())
+    }
+
+    /// Reminds the player to move at half time and ends the game when time runs out
+    async fn run_job(ctx: Context, job: Job) -> Result<()> {
+        let timer: TurnTimer = serde_json::from_str(&job.payload)?;
+        let games = Games::get(&ctx).await;
+
+        if timer.reminder {
+            let Some(record) = games.find(timer.message_id).await else {
+                return Ok(());
+            };
+            // A move was made since the timer was started
+            if record.finished_at.is_some() || record.moves.len() != timer.moves {
+                return Ok(());
+            }
+            let variant = record_variant(&record)?;
+            let to_move = Seats::of(&record)
+                .user(next_player(variant, &decode_state(variant, &record.state)?));
+            let content = MessageBuilder::default()
+                .mention(&to_move)
+                .push(format!(
+                    " it is your turn in TicTacToe, the game is forfeited <t:{}:R>",
+                    timer.deadline
+                ))
+                .build();
+            timer
+                .channel_id
+                .send_message(
+                    &ctx,
+                    CreateMessage::new()
+                        .content(content)
+                        .reference_message((timer.channel_id, timer.message_id)),
+                )
+                .await?;
+            return Ok(());
+        }
+
+        let Some(Moved {
+            game,
+            seats,
+            difficulty,
+            header,
+            ..
+        }) = games
+            .update(timer.message_id, |record| forfeit(record, timer.moves))
+            .await
+            .transpose()?
+            .flatten()
+        else {
+            return Ok(());
+        };
+        let to_move = seats.user(game.next_turn);
+        let other = seats.user(game.next_turn.other());
+        if difficulty.is_none() {
+            Stats::get(&ctx)
+                .await
+                .record(job.guild_id, Self::NAME, other, to_move, Outcome::Forfeit)
+                .await;
+        }
+        Collectors::get(&ctx).await.untrack(timer.message_id).await;
+
+        let mut message = timer.channel_id.message(&ctx, timer.message_id).await?;
+        let content = MessageBuilder::default()
+            .push_line(
+                header
+                    .as_deref()
+                    .unwrap_or(message.content.split_once("\n").unwrap_or_default().0),
+            )
+            .push(Tile::from(game.next_turn.other()).to_string())
+            .mention(&other)
+            .push(" wins by forfeit, ")
+            .mention(&to_move)
+            .push(" ran out of time")
+            .build();
+        message
+            .edit(
+                &ctx,
+                EditMessage::new()
+                    .content(content)
+                    .components(create_components(&game)),
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+/// End the game of the player that ran out of time, `None` when a move was made since the
+/// timer started
+fn forfeit(record: &mut GameRecord, moves: usize) -> Result<Option<Moved>> {
+    if record.finished_at.is_some() || record.moves.len() != moves {
+        return Ok(None);
+    }
+    let variant = record_variant(record)?;
+    let mut game = TicTacToe::new(variant, decode_state(variant, &record.state)?, None);
+    let seats = Seats::of(record);
+    record.options.insert(
+        "forfeited_by".to_string(),
+        seats.user(game.next_turn).to_string(),
+    );
+    let header = score_series(record, Some(seats.user(game.next_turn.other())))?;
+    record.finish();
+    game.winning = Some(Winning::Forfeit);
+    Ok(Some(Moved {
+        game,
+        seats,
+        difficulty: record_difficulty(record)?,
+        moves,
+        header,
+    }))
+}
+
+/// A move made in a stored game, with what is needed to show it once the lock is released
+#[derive(Debug)]
+struct Moved {
+    game: TicTacToe,
+    seats: Seats,
+    difficulty: Option<Difficulty>,
+    /// Moves in the record after this one
+    moves: usize,
+    /// The new first line of the message when the move ended a game of a series
+    header: Option<String>,
+}
+
+/// Take `coord` for `user` in the stored game, or why the move cannot be made. The record is
+/// left alone when the move is refused
+fn take_tile(
+    record: &mut GameRecord,
+    user: UserId,
+    coord: Coord,
+) -> Result<std::result::Result<Moved, &'static str>> {
+    if is_invitation(record) {
+        return Ok(Err("The challenge has not been accepted yet"));
+    }
+    if record.finished_at.is_some() {
+        return Ok(Err("The game is over"));
+    }
+    let variant = record_variant(record)?;
+    let state = decode_state(variant, &record.state)?;
+    if state.get(&coord) != Some(&Tile::Empty) {
+        return Ok(Err("That tile is already taken"));
+    }
+    if user != Seats::of(record).user(next_player(variant, &state)) {
+        return Ok(Err("Its not your turn"));
+    }
+    Ok(Ok(make_move(record, variant, state, coord)?))
+}
+
+/// The bot's answer to the move that left `moves` moves in the record, `None` when the game
+/// changed while the bot was thinking
+fn answer_move(record: &mut GameRecord, moves: usize, coord: Coord) -> Result<Option<Moved>> {
+    if record.finished_at.is_some() || record.moves.len() != moves {
+        return Ok(None);
+    }
+    let variant = record_variant(record)?;
+    let state = decode_state(variant, &record.state)?;
+    make_move(record, variant, state, coord).map(Some)
+}
+
+/// Add `coord` to the record, scoring and finishing the game when the move ends it
+fn make_move(
+    record: &mut GameRecord,
+    variant: Variant,
+    state: HashMap<Coord, Tile>,
+    coord: Coord,
+) -> Result<Moved> {
+    let game = TicTacToe::new(variant, state, Some(coord));
+    let seats = Seats::of(record);
+    record.moves.push(coord.to_string());
+    record.state = encode_state(variant, &game.state);
+    let header = match &game.winning {
+        Some(winning) => {
+            // The player that moved last made the line
+            let winner = seats.user(game.next_turn.other());
+            let header =
+                score_series(record, (!matches!(winning, Winning::Tie)).then_some(winner))?;
+            record.finish();
+            header
+        }
+        None => None,
+    };
+    Ok(Moved {
+        game,
+        seats,
+        difficulty: record_difficulty(record)?,
+        moves: record.moves.len(),
+        header,
+    })
+}
+
+/// Take `coord` for `user` and answer for the bot, the updated game message or why the move
+/// cannot be made
+async fn play(
+    ctx: &Context,
+    message: &Message,
+    user: UserId,
+    coord: Coord,
+) -> Result<std::result::Result<GameMessage, &'static str>> {
+    let games = Games::get(ctx).await;
+    let moved = games
+        .update(message.id, |record| take_tile(record, user, coord))
+        .await
+        .transpose()?;
+    let mut moved = match moved {
+        Some(Ok(moved)) => moved,
+        Some(Err(reason)) => return Ok(Err(reason)),
+        None => return Ok(Err("The game is no longer recorded")),
+    };
+
+    // The bot answers in the same response, it thinks without holding the lock
+    if let (Some(difficulty), None) = (moved.difficulty, &moved.game.winning) {
+        let answer = ai::best_move(moved.game.variant, &moved.game.state, difficulty).await?;
+        if let Some(coord) = answer {
+            let moves = moved.moves;
+            let answered = games
+                .update(message.id, |record| answer_move(record, moves, coord))
+                .await
+                .transpose()?
+                .flatten();
+            match answered {
+                Some(answered) => moved = answered,
+                None => return Ok(Err("The game changed while the bot was thinking")),
+            }
+        }
+    }
+    let Moved {
+        game,
+        seats,
+        difficulty,
+        moves,
+        header,
+    } = moved;
+
+    let deadline = if let Some(winning) = &game.winning {
+        // Games against the bot are not rated
+        if difficulty.is_none() {
+            let outcome = match winning {
+                Winning::Tie => Outcome::Tie,
+                Winning::Line(_) => Outcome::Win,
+                Winning::Forfeit => Outcome::Forfeit,
+            };
+            Stats::get(ctx)
+                .await
+                .record(
+                    message.guild_id,
+                    TicTacToe::NAME,
+                    seats.user(game.next_turn.other()),
+                    seats.user(game.next_turn),
+                    outcome,
+                )
+                .await;
+        }
+        cancel_turn_timer(ctx, message.id).await;
+        None
+    } else {
+        start_turn_timer(ctx, message, moves).await?
+    };
+
+    let mut msg = MessageBuilder::default();
+
+    // Preserve first line, unless the series score changed
+    msg.push_line(
+        header
+            .as_deref()
+            .unwrap_or(message.content.split_once("\n").unwrap_or_default().0),
+    );
+
+    match &game.winning {
+        Some(winning) => match winning {
+            Winning::Tie => msg.push("The game is a tie"),
+            _ => msg
+                .push(Tile::from(game.next_turn.other()).to_string())
+                .mention(&seats.user(game.next_turn.other()))
+                .push(" is the winner!"),
+        },
+        None => msg.push(turn_line(&game, &seats)),
+    };
+    if let Some(deadline) = deadline {
+        msg.push(format!(", time runs out <t:{}:R>", deadline));
+    }
+    let collectors = Collectors::get(ctx).await;
+    if game.winning.is_some() {
+        collectors.untrack(message.id).await;
+    } else {
+        collectors.track(TicTacToe::NAME, message, GAME_TTL).await;
+    }
+    Ok(Ok(GameMessage::new(
+        ctx,
+        message.guild_id,
+        msg.build(),
+        &game,
+    )
+    .await?))
+}
+
+/// Take a square written like `b2` or `top left`, see [`Coord::from_square`]
+async fn typed_move(
+    ctx: &Context,
+    message: &Message,
+    record: Option<GameRecord>,
+    user: UserId,
+    square: &str,
+) -> Result<std::result::Result<GameMessage, String>> {
+    let Some(record) = record else {
+        return Ok(Err("The game is no longer recorded".to_string()));
+    };
+    let variant = record_variant(&record)?;
+    let coord = match Coord::from_square(variant, square) {
+        Ok(coord) => coord,
+        Err(err) => return Ok(Err(err.to_string())),
+    };
+    Ok(play(ctx, message, user, coord)
+        .await?
+        .map_err(str::to_string))
+}
+
+/// Make a move in a TicTacToe game by writing the square, for players that cannot use the
+/// buttons
+pub struct TypeMove;
+
+#[async_trait]
+impl CustomCommand for TypeMove {
+    const NAME: &'static str = "move";
+
+    fn command() -> CreateCommand {
+        CreateCommand::new(Self::NAME)
+            .description("Make a move in your TicTacToe game in this channel")
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::String,
+                    "square",
+                    "Like b2, with the column letter and the row number, or top left",
+                )
+                .required(true),
+            )
+            .to_owned()
+    }
+
+    async fn slash(ctx: Context, command: CommandInteraction) -> Result<()> {
+        let Some(ResolvedValue::String(square)) = command
+            .data
+            .options()
+            .first()
+            .map(|option| option.value.clone())
+        else {
+            bail!("No square for the move command");
+        };
+        let reply = |content: String| {
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content(content)
+                    .ephemeral(true),
+            )
+        };
+
+        // The most recent game where it is the user's turn, otherwise the most recent game
+        let mut games = Games::get(&ctx)
+            .await
+            .playing(TicTacToe::NAME, command.channel_id, command.user.id)
+            .await;
+        let mut turn = None;
+        for (i, record) in games.iter().enumerate() {
+            let variant = record_variant(record)?;
+            let state = decode_state(variant, &record.state)?;
+            if Seats::of(record).user(next_player(variant, &state)) == command.user.id {
+                turn = Some(i);
+                break;
+            }
+        }
+        if games.is_empty() {
+            let content = "You are not playing TicTacToe in this channel".to_string();
+            command.create_response(&ctx, reply(content)).await?;
+            return Ok(());
+        }
+        let record = games.swap_remove(turn.unwrap_or_default());
+
+        let mut message = command.channel_id.message(&ctx, record.message_id).await?;
+        message.guild_id = command.guild_id;
+        let content =
+            match typed_move(&ctx, &message, Some(record), command.user.id, square).await? {
+                Ok(update) => {
+                    message.edit(&ctx, update.edit()).await?;
+                    format!("You took {}", square.trim())
+                }
+                Err(reason) => reason,
+            };
+        command.create_response(&ctx, reply(content)).await?;
+        Ok(())
+    }
+}
+
+/// Slash command version of [`TicTacToe`] with options for playing against the bot
+pub struct PlayTicTacToe;
+
+#[async_trait]
+impl CustomCommand for PlayTicTacToe {
+    const NAME: &'static str = "tictactoe";
+
+    fn command() -> CreateCommand {
+        CreateCommand::new(Self::NAME)
+            .description("Challenge someone, or me, to TicTacToe")
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::User, "opponent", "Who to challenge")
+                    .required(true),
+            )
+            .add_option(Variant::ALL.into_iter().fold(
+                CreateCommandOption::new(
+                    CommandOptionType::String,
+                    "board",
+                    "The board to play on",
+                ),
+                |option, variant| {
+                    option.add_string_choice(variant.description(), variant.to_string())
+                },
+            ))
+            .add_option(Difficulty::ALL.into_iter().fold(
+                CreateCommandOption::new(
+                    CommandOptionType::String,
+                    "difficulty",
+                    "How well I play when you challenge me",
+                ),
+                |option, difficulty| {
+                    option.add_string_choice(difficulty.to_string(), difficulty.to_string())
+                },
+            ))
+            .add_option(FirstPlayer::ALL.into_iter().fold(
+                CreateCommandOption::new(
+                    CommandOptionType::String,
+                    "first",
+                    "Who moves first, random when left out",
+                ),
+                |option, first| option.add_string_choice(first.description(), first.to_string()),
+            ))
+            .add_option(Series::LENGTHS.into_iter().fold(
+                CreateCommandOption::new(
+                    CommandOptionType::Integer,
+                    "series",
+                    "Play a series of games with rematches, a single game when left out",
+                ),
+                |option, games| option.add_int_choice(format!("Best of {}", games), games as i32),
+            ))
+            .to_owned()
+    }
+
+    async fn slash(ctx: Context, command: CommandInteraction) -> Result<()> {
+        let mut target = None;
+        let mut variant = Variant::default();
+        let mut difficulty = Difficulty::default();
+        let mut first = FirstPlayer::default();
+        let mut series = None;
+        for option in command.data.options() {
+            match (option.name, option.value) {
+                ("opponent", ResolvedValue::User(user, _)) => target = Some(user.clone()),
+                ("board", ResolvedValue::String(value)) => variant = value.try_into()?,
+                ("difficulty", ResolvedValue::String(value)) => difficulty = value.try_into()?,
+                ("first", ResolvedValue::String(value)) => first = value.try_into()?,
+                ("series", ResolvedValue::Integer(games)) => {
+                    series = Some(Series::new(games.try_into()?)?)
+                }
+                _ => {}
+            }
+        }
+        let target = target.ok_or(anyhow!("No opponent for the tictactoe command"))?;
+        challenge(&ctx, &command, &target, variant, difficulty, first, series).await
+    }
+}
+
+/// Invite `target` to a game, games against the bot or yourself start right away
+async fn challenge(
+    ctx: &Context,
+    command: &CommandInteraction,
+    target: &User,
+    variant: Variant,
+    difficulty: Difficulty,
+    first: FirstPlayer,
+    series: Option<Series>,
+) -> Result<()> {
+    let against_bot = target.bot && target.id == ctx.http.get_current_user().await?.id;
+    if target.bot && !against_bot {
+        command
+            .create_response(
+                ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content("You cannot challenge another bot to TicTacToe, challenge me!")
+                        .ephemeral(true),
+                ),
+            )
+            .await?;
+        return Ok(());
+    }
+    let players = Players {
+        challenger: command.user.id,
+        opponent: target.id,
+    };
+    if against_bot || target.id == command.user.id {
+        let setup = Setup {
+            players,
+            variant,
+            difficulty: against_bot.then_some(difficulty),
+            first: first.pick(&players),
+            series,
+        };
+        let (game, content) = setup.open().await?;
+        let start = GameMessage::new(ctx, command.guild_id, content, &game).await?;
+        command
+            .create_response(ctx, CreateInteractionResponse::Message(start.response()))
+            .await?;
+        let message = response_message(ctx, command).await?;
+        return setup.begin(ctx, &message, &game).await;
+    }
+
+    let expires = Timestamp::now().unix_timestamp() + INVITE_TTL.as_secs() as i64;
+    let mut content = MessageBuilder::default();
+    content.push_line(header(&players, variant, None, series.as_ref()));
+    match first {
+        FirstPlayer::Random => content.push_line("Who moves first is decided at random"),
+        first => content
+            .mention(&first.pick(&players))
+            .push_line(" moves first"),
+    };
+    content.push(format!("The invitation expires <t:{}:R>", expires));
+    command
+        .create_response(
+            ctx,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content(content.build())
+                    .components(vec![CreateActionRow::Buttons(vec![
+                        CreateButton::new(ACCEPT_ID)
+                            .label("Accept")
+                            .style(ButtonStyle::Success),
+                        CreateButton::new(DECLINE_ID)
+                            .label("Decline")
+                            .style(ButtonStyle::Danger),
+                    ])]),
+            ),
+        )
+        .await?;
+    let message = response_message(ctx, command).await?;
+    let mut record = GameRecord::new(TicTacToe::NAME, &message, &players);
+    record
+        .options
+        .insert("variant".to_string(), variant.to_string());
+    record
+        .options
+        .insert("invitation".to_string(), first.to_string());
+    if let Some(series) = &series {
+        series.save(&mut record);
+    }
+    record.state = encode_state(variant, &variant.empty_state());
+    Games::get(ctx).await.insert(record).await;
+    Collectors::get(ctx)
+        .await
+        .track(TicTacToe::NAME, &message, INVITE_TTL)
+        .await;
+    Ok(())
+}
+
+/// A game that starts without an invitation, after a challenge of the bot or a rematch
+struct Setup {
+    players: Players,
+    variant: Variant,
+    /// Only for games against the bot, which is always the opponent
+    difficulty: Option<Difficulty>,
+    first: UserId,
+    series: Option<Series>,
+}
+
+impl Setup {
+    /// The board, with the first move made when the bot moves first, and the message content
+    async fn open(&self) -> Result<(TicTacToe, String)> {
+        let state = self.variant.empty_state();
+        let coord = match self.difficulty {
+            Some(difficulty) if self.first == self.players.opponent => {
+                ai::best_move(self.variant, &state, difficulty).await?
+            }
+            _ => None,
+        };
+        let game = TicTacToe::new(self.variant, state, coord);
+        let content = MessageBuilder::default()
+            .push_line(header(
+                &self.players,
+                self.variant,
+                self.difficulty,
+                self.series.as_ref(),
+            ))
+            .push(turn_line(&game, &Seats::new(&self.players, self.first)))
+            .build();
+        Ok((game, content))
+    }
+
+    /// Store the game posted as `message` and start its timers
+    async fn begin(&self, ctx: &Context, message: &Message, game: &TicTacToe) -> Result<()> {
+        let mut record = GameRecord::new(TicTacToe::NAME, message, &self.players);
+        record
+            .options
+            .insert("variant".to_string(), self.variant.to_string());
+        record
+            .options
+            .insert("first".to_string(), self.first.to_string());
+        if let Some(difficulty) = self.difficulty {
+            record
+                .options
+                .insert("difficulty".to_string(), difficulty.to_string());
+        }
+        if let Some(series) = &self.series {
+            series.save(&mut record);
+        }
+        record.moves = game
+            .state
+            .iter()
+            .filter(|(_, &tile)| tile != Tile::Empty)
+            .map(|(coord, _)| coord.to_string())
+            .collect();
+        record.state = encode_state(self.variant, &game.state);
+        let moves = record.moves.len();
+        Games::get(ctx).await.insert(record).await;
+        start_turn_timer(ctx, message, moves).await?;
+        Collectors::get(ctx)
+            .await
+            .track(TicTacToe::NAME, message, GAME_TTL)
+            .await;
+        Ok(())
+    }
+}
+
+/// Start the game when the challenged user accepts, or drop the invitation on a decline
+async fn answer_invitation(
+    ctx: &Context,
+    interaction: &ComponentInteraction,
+    record: Option<GameRecord>,
+) -> Result<()> {
+    let Some(record) = record.filter(is_invitation) else {
+        return game::reply_ephemeral(ctx, interaction, "The invitation is no longer open").await;
+    };
+    let header = interaction
+        .message
+        .content
+        .split_once("\n")
+        .unwrap_or_default()
+        .0;
+    let games = Games::get(ctx).await;
+    let collectors = Collectors::get(ctx).await;
+
+    if interaction.data.custom_id == DECLINE_ID {
+        let mut msg = MessageBuilder::default();
+        msg.push_line(header);
+        if interaction.user.id == record.opponent {
+            msg.mention(&record.opponent)
+                .push(" declined the challenge");
+        } else {
+            msg.push("The challenge was withdrawn");
+        }
+        interaction
+            .create_response(
+                ctx,
+                CreateInteractionResponse::UpdateMessage(
+                    CreateInteractionResponseMessage::new()
+                        .content(msg.build())
+                        .components(Vec::new()),
+                ),
+            )
+            .await?;
+        collectors.untrack(interaction.message.id).await;
+        games.remove(interaction.message.id).await;
+        return Ok(());
+    }
+
+    if interaction.user.id != record.opponent {
+        let content = MessageBuilder::default()
+            .push("Waiting for ")
+            .mention(&record.opponent)
+            .push(" to accept")
+            .build();
+        return game::reply_ephemeral(ctx, interaction, content).await;
+    }
+
+    let Some((game, seats)) = games
+        .update(interaction.message.id, accept_invitation)
+        .await
+        .transpose()?
+        .flatten()
+    else {
+        return game::reply_ephemeral(ctx, interaction, "The invitation is no longer open").await;
+    };
+    let deadline = start_turn_timer(ctx, &interaction.message, 0).await?;
+
+    let mut msg = MessageBuilder::default();
+    msg.push_line(header).push(turn_line(&game, &seats));
+    if let Some(deadline) = deadline {
+        msg.push(format!(", time runs out <t:{}:R>", deadline));
+    }
+    let start = GameMessage::new(ctx, interaction.guild_id, msg.build(), &game).await?;
+    interaction
+        .create_response(
+            ctx,
+            CreateInteractionResponse::UpdateMessage(start.response()),
+        )
+        .await?;
+    collectors
+        .track(TicTacToe::NAME, &interaction.message, GAME_TTL)
+        .await;
+    Ok(())
+}
+
+/// Pick who moves first in the stored invitation, `None` when it was accepted already
+fn accept_invitation(record: &mut GameRecord) -> Result<Option<(TicTacToe, Seats)>> {
+    let Some(first) = record.options.get("invitation") else {
+        return Ok(None);
+    };
+    let first = FirstPlayer::try_from(first.as_str())?.pick(&record.players());
+    record.options.remove("invitation");
+    record
+        .options
+        .insert("first".to_string(), first.to_string());
+    let variant = record_variant(record)?;
+    Ok(Some((
+        TicTacToe::new(variant, variant.empty_state(), None),
+        Seats::of(record),
+    )))
+}
+
+/// Invitations are stored with who moves first until they are accepted
+fn is_invitation(record: &GameRecord) -> bool {
+    record.options.contains_key("invitation")
+}
+
+/// The first line of the game message, kept until the game ends
+fn header(
+    players: &Players,
+    variant: Variant,
+    difficulty: Option<Difficulty>,
+    series: Option<&Series>,
+) -> String {
+    let mut header = MessageBuilder::default();
+    header
+        .mention(&players.opponent)
+        .push(" has been challenged to ");
+    if variant != Variant::Classic {
+        header.push(variant.description()).push(" ");
+    }
+    header.push("TicTacToe");
+    if let Some(difficulty) = difficulty {
+        header.push(" on ").push_bold(difficulty.to_string());
+    }
+    header.push("!");
+    if let Some(series) = series {
+        header
+            .push(format!(" Best of {}, ", series.games))
+            .mention(&players.challenger)
+            .push(format!(" {} - {} ", series.challenger, series.opponent))
+            .mention(&players.opponent);
+        if let Some(winner) = series.winner(players) {
+            header.push(", ").mention(&winner).push(" wins the series");
+        }
+    }
+    header.build()
+}
+
+/// A best-of-N series, played as rematches of the first game
+#[derive(Debug, Clone, Copy)]
+struct Series {
+    games: u8,
+    /// Games won by the challenger, ties are not counted
+    challenger: u8,
+    /// Games won by the opponent
+    opponent: u8,
+}
+
+impl Series {
+    const LENGTHS: [u8; 2] = [3, 5];
+
+    fn new(games: u8) -> Result<Self> {
+        if !Self::LENGTHS.contains(&games) {
+            bail!("Unsupported TicTacToe series length: {}", games);
+        }
+        Ok(Self {
+            games,
+            challenger: 0,
+            opponent: 0,
+        })
+    }
+
+    fn of(record: &GameRecord) -> Result<Option<Self>> {
+        let Some(games) = record.options.get("series") else {
+            return Ok(None);
+        };
+        let mut series = Self::new(games.parse()?)?;
+        if let Some((challenger, opponent)) = record
+            .options
+            .get("score")
+            .and_then(|score| score.split_once('-'))
+        {
+            series.challenger = challenger.parse()?;
+            series.opponent = opponent.parse()?;
+        }
+        Ok(Some(series))
+    }
+
+    fn save(&self, record: &mut GameRecord) {
+        record
+            .options
+            .insert("series".to_string(), self.games.to_string());
+        record.options.insert(
+            "score".to_string(),
+            format!("{}-{}", self.challenger, self.opponent),
+        );
+    }
+
+    /// The player that won more than half of the games
+    fn winner(&self, players: &Players) -> Option<UserId> {
+        let needed = self.games / 2 + 1;
+        if self.challenger >= needed {
+            Some(players.challenger)
+        } else if self.opponent >= needed {
+            Some(players.opponent)
+        } else {
+            None
+        }
+    }
+
+    fn win(&mut self, players: &Players, winner: UserId) {
+        if winner == players.challenger {
+            self.challenger += 1;
+        } else {
+            self.opponent += 1;
+        }
+    }
+}
+
+/// Count a finished game in the series of the record, returning the new first line of the
+/// message when the game is part of a series
+fn score_series(record: &mut GameRecord, winner: Option<UserId>) -> Result<Option<String>> {
+    let Some(mut series) = Series::of(record)? else {
+        return Ok(None);
+    };
+    let players = record.players();
+    if let Some(winner) = winner {
+        series.win(&players, winner);
+    }
+    series.save(record);
+    Ok(Some(header(
+        &players,
+        record_variant(record)?,
+        record_difficulty(record)?,
+        Some(&series),
+    )))
+}
+
+/// What a click on Rematch did to the finished game
+#[derive(Debug)]
+enum Rematch {
+    /// The game is not finished
+    NotOver,
+    Started,
+    /// The player asked before and `0` has not answered yet
+    Waiting(UserId),
+    /// The first of the two players asked
+    Asked(GameRecord),
+    /// Both players agreed, the rematch is claimed by the game until it is posted
+    Agreed(GameRecord),
+}
+
+/// Ask for a rematch of the stored game, the bot always agrees
+fn ask_rematch(record: &mut GameRecord, user: UserId) -> Result<Rematch> {
+    if record.finished_at.is_none() {
+        return Ok(Rematch::NotOver);
+    }
+    if record.options.contains_key("rematched") {
+        return Ok(Rematch::Started);
+    }
+    let players = record.players();
+    let other = if user == players.challenger {
+        players.opponent
+    } else {
+        players.challenger
+    };
+    if record_difficulty(record)?.is_none() && other != user {
+        match record.options.get("rematch") {
+            Some(asked) if *asked == user.to_string() => return Ok(Rematch::Waiting(other)),
+            Some(_) => {}
+            None => {
+                record
+                    .options
+                    .insert("rematch".to_string(), user.to_string());
+                return Ok(Rematch::Asked(record.clone()));
+            }
+        }
+    }
+    record.options.remove("rematch");
+    record
+        .options
+        .insert("rematched".to_string(), String::new());
+    Ok(Rematch::Agreed(record.clone()))
+}
+
+/// Start a new game with the sides swapped once both players asked for it
+async fn rematch(ctx: &Context, interaction: &ComponentInteraction) -> Result<()> {
+    let games = Games::get(ctx).await;
+    let user = interaction.user.id;
+    let asked = games
+        .update(interaction.message.id, |record| ask_rematch(record, user))
+        .await
+        .transpose()?;
+    let (record, agreed) = match asked {
+        Some(Rematch::Asked(record)) => (record, false),
+        Some(Rematch::Agreed(record)) => (record, true),
+        Some(Rematch::Started) => {
+            return game::reply_ephemeral(ctx, interaction, "A rematch has already started").await
+        }
+        Some(Rematch::Waiting(other)) => {
+            let content = MessageBuilder::default()
+                .push("Waiting for ")
+                .mention(&other)
+                .push(" to accept the rematch")
+                .build();
+            return game::reply_ephemeral(ctx, interaction, content).await;
+        }
+        Some(Rematch::NotOver) | None => {
+            return game::reply_ephemeral(ctx, interaction, "The game is no longer recorded").await
+        }
+    };
+    // The finished game without any earlier rematch request
+    let result = interaction
+        .message
+        .content
+        .lines()
+        .take(2)
+        .collect::<Vec<_>>()
+        .join("\n");
+    // Updating the message drops its attachments, so the board image is sent again
+    let files = board_files(
+        ctx,
+        interaction.guild_id,
+        &position(&record, record.moves.len())?,
+    )
+    .await?;
+
+    if !agreed {
+        let content = MessageBuilder::default()
+            .push_line(result)
+            .mention(&user)
+            .push(" wants a rematch")
+            .build();
+        interaction
+            .create_response(
+                ctx,
+                CreateInteractionResponse::UpdateMessage(
+                    CreateInteractionResponseMessage::new()
+                        .content(content)
+                        .files(files),
+                ),
+            )
+            .await?;
+        return Ok(());
+    }
+
+    let started = start_rematch(ctx, interaction, &record).await;
+    games
+        .update(interaction.message.id, |record| match &started {
+            Ok(message_id) => record
+                .options
+                .insert("rematched".to_string(), message_id.to_string()),
+            // Let the players try again
+            Err(_) => record.options.remove("rematched"),
+        })
+        .await;
+    started?;
+    interaction
+        .create_response(
+            ctx,
+            CreateInteractionResponse::UpdateMessage(
+                CreateInteractionResponseMessage::new()
+                    .content(result + "\nA rematch has started")
+                    .components(without_rematch(&interaction.message.components))
+                    .files(files),
+            ),
+        )
+        .await?;
+    Ok(())
+}
+
+/// Post the rematch of the finished game of `record` and return the id of its message
+async fn start_rematch(
+    ctx: &Context,
+    interaction: &ComponentInteraction,
+    record: &GameRecord,
+) -> Result<MessageId> {
+    let players = record.players();
+    // A finished series starts over
+    let series = match Series::of(record)? {
+        Some(series) if series.winner(&players).is_some() => Some(Series::new(series.games)?),
+        series => series,
+    };
+    let setup = Setup {
+        players,
+        variant: record_variant(record)?,
+        difficulty: record_difficulty(record)?,
+        first: Seats::of(record).second,
+        series,
+    };
+    let (game, content) = setup.open().await?;
+    let files = board_files(ctx, interaction.guild_id, &game).await?;
+    let mut message = interaction
+        .channel_id
+        .send_message(
+            ctx,
+            CreateMessage::new()
+                .content(content)
+                .components(create_components(&game))
+                .embed(board_text(&game))
+                .files(files),
+        )
+        .await?;
+    message.guild_id = interaction.guild_id;
+    setup.begin(ctx, &message, &game).await?;
+    Ok(message.id)
+}
+
+/// The buttons of a finished game with the Rematch button disabled
+fn without_rematch(rows: &[ActionRow]) -> Vec<CreateActionRow> {
+    rows.iter()
+        .map(|row| {
+            CreateActionRow::Buttons(
+                row.components
+                    .iter()
+                    .filter_map(|component| match component {
+                        ActionRowComponent::Button(button) => {
+                            let rematch = matches!(
+                                &button.data,
+                                ButtonKind::NonLink { custom_id, .. } if custom_id == REMATCH_ID
+                            );
+                            Some(
+                                CreateButton::from(button.clone())
+                                    .disabled(button.disabled || rematch),
+                            )
+                        }
+                        _ => None,
+                    })
+                    .collect(),
+            )
+        })
+        .collect()
+}
+
+/// Like `❌@user's turn`
+fn turn_line(game: &TicTacToe, seats: &Seats) -> String {
+    MessageBuilder::default()
+        .push(Tile::from(game.next_turn).to_string())
+        .mention(&seats.user(game.next_turn))
+        .push("'s turn")
+        .build()
+}
+
+/// What a click on Undo did to the stored game
+#[derive(Debug)]
+enum UndoRequest {
+    Refused(&'static str),
+    TakenBack(TakenBack),
+    /// The opponent of `0` clicked while their request is open
+    Answer(UserId),
+    /// The player asked before and `0` has not answered yet
+    Waiting(UserId),
+    /// `mover` asked `other` to take back `square`
+    Asked {
+        mover: UserId,
+        other: UserId,
+        square: String,
+        game: TicTacToe,
+    },
+}
+
+/// Ask to take back the last move of the stored game. The bot and players of games against
+/// themselves need no consent
+fn ask_undo(record: &mut GameRecord, user: UserId) -> Result<UndoRequest> {
+    if record.finished_at.is_some() {
+        return Ok(UndoRequest::Refused("There is no move to take back"));
+    }
+    let seats = Seats::of(record);
+    let against_bot = record_difficulty(record)?.is_some();
+    // The bot's first move cannot be taken back
+    let first_move = usize::from(against_bot && seats.first == record.opponent);
+    if record.moves.len() <= first_move {
+        return Ok(UndoRequest::Refused("There is no move to take back"));
+    }
+    let game = position(record, record.moves.len())?;
+    let mover = seats.user(game.next_turn.other());
+    let other = seats.user(game.next_turn);
+
+    if against_bot || mover == other {
+        return Ok(UndoRequest::TakenBack(take_back(record)?));
+    }
+    let pending = undo_pending(record);
+    if user == other {
+        if !pending {
+            return Ok(UndoRequest::Refused(
+                "Only the player who just moved can ask to take it back",
+            ));
+        }
+        return Ok(UndoRequest::Answer(mover));
+    }
+    if pending {
+        return Ok(UndoRequest::Waiting(other));
+    }
+    record
+        .options
+        .insert("undo".to_string(), record.moves.len().to_string());
+    let last = record.moves.last().map(String::as_str).unwrap_or_default();
+    let square = Coord::try_from(last)?.square(game.variant);
+    Ok(UndoRequest::Asked {
+        mover,
+        other,
+        square,
+        game,
+    })
+}
+
+/// Ask to take back the last move, or show the opponent of the player asking how to answer
+async fn undo(ctx: &Context, interaction: &ComponentInteraction) -> Result<()> {
+    let user = interaction.user.id;
+    let request = Games::get(ctx)
+        .await
+        .update(interaction.message.id, |record| ask_undo(record, user))
+        .await
+        .transpose()?
+        .unwrap_or(UndoRequest::Refused("There is no move to take back"));
+    let (mover, other, square, game) = match request {
+        UndoRequest::Refused(reason) => {
+            return game::reply_ephemeral(ctx, interaction, reason).await
+        }
+        UndoRequest::TakenBack(taken_back) => {
+            let update = taken_back_message(ctx, taken_back, &interaction.message).await?;
+            interaction
+                .create_response(
+                    ctx,
+                    CreateInteractionResponse::UpdateMessage(update.response()),
+                )
+                .await?;
+            return Ok(());
+        }
+        UndoRequest::Answer(mover) => {
+            let content = MessageBuilder::default()
+                .mention(&mover)
+                .push(" wants to take back their last move")
+                .build();
+            let message_id = interaction.message.id;
+            interaction
+                .create_response(
+                    ctx,
+                    CreateInteractionResponse::Message(
+                        CreateInteractionResponseMessage::new()
+                            .content(content)
+                            .ephemeral(true)
+                            .components(vec![CreateActionRow::Buttons(vec![
+                                CreateButton::new(format!("{}_{}", APPROVE_UNDO_ID, message_id))
+                                    .label("Approve")
+                                    .style(ButtonStyle::Success),
+                                CreateButton::new(format!("{}_{}", REJECT_UNDO_ID, message_id))
+                                    .label("Reject")
+                                    .style(ButtonStyle::Danger),
+                            ])]),
+                    ),
+                )
+                .await?;
+            return Ok(());
+        }
+        UndoRequest::Waiting(other) => {
+            let content = MessageBuilder::default()
+                .push("Waiting for ")
+                .mention(&other)
+                .push(" to answer")
+                .build();
+            return game::reply_ephemeral(ctx, interaction, content).await;
+        }
+        UndoRequest::Asked {
+            mover,
+            other,
+            square,
+            game,
+        } => (mover, other, square, game),
+    };
+
+    let content = MessageBuilder::default()
+        .push_line(turn_message(&interaction.message.content))
+        .mention(&mover)
+        .push(format!(" asks to take back {}, ", square))
+        .mention(&other)
+        .push(" can answer with Undo")
+        .build();
+    let files = board_files(ctx, interaction.guild_id, &game).await?;
+    interaction
+        .create_response(
+            ctx,
+            CreateInteractionResponse::UpdateMessage(
+                CreateInteractionResponseMessage::new()
+                    .content(content)
+                    .files(files),
+            ),
+        )
+        .await?;
+    Ok(())
+}
+
+/// What became of an answer to a request to take back a move
+#[derive(Debug)]
+enum UndoAnswer {
+    /// The request was answered before or the game ended
+    Closed,
+    NotYours,
+    Approved(TakenBack),
+    Rejected,
+}
+
+/// Approve or reject the open request to take back a move of the stored game
+fn answer_request(record: &mut GameRecord, user: UserId, approved: bool) -> Result<UndoAnswer> {
+    if record.finished_at.is_some() || !undo_pending(record) {
+        return Ok(UndoAnswer::Closed);
+    }
+    let game = position(record, record.moves.len())?;
+    if user != Seats::of(record).user(game.next_turn) {
+        return Ok(UndoAnswer::NotYours);
+    }
+    if approved {
+        return Ok(UndoAnswer::Approved(take_back(record)?));
+    }
+    record.options.remove("undo");
+    Ok(UndoAnswer::Rejected)
+}
+
+/// The opponent approved or rejected taking back a move, from the buttons of an ephemeral
+/// message that carry the id of the game message
+async fn answer_undo(
+    ctx: &Context,
+    interaction: &ComponentInteraction,
+    approved: bool,
+) -> Result<()> {
+    let Some(message_id) = interaction
+        .data
+        .custom_id
+        .rsplit('_')
+        .next()
+        .and_then(|id| id.parse().ok())
+        .map(MessageId::new)
+    else {
+        bail!("Malformed undo customId: {}", interaction.data.custom_id);
+    };
+    let user = interaction.user.id;
+    let answered = Games::get(ctx)
+        .await
+        .update(message_id, |record| answer_request(record, user, approved))
+        .await
+        .transpose()?
+        .unwrap_or(UndoAnswer::Closed);
+    let answer = |content: &str| {
+        CreateInteractionResponse::UpdateMessage(
+            CreateInteractionResponseMessage::new()
+                .content(content)
+                .components(Vec::new()),
+        )
+    };
+    let taken_back = match answered {
+        UndoAnswer::Closed => {
+            interaction
+                .create_response(ctx, answer("The request is no longer open"))
+                .await?;
+            return Ok(());
+        }
+        UndoAnswer::NotYours => {
+            return game::reply_ephemeral(ctx, interaction, "You cannot answer this request").await
+        }
+        UndoAnswer::Approved(taken_back) => Some(taken_back),
+        UndoAnswer::Rejected => None,
+    };
+
+    let mut message = interaction.channel_id.message(ctx, message_id).await?;
+    message.guild_id = interaction.guild_id;
+    if let Some(taken_back) = taken_back {
+        let update = taken_back_message(ctx, taken_back, &message).await?;
+        message.edit(ctx, update.edit()).await?;
+        interaction
+            .create_response(ctx, answer("The move was taken back"))
+            .await?;
+    } else {
+        message
+            .edit(
+                ctx,
+                EditMessage::new().content(turn_message(&message.content)),
+            )
+            .await?;
+        interaction
+            .create_response(ctx, answer("The move stays"))
+            .await?;
+    }
+    Ok(())
+}
+
+/// Whether the player that moved last asked to take the move back
+fn undo_pending(record: &GameRecord) -> bool {
+    record.options.get("undo") == Some(&record.moves.len().to_string())
+}
+
+/// The first line and the turn of the game message, without a request to undo
+fn turn_message(content: &str) -> String {
+    content.lines().take(2).collect::<Vec<_>>().join("\n")
+}
+
+/// The board after moves were taken back
+#[derive(Debug)]
+struct TakenBack {
+    game: TicTacToe,
+    seats: Seats,
+    /// Moves left in the record
+    moves: usize,
+}
+
+/// Take back the last move of the stored game, together with the bot's answer to it
+fn take_back(record: &mut GameRecord) -> Result<TakenBack> {
+    let seats = Seats::of(record);
+    let against_bot = record_difficulty(record)?.is_some();
+    while record.moves.pop().is_some() {
+        // The player to move made the move that was taken back
+        let to_move = seats.user(position(record, record.moves.len())?.next_turn);
+        if !against_bot || to_move != record.opponent {
+            break;
+        }
+    }
+    record.options.remove("undo");
+    let game = position(record, record.moves.len())?;
+    record.state = encode_state(game.variant, &game.state);
+    Ok(TakenBack {
+        game,
+        seats,
+        moves: record.moves.len(),
+    })
+}
+
+/// The game message for the board before the moves that were taken back
+async fn taken_back_message(
+    ctx: &Context,
+    taken_back: TakenBack,
+    message: &Message,
+) -> Result<GameMessage> {
+    let TakenBack { game, seats, moves } = taken_back;
+    let deadline = start_turn_timer(ctx, message, moves).await?;
+
+    let mut content = MessageBuilder::default();
+    content
+        .push_line(message.content.split_once("\n").unwrap_or_default().0)
+        .push(turn_line(&game, &seats));
+    if let Some(deadline) = deadline {
+        content.push(format!(", time runs out <t:{}:R>", deadline));
+    }
+    GameMessage::new(ctx, message.guild_id, content.build(), &game).await
+}
+
+/// Payload of the jobs that remind the player to move and end the game when time runs out
+#[derive(Debug, Serialize, Deserialize)]
+struct TurnTimer {
+    channel_id: ChannelId,
+    message_id: MessageId,
+    /// Moves made when the timer was started, a later move makes the timer stale
+    moves: usize,
+    /// Unix timestamp in seconds at which the player to move forfeits
+    deadline: i64,
+    reminder: bool,
+}
+
+/// Replace the timers of a game with ones for the next move and return its deadline
+async fn start_turn_timer(ctx: &Context, message: &Message, moves: usize) -> Result<Option<i64>> {
+    cancel_turn_timer(ctx, message.id).await;
+    let Some(limit) = Settings::get(ctx)
+        .await
+        .current()
+        .turn_time_limit(message.guild_id)
+    else {
+        return Ok(None);
+    };
+    let now = Timestamp::now().unix_timestamp();
+    let deadline = now + limit.as_secs() as i64;
+    let scheduler = Scheduler::get(ctx).await;
+    for (at, reminder) in [(now + limit.as_secs() as i64 / 2, true), (deadline, false)] {
+        let timer = TurnTimer {
+            channel_id: message.channel_id,
+            message_id: message.id,
+            moves,
+            deadline,
+            reminder,
+        };
+        scheduler
+            .add(
+                Job::once(TicTacToe::NAME, at)
+                    .guild(message.guild_id)
+                    .payload(serde_json::to_string(&timer)?)
+                    // A late reminder is only noise
+                    .missed(if reminder {
+                        Missed::Skip
+                    } else {
+                        Missed::RunOnce
+                    }),
+            )
+            .await?;
+    }
+    Ok(Some(deadline))
+}
+
+async fn cancel_turn_timer(ctx: &Context, message_id: MessageId) {
+    Scheduler::get(ctx)
+        .await
+        .cancel_where(TicTacToe::NAME, |job| {
+            serde_json::from_str::<TurnTimer>(&job.payload)
+                .is_ok_and(|timer| timer.message_id == message_id)
+        })
+        .await;
+}
+
+fn record_variant(record: &GameRecord) -> Result<Variant> {
+    record
+        .options
+        .get("variant")
+        .map_or(Ok(Variant::default()), |variant| {
+            variant.as_str().try_into()
+        })
+}
+
+/// The game after the first `moves` moves of the record
+fn position(record: &GameRecord, moves: usize) -> Result<TicTacToe> {
+    let variant = record_variant(record)?;
+    let state = if record.initial.is_empty() {
+        variant.empty_state()
+    } else {
+        decode_state(variant, &record.initial)?
+    };
+    let mut game = TicTacToe::new(variant, state, None);
+    for coord in record.moves.iter().take(moves) {
+        game = TicTacToe::new(variant, game.state, Some(coord.as_str().try_into()?));
+    }
+    Ok(game)
+}
+
+/// The moves of a game as squares like `b2`, see [`game::notation`]
+pub(super) fn notation(record: &GameRecord) -> Result<String> {
+    let variant = record_variant(record)?;
+    let game = position(record, record.moves.len())?;
+    let moves = record
+        .moves
+        .iter()
+        .map(|coord| Ok(Coord::try_from(coord.as_str())?.square(variant)))
+        .collect::<Result<Vec<_>>>()?;
+    let forfeited_by = record.options.get("forfeited_by");
+    let result = match (&game.winning, game.next_turn) {
+        _ if forfeited_by == Some(&Seats::of(record).first.to_string()) => "0-1",
+        _ if forfeited_by.is_some() => "1-0",
+        (None, _) => "*",
+        (Some(Winning::Tie), _) => "1/2-1/2",
+        // The player that moved last won
+        (Some(_), Player::Second) => "1-0",
+        (Some(_), Player::First) => "0-1",
+    };
+    let mut title = format!("{} {}", TicTacToe::NAME, variant);
+    if !record.initial.is_empty() {
+        title.push_str(&format!(" from {}", record.initial));
+    }
+    Ok(game::notation(&title, &moves, result))
+}
+
+/// The board after the first `moves` moves of the record for `/replay`
+pub(super) fn replay(record: &GameRecord, moves: usize) -> Result<Position> {
+    let variant = record_variant(record)?;
+    let game = position(record, moves)?;
+    let mut content = MessageBuilder::default();
+    content.push_bold_line(format!("{} {}", TicTacToe::NAME, variant.description()));
+    match moves.checked_sub(1).and_then(|i| record.moves.get(i)) {
+        Some(coord) => {
+            let coord = Coord::try_from(coord.as_str())?;
+            content
+                .push(game.state.get(&coord).unwrap_or(&Tile::Empty).to_string())
+                .push(" ")
+                .push(coord.square(variant))
+        }
+        None => content.push("Start"),
+    };
+    match &game.winning {
+        Some(Winning::Tie) => content.push(", the game is a tie"),
+        Some(_) => content
+            .push(", ")
+            .push(Tile::from(game.next_turn.other()).to_string())
+            .push(" wins"),
+        None => &mut content,
+    };
+    Ok(Position {
+        content: content.build(),
+        components: create_tiles(&game, false),
+    })
+}
+
+/// Only games against the bot have a difficulty
+fn record_difficulty(record: &GameRecord) -> Result<Option<Difficulty>> {
+    record
+        .options
+        .get("difficulty")
+        .map(|difficulty| difficulty.as_str().try_into())
+        .transpose()
+}
+
+/// A record for a game started before games were stored, read from the message
+fn legacy_record(
+    message: &Message,
+    players: &Players,
+    variant: Variant,
+    state: &HashMap<Coord, Tile>,
+) -> GameRecord {
+    let mut record = GameRecord::new(TicTacToe::NAME, message, players);
+    record.initial = encode_state(variant, state);
+    record.state = record.initial.clone();
+    record
+        .options
+        .insert("variant".to_string(), variant.to_string());
+    if message
+        .mentions
+        .iter()
+        .any(|user| user.id == players.opponent && user.bot)
+    {
+        let header = message.content.split_once("\n").unwrap_or_default().0;
+        record
+            .options
+            .insert("difficulty".to_string(), difficulty(header).to_string());
+    }
+    record
+}
+
+/// Read the board back from the emojis of the buttons of a legacy message
+fn scrape_state(components: &[ActionRow]) -> HashMap<Coord, Tile> {
+    components
+        .iter()
+        .flat_map(|row| &row.components)
+        .filter_map(|component| {
+            let ActionRowComponent::Button(button) = component else {
+                return None;
+            };
+            let ButtonKind::NonLink { custom_id, .. } = &button.data else {
+                return None;
+            };
+            let (_, coord) = parse_tile_id(custom_id).ok()?;
+            let tile = match &button.emoji {
+                Some(e) if e.unicode_eq(X_EMOJI) => Tile::X,
+                Some(e) if e.unicode_eq(O_EMOJI) => Tile::O,
+                _ => Tile::Empty,
+            };
+            Some((coord, tile))
+        })
+        .collect()
+}
+
+/// Rows from the top separated by `/`, like `X.O/.X./..O`
+fn encode_state(variant: Variant, state: &HashMap<Coord, Tile>) -> String {
+    (0..variant.size())
+        .map(|row| {
+            (0..variant.size())
+                .map(|col| match state.get(&Coord(Row(row), Column(col))) {
+                    Some(Tile::X) => 'X',
+                    Some(Tile::O) => 'O',
+                    _ => '.',
+                })
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn decode_state(variant: Variant, value: &str) -> Result<HashMap<Coord, Tile>> {
+    let rows: Vec<&str> = value.split('/').collect();
+    if rows.len() != variant.size() as usize {
+        bail!("Malformed TicTacToe state for {}: {}", variant, value);
+    }
+    let mut state = HashMap::new();
+    for (row, tiles) in rows.into_iter().enumerate() {
+        if tiles.chars().count() != variant.size() as usize {
+            bail!("Malformed TicTacToe state for {}: {}", variant, value);
+        }
+        for (col, tile) in tiles.chars().enumerate() {
+            let tile = match tile {
+                'X' => Tile::X,
+                'O' => Tile::O,
+                '.' => Tile::Empty,
+                _ => bail!("Unknown tile '{}' in TicTacToe state: {}", tile, value),
+            };
+            state.insert(Coord(Row(row as u8), Column(col as u8)), tile);
+        }
+    }
+    Ok(state)
+}
+
+/// The difficulty is shown in bold in the first line of games against the bot
+fn difficulty(header: &str) -> Difficulty {
+    Difficulty::ALL
+        .into_iter()
+        .find(|difficulty| header.contains(&format!("**{}**", difficulty)))
+        .unwrap_or_default()
+}
+
+/// How long a game may go without a move before the board is disabled
+const GAME_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+/// How long the challenged user has to accept
+const INVITE_TTL: Duration = Duration::from_secs(15 * 60);
+
+/// The size of the board and how many tiles in a line win
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+enum Variant {
+    #[default]
+    Classic,
+    FourByFour,
+    /// 4×4 board where three in a line is enough to win
+    FourByFourThree,
 }
 
-fn next_player(state: &HashMap<Coord, Tile>) -> Player {
-    if state
-        .iter()
-        .filter(|(_, &tile)| tile != Tile::Empty)
-        .count()
-        % 2
-        == 0
-    {
-        Player::Opponent
-    } else {
-        Player::Challenger
-    }
-}
+impl Variant {
+    const ALL: [Variant; 3] = [
+        Variant::Classic,
+        Variant::FourByFour,
+        Variant::FourByFourThree,
+    ];
 
-impl TicTacToe {
-    fn new(mut state: HashMap<Coord, Tile>, clicked_tile: Option<Coord>) -> Self {
-        if let Some(clicked) = clicked_tile {
-            state.insert(clicked, next_player(&state).into());
-        }
-        Self {
-            next_turn: next_player(&state),
-            winning: calculate_winner(&state),
-            state,
+    /// Tiles along each side, at most 4 as the fifth action row holds the Remove button
+    fn size(&self) -> u8 {
+        match self {
+            Variant::Classic => 3,
+            Variant::FourByFour | Variant::FourByFourThree => 4,
         }
     }
-}
 
-#[async_trait]
-impl CustomCommand for TicTacToe {
-    const NAME: &'static str = "TicTacToe";
-    fn command() -> CreateCommand {
-        CreateCommand::new(Self::NAME)
-            .kind(CommandType::User)
-            .to_owned()
+    fn win_length(&self) -> u8 {
+        match self {
+            Variant::Classic | Variant::FourByFourThree => 3,
+            Variant::FourByFour => 4,
+        }
     }
 
-    async fn slash(ctx: Context, command: CommandInteraction) -> Result<()> {
-        if let Some(ResolvedTarget::User(target, _)) = command.data.target() {
-            if target.bot {
-                command
-                    .create_response(
-                        &ctx,
-                        CreateInteractionResponse::Message(
-                            CreateInteractionResponseMessage::new()
-                                .content("You cannot challenge a bot to TicTacToe!")
-                                .ephemeral(true),
-                        ),
-                    )
-                    .await?;
-            } else {
-                command
-                    .create_response(
-                        &ctx,
-                        CreateInteractionResponse::Message(
-                            CreateInteractionResponseMessage::new()
-                                .content(
-                                    MessageBuilder::default()
-                                        .mention(&target.id)
-                                        .push_line(" has been challenged to TicTacToe!")
-                                        .push(X_EMOJI)
-                                        .mention(&target.id)
-                                        .push("'s turn")
-                                        .build(),
-                                )
-                                .components(create_components(&TicTacToe::default())),
-                        ),
-                    )
-                    .await?;
-            }
-            Ok(())
-        } else {
-            Err(anyhow!("No user for the user command tictactoe"))
+    /// Shown to players when choosing the board
+    fn description(&self) -> &'static str {
+        match self {
+            Variant::Classic => "3×3",
+            Variant::FourByFour => "4×4",
+            Variant::FourByFourThree => "4×4 three-in-a-row",
         }
     }
 
-    async fn component(ctx: Context, interaction: ComponentInteraction) -> Result<()> {
-        let Some(ref interaction_metadata) = interaction.message.interaction_metadata else {
-            bail!("There was no interaction on the message");
-        };
-        let MessageInteractionMetadata::Command(MessageCommandInteractionMetadata {
-            user: challenger,
-            ..
-        }) = interaction_metadata.as_ref()
-        else {
-            bail!("There was no interaction on the message");
-        };
+    /// Every tile on the board, row by row from the top
+    fn coords(&self) -> impl Iterator<Item = Coord> {
+        let size = self.size();
+        (0..size).flat_map(move |row| (0..size).map(move |col| Coord(Row(row), Column(col))))
+    }
 
-        // Check if user is not part of the game
-        if &interaction.user != challenger
-            && !interaction
-                .message
-                .mentions
-                .iter()
-                .any(|user| user == &interaction.user)
-        {
-            interaction
-                .create_response(
-                    &ctx,
-                    CreateInteractionResponse::Message(
-                        CreateInteractionResponseMessage::new()
-                            .content("You are not part of this game")
-                            .ephemeral(true),
-                    ),
-                )
-                .await?;
-            return Ok(());
-        };
+    fn empty_state(&self) -> HashMap<Coord, Tile> {
+        self.coords().map(|coord| (coord, Tile::Empty)).collect()
+    }
 
-        // Handle remove game
-        if interaction.data.custom_id == REMOVE_ID {
-            interaction.message.delete(&ctx).await?;
-            interaction
-                .create_response(
-                    &ctx,
-                    CreateInteractionResponse::Message(
-                        CreateInteractionResponseMessage::new()
-                            .content("The game has been removed")
-                            .ephemeral(true),
-                    ),
-                )
-                .await?;
-            return Ok(());
+    /// The state for the game engine, missing tiles are empty
+    fn board(&self, state: &HashMap<Coord, Tile>) -> Board {
+        let mut board = Board::new(self.size(), self.win_length());
+        for (square, coord) in self.coords().enumerate() {
+            board.set(square, (*state.get(&coord).unwrap_or(&Tile::Empty)).into());
         }
+        board
+    }
 
-        let opponent = if interaction.message.mentions.len() == 1 {
-            interaction
-                .message
-                .mentions
-                .first()
-                .ok_or(anyhow!("There was no mentions"))?
-        } else {
-            interaction
-                .message
-                .mentions
-                .iter()
-                .find(|&user| user != challenger)
-                .ok_or(anyhow!(
-                    "There was no mentions other than the player who created the interaction"
-                ))?
-        };
-
-        let clicked_coord: Coord = interaction
-            .data
-            .custom_id
-            .as_str()
-            .split_once("_")
-            .ok_or(anyhow!("Invalid customId, does not have a '_'"))?
-            .1
-            .try_into()?;
+    /// The tile of a square of the [`Board`]
+    fn coord(&self, square: usize) -> Coord {
+        let size = self.size() as usize;
+        Coord(Row((square / size) as u8), Column((square % size) as u8))
+    }
+}
 
-        let game = TicTacToe::new(
-            interaction
-                .message
-                .components
-                .iter()
-                .flat_map(|row| {
-                    row.components.iter().filter_map(|component| {
-                        if let ActionRowComponent::Button(button) = component {
-                            if let ButtonKind::NonLink { custom_id, .. } = &button.data {
-                                let coord = custom_id.split_once("_")?.1.try_into().ok()?;
-                                let tile = match &button.emoji {
-                                    Some(e) if e.unicode_eq(X_EMOJI) => Tile::X,
-                                    Some(e) if e.unicode_eq(O_EMOJI) => Tile::O,
-                                    _ => Tile::Empty,
-                                };
-                                return Some((coord, tile));
-                            }
-                        }
-                        unreachable!();
-                    })
-                })
-                .collect(),
-            Some(clicked_coord),
-        );
+impl TryFrom<&str> for Variant {
+    type Error = anyhow::Error;
 
-        // It is your turn if we reach here, meaning that next_turn must be your opponents
-        if match game.next_turn {
-            Player::Challenger => &interaction.user == opponent,
-            Player::Opponent => &interaction.user == challenger,
-        } {
-            let mut msg = MessageBuilder::default();
-
-            // Preserve first line
-            msg.push_line(interaction.message.content.split_once("\n").unwrap().0);
-
-            match &game.winning {
-                Some(winning) => match winning {
-                    Winning::Tie => msg.push("The game is a tie"),
-                    _ => msg
-                        .push(
-                            Tile::from(match game.next_turn {
-                                Player::Opponent => Player::Challenger,
-                                Player::Challenger => Player::Opponent,
-                            })
-                            .to_string(),
-                        )
-                        .mention(match game.next_turn {
-                            Player::Opponent => &challenger.id,
-                            Player::Challenger => &opponent.id,
-                        })
-                        .push(" is the winner!"),
-                },
-                None => msg
-                    .push(Tile::from(game.next_turn).to_string())
-                    .mention(match game.next_turn {
-                        Player::Challenger => &challenger.id,
-                        Player::Opponent => &opponent.id,
-                    })
-                    .push("'s turn"),
-            };
-            interaction
-                .create_response(
-                    &ctx,
-                    CreateInteractionResponse::UpdateMessage(
-                        CreateInteractionResponseMessage::new()
-                            .content(msg.build())
-                            .components(create_components(&game)),
-                    ),
-                )
-                .await?;
-        } else {
-            interaction
-                .create_response(
-                    &ctx,
-                    CreateInteractionResponse::Message(
-                        CreateInteractionResponseMessage::new()
-                            .content("Its not your turn")
-                            .ephemeral(true),
-                    ),
-                )
-                .await?;
-        };
-        Ok(())
+    fn try_from(value: &str) -> std::result::Result<Self, Self::Error> {
+        match value {
+            "3x3" => Ok(Self::Classic),
+            "4x4" => Ok(Self::FourByFour),
+            "4x4k3" => Ok(Self::FourByFourThree),
+            _ => Err(anyhow!("Could not parse Variant from String: {}", value)),
+        }
     }
 }
 
-const COMPONENT_ROWS: [Row; 3] = [Row::Bottom, Row::Middle, Row::Top];
-const COMPONENT_COLUMNS: [Column; 3] = [Column::Left, Column::Center, Column::Right];
+/// Part of the custom ids of the tiles
+impl Display for Variant {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Variant::Classic => "3x3",
+            Variant::FourByFour => "4x4",
+            Variant::FourByFourThree => "4x4k3",
+        })
+    }
+}
 
 const REMOVE_ID: &str = const_format::formatcp!("{}_{}", TicTacToe::NAME, "_remove");
+const EXPORT_ID: &str = const_format::formatcp!("{}_{}", TicTacToe::NAME, "_export");
+const ACCEPT_ID: &str = const_format::formatcp!("{}_{}", TicTacToe::NAME, "_accept");
+const DECLINE_ID: &str = const_format::formatcp!("{}_{}", TicTacToe::NAME, "_decline");
+const REMATCH_ID: &str = const_format::formatcp!("{}_{}", TicTacToe::NAME, "_rematch");
+const UNDO_ID: &str = const_format::formatcp!("{}_{}", TicTacToe::NAME, "_undo");
+/// The button and the modal for writing a move instead of clicking it
+const TYPE_ID: &str = const_format::formatcp!("{}_{}", TicTacToe::NAME, "_type");
+/// Followed by `_` and the id of the game message
+const APPROVE_UNDO_ID: &str = const_format::formatcp!("{}_{}", TicTacToe::NAME, "_approveundo");
+const REJECT_UNDO_ID: &str = const_format::formatcp!("{}_{}", TicTacToe::NAME, "_rejectundo");
 
 const X_EMOJI: &str = "❌";
 const O_EMOJI: &str = "⭕";
@@ -271,11 +2066,21 @@ enum Tile {
     O,
 }
 
+impl From<Tile> for Option<Player> {
+    fn from(value: Tile) -> Self {
+        match value {
+            Tile::X => Some(Player::First),
+            Tile::O => Some(Player::Second),
+            Tile::Empty => None,
+        }
+    }
+}
+
 impl From<Player> for Tile {
     fn from(value: Player) -> Self {
         match value {
-            Player::Challenger => Tile::O,
-            Player::Opponent => Tile::X,
+            Player::First => Tile::X,
+            Player::Second => Tile::O,
         }
     }
 }
@@ -292,159 +2097,323 @@ impl Display for Tile {
 
 #[derive(Debug)]
 enum Winning {
-    Vertical(Column),
-    Horizontal(Row),
-    Diagonal(Diagonal),
+    Line(Vec<Coord>),
     Tie,
+    /// The player to move ran out of time
+    Forfeit,
 }
 
+/// Counted from the top of the board
 #[derive(Debug, Hash, Clone, Copy, PartialEq, Eq)]
-enum Row {
-    Top,
-    Middle,
-    Bottom,
-}
+struct Row(u8);
 
 impl TryFrom<&str> for Row {
     type Error = anyhow::Error;
 
     fn try_from(value: &str) -> std::result::Result<Self, Self::Error> {
         match value {
-            "top" => Ok(Self::Top),
-            "middle" => Ok(Self::Middle),
-            "bottom" => Ok(Self::Bottom),
-            _ => Err(anyhow!("Could not parse Row from String: {}", value)),
+            // Names from before the board size was configurable, in the order they were shown
+            "bottom" => Ok(Self(0)),
+            "middle" => Ok(Self(1)),
+            "top" => Ok(Self(2)),
+            _ => value
+                .parse()
+                .map(Self)
+                .map_err(|_| anyhow!("Could not parse Row from String: {}", value)),
         }
     }
 }
 
 impl Display for Row {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str(match self {
-            Row::Top => "top",
-            Row::Middle => "middle",
-            Row::Bottom => "bottom",
-        })
+        write!(f, "{}", self.0)
     }
 }
 
+/// Counted from the left of the board
 #[derive(Debug, Hash, Clone, Copy, PartialEq, Eq)]
-enum Column {
-    Left,
-    Center,
-    Right,
-}
+struct Column(u8);
 
 impl TryFrom<&str> for Column {
     type Error = anyhow::Error;
 
     fn try_from(value: &str) -> std::result::Result<Self, Self::Error> {
         match value {
-            "left" => Ok(Self::Left),
-            "center" => Ok(Self::Center),
-            "right" => Ok(Self::Right),
-            _ => Err(anyhow!("Could not parse Column from String: {}", value)),
+            "left" => Ok(Self(0)),
+            "center" => Ok(Self(1)),
+            "right" => Ok(Self(2)),
+            _ => value
+                .parse()
+                .map(Self)
+                .map_err(|_| anyhow!("Could not parse Column from String: {}", value)),
         }
     }
 }
+
 impl Display for Column {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str(match self {
-            Column::Left => "left",
-            Column::Center => "center",
-            Column::Right => "right",
-        })
+        write!(f, "{}", self.0)
     }
 }
 
-#[derive(Debug, PartialEq)]
-enum Diagonal {
-    TopLeftToBottomRight,
-    BottomLeftToTopRight,
-}
-
-#[derive(Debug, Hash, PartialEq, Eq)]
+#[derive(Debug, Hash, Clone, Copy, PartialEq, Eq)]
 struct Coord(Row, Column);
 
 impl TryFrom<&str> for Coord {
     type Error = anyhow::Error;
 
     fn try_from(value: &str) -> std::result::Result<Self, Self::Error> {
-        let (col, row) = value
+        let (row, col) = value
             .split_once("_")
             .ok_or(anyhow!("Malformed input for Coords: {}", value))?;
-        Ok(Coord(col.try_into()?, row.try_into()?))
+        Ok(Coord(row.try_into()?, col.try_into()?))
     }
 }
 
 impl Display for Coord {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_fmt(format_args!("{}_{}_{}", TicTacToe::NAME, self.0, self.1))?;
-        Ok(())
+        write!(f, "{}_{}", self.0, self.1)
     }
 }
 
-fn calculate_winner(state: &HashMap<Coord, Tile>) -> Option<Winning> {
-    if !state.iter().any(|(_, &tile)| tile == Tile::Empty) {
-        return Some(Winning::Tie);
+impl Coord {
+    /// Column letter from the left and row number from the bottom, like `b2`
+    fn square(&self, variant: Variant) -> String {
+        format!(
+            "{}{}",
+            (b'a' + self.1 .0) as char,
+            variant.size() - self.0 .0
+        )
     }
 
-    if let Some(winning) = COMPONENT_COLUMNS.iter().find_map(|&column| {
-        match state
-            .iter()
-            .filter(|(k, _)| k.1 == column)
-            .map(|(_, v)| v)
-            .reduce(|a, v| if a == v { v } else { &Tile::Empty })
+    /// The square written by a player, either like [`Coord::square`] or with words like
+    /// `top left` or `middle`. Errors are meant for the player
+    fn from_square(variant: Variant, value: &str) -> Result<Self> {
+        let size = variant.size();
+        let value = value.trim().to_lowercase();
+        let mut chars = value.chars();
+        if let (Some(letter @ 'a'..='z'), Ok(number)) =
+            (chars.next(), chars.as_str().trim().parse::<u8>())
         {
-            None | Some(Tile::Empty) => None,
-            _ => Some(Winning::Vertical(column)),
+            let col = letter as u8 - b'a';
+            if col >= size || !(1..=size).contains(&number) {
+                bail!("There is no square {} on this board", value);
+            }
+            return Ok(Coord(Row(size - number), Column(col)));
         }
-    }) {
-        return Some(winning);
-    };
-    if let Some(winning) = COMPONENT_ROWS.iter().find_map(|&row| {
-        match state
-            .iter()
-            .filter(|(k, _)| k.0 == row)
-            .map(|(_, v)| v)
-            .reduce(|a, v| if a == v { v } else { &Tile::Empty })
+
+        let (mut row, mut col, mut middle) = (None, None, false);
+        for word in value
+            .split(|c: char| c.is_whitespace() || c == '-' || c == '_')
+            .filter(|word| !word.is_empty())
         {
-            None | Some(Tile::Empty) => None,
-            _ => Some(Winning::Horizontal(row)),
+            match word {
+                "top" | "upper" => row = Some(0),
+                "bottom" | "lower" => row = Some(size - 1),
+                "left" => col = Some(0),
+                "right" => col = Some(size - 1),
+                "middle" | "center" | "centre" => middle = true,
+                _ => bail!("Write the square like b2 or top left, not {}", value),
+            }
         }
-    }) {
-        return Some(winning);
-    };
-
-    match (
-        state.get(&Coord(Row::Middle, Column::Center)),
-        state.get(&Coord(Row::Top, Column::Left)),
-        state.get(&Coord(Row::Bottom, Column::Right)),
-        state.get(&Coord(Row::Bottom, Column::Left)),
-        state.get(&Coord(Row::Top, Column::Right)),
-    ) {
-        (Some(a), Some(b), Some(c), _, _) if a != &Tile::Empty && a == b && a == c => {
-            Some(Winning::Diagonal(Diagonal::TopLeftToBottomRight))
+        // The middle is whatever was not named
+        if middle {
+            if size.is_multiple_of(2) {
+                bail!("This board has no middle square, write it like b2");
+            }
+            row = row.or(Some(size / 2));
+            col = col.or(Some(size / 2));
+        }
+        match (row, col) {
+            (Some(row), Some(col)) => Ok(Coord(Row(row), Column(col))),
+            _ => bail!("Name both the row and the column, like top left"),
         }
-        (Some(a), _, _, Some(b), Some(c)) if a != &Tile::Empty && a == b && a == c => {
-            Some(Winning::Diagonal(Diagonal::BottomLeftToTopRight))
+    }
+}
+
+/// `TicTacToe_<variant>_<row>_<column>`
+fn tile_id(variant: Variant, coord: Coord) -> String {
+    format!("{}_{}_{}", TicTacToe::NAME, variant, coord)
+}
+
+/// Boards from before the variants were added have ids like `TicTacToe_top_left`
+fn parse_tile_id(custom_id: &str) -> Result<(Variant, Coord)> {
+    let (_, tile) = custom_id
+        .split_once("_")
+        .ok_or(anyhow!("Invalid customId, does not have a '_'"))?;
+    if let Some((variant, coord)) = tile.split_once("_") {
+        if let Ok(variant) = Variant::try_from(variant) {
+            return Ok((variant, coord.try_into()?));
         }
-        _ => None,
+    }
+    Ok((Variant::Classic, tile.try_into()?))
+}
+
+fn calculate_winner(variant: Variant, state: &HashMap<Coord, Tile>) -> Option<Winning> {
+    let board = variant.board(state);
+    match board.outcome()? {
+        engine::Outcome::Win(_) => Some(Winning::Line(
+            board
+                .winning_line()?
+                .iter()
+                .map(|&square| variant.coord(square))
+                .collect(),
+        )),
+        engine::Outcome::Draw => Some(Winning::Tie),
     }
 }
 
 fn create_components(game: &TicTacToe) -> Vec<CreateActionRow> {
-    COMPONENT_ROWS
+    let mut buttons = vec![
+        CreateButton::new(EXPORT_ID)
+            .label("Export")
+            .style(ButtonStyle::Secondary),
+        CreateButton::new(REMOVE_ID)
+            .label("Remove")
+            .style(ButtonStyle::Danger),
+    ];
+    buttons.push(match game.winning {
+        Some(_) => CreateButton::new(REMATCH_ID)
+            .label("Rematch")
+            .style(ButtonStyle::Primary),
+        None => CreateButton::new(UNDO_ID)
+            .label("Undo")
+            .style(ButtonStyle::Secondary),
+    });
+    if game.winning.is_none() {
+        buttons.push(
+            CreateButton::new(TYPE_ID)
+                .label("Type move")
+                .style(ButtonStyle::Secondary),
+        );
+    }
+    create_tiles(game, true)
         .into_iter()
+        .chain(std::iter::once(CreateActionRow::Buttons(buttons)))
+        .collect()
+}
+
+/// A game message after its board changed
+struct GameMessage {
+    content: String,
+    components: Vec<CreateActionRow>,
+    files: Vec<CreateAttachment>,
+    embed: CreateEmbed,
+}
+
+impl GameMessage {
+    async fn new(
+        ctx: &Context,
+        guild_id: Option<GuildId>,
+        content: String,
+        game: &TicTacToe,
+    ) -> Result<Self> {
+        Ok(Self {
+            content,
+            components: create_components(game),
+            files: board_files(ctx, guild_id, game).await?,
+            embed: board_text(game),
+        })
+    }
+
+    fn response(self) -> CreateInteractionResponseMessage {
+        CreateInteractionResponseMessage::new()
+            .content(self.content)
+            .components(self.components)
+            .embed(self.embed)
+            .files(self.files)
+    }
+
+    /// For game messages that are not the message of the interaction
+    fn edit(self) -> EditMessage {
+        let edit = EditMessage::new()
+            .content(self.content)
+            .components(self.components)
+            .embed(self.embed);
+        if self.files.is_empty() {
+            return edit;
+        }
+        edit.attachments(
+            self.files
+                .into_iter()
+                .fold(EditAttachments::new(), EditAttachments::add),
+        )
+    }
+}
+
+/// The board as text with the squares labelled like `b2`, for those who cannot make out the
+/// emojis of the buttons
+fn board_text(game: &TicTacToe) -> CreateEmbed {
+    let size = game.variant.size();
+    let mut text: String = std::iter::once("  ".to_string())
+        .chain((0..size).map(|col| format!(" {}", (b'a' + col) as char)))
+        .collect();
+    for row in 0..size {
+        text.push_str(&format!("\n{:>2}", size - row));
+        for col in 0..size {
+            text.push_str(match game.state.get(&Coord(Row(row), Column(col))) {
+                Some(Tile::X) => " X",
+                Some(Tile::O) => " O",
+                _ => " .",
+            });
+        }
+    }
+    CreateEmbed::new().description(MessageBuilder::default().push_codeblock(text, None).build())
+}
+
+/// The board as an image for guilds with `board_images`, nothing for other guilds
+async fn board_files(
+    ctx: &Context,
+    guild_id: Option<GuildId>,
+    game: &TicTacToe,
+) -> Result<Vec<CreateAttachment>> {
+    if !Settings::get(ctx).await.current().board_images(guild_id) {
+        return Ok(Vec::new());
+    }
+    board_image(game)
+}
+
+#[cfg(feature = "images")]
+fn board_image(game: &TicTacToe) -> Result<Vec<CreateAttachment>> {
+    use crate::render::{Picture, Piece};
+
+    let size = game.variant.size() as u32;
+    let mut picture = Picture::new(size, size);
+    for coord in game.variant.coords() {
+        let Coord(Row(row), Column(col)) = coord;
+        let piece = match game.state.get(&coord).unwrap_or(&Tile::Empty) {
+            Tile::X => Some(Piece::Cross),
+            Tile::O => Some(Piece::Ring),
+            Tile::Empty => None,
+        };
+        picture.set(row as u32, col as u32, piece);
+        // The same tiles as the buttons
+        if let Some(winning) = &game.winning {
+            if get_style(&coord, winning) == ButtonStyle::Success {
+                picture.highlight(row as u32, col as u32);
+            }
+        }
+    }
+    Ok(vec![CreateAttachment::bytes(picture.png()?, "board.png")])
+}
+
+/// Builds without the `images` feature only have the buttons
+#[cfg(not(feature = "images"))]
+fn board_image(_game: &TicTacToe) -> Result<Vec<CreateAttachment>> {
+    Ok(Vec::new())
+}
+
+/// The tiles of the board, none can be clicked unless `playable`
+fn create_tiles(game: &TicTacToe, playable: bool) -> Vec<CreateActionRow> {
+    (0..game.variant.size())
         .map(|row| {
             CreateActionRow::Buttons(
-                COMPONENT_COLUMNS
-                    .into_iter()
+                (0..game.variant.size())
                     .map(|col| {
-                        let coord = Coord(row, col);
+                        let coord = Coord(Row(row), Column(col));
                         let tile = game.state.get(&coord).unwrap_or(&Tile::Empty);
-                        CreateButton::new(coord.to_string())
-                            .disabled(*tile != Tile::Empty || game.winning.is_some())
+                        CreateButton::new(tile_id(game.variant, coord))
+                            .disabled(!playable || *tile != Tile::Empty || game.winning.is_some())
                             .style(match &game.winning {
                                 Some(value) => get_style(&coord, value),
                                 None => ButtonStyle::Secondary,
@@ -454,38 +2423,376 @@ fn create_components(game: &TicTacToe) -> Vec<CreateActionRow> {
                     .collect(),
             )
         })
-        .chain(std::iter::once(CreateActionRow::Buttons(vec![
-            CreateButton::new(REMOVE_ID)
-                .label("Remove")
-                .style(ButtonStyle::Danger),
-        ])))
         .collect()
 }
 
 fn get_style(id: &Coord, value: &Winning) -> ButtonStyle {
     if match value {
-        Winning::Vertical(col) => id.1 == *col,
-        Winning::Horizontal(row) => id.0 == *row,
-        Winning::Diagonal(diagonal) => match id {
-            &Coord(Row::Middle, Column::Center) => true,
-            &Coord(Row::Top, Column::Left) | &Coord(Row::Bottom, Column::Right)
-                if *diagonal == Diagonal::TopLeftToBottomRight =>
-            {
-                true
-            }
-
-            &Coord(Row::Bottom, Column::Left) | &Coord(Row::Top, Column::Right)
-                if *diagonal == Diagonal::BottomLeftToTopRight =>
-            {
-                true
-            }
-
-            _ => false,
-        },
-        Winning::Tie => false,
+        Winning::Line(line) => line.contains(id),
+        Winning::Tie | Winning::Forfeit => false,
     } {
         ButtonStyle::Success
     } else {
         ButtonStyle::Secondary
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    const CHALLENGER: UserId = UserId::new(1);
+    const OPPONENT: UserId = UserId::new(2);
+
+    fn coord(square: &str) -> Coord {
+        Coord::from_square(Variant::Classic, square).unwrap()
+    }
+
+    /// A 3×3 game where the opponent plays X, with `squares` played
+    fn record(squares: &[&str], options: &[(&str, &str)]) -> GameRecord {
+        let mut record = GameRecord {
+            command: TicTacToe::NAME.to_string(),
+            guild_id: None,
+            channel_id: None,
+            message_id: MessageId::new(3),
+            challenger: CHALLENGER,
+            opponent: OPPONENT,
+            options: options
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .chain([("first".to_string(), OPPONENT.to_string())])
+                .collect::<BTreeMap<_, _>>(),
+            initial: String::new(),
+            moves: squares
+                .iter()
+                .map(|square| coord(square).to_string())
+                .collect(),
+            state: String::new(),
+            finished_at: None,
+        };
+        let game = position(&record, record.moves.len()).unwrap();
+        record.state = encode_state(Variant::Classic, &game.state);
+        record
+    }
+
+    #[test]
+    fn players_take_turns() {
+        let mut game = record(&[], &[]);
+        assert_eq!(
+            take_tile(&mut game, CHALLENGER, coord("b2"))
+                .unwrap()
+                .unwrap_err(),
+            "Its not your turn"
+        );
+        assert!(game.moves.is_empty());
+        let moved = take_tile(&mut game, OPPONENT, coord("b2"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(moved.moves, 1);
+        assert_eq!(moved.game.next_turn, Player::Second);
+        assert_eq!(
+            take_tile(&mut game, OPPONENT, coord("a1"))
+                .unwrap()
+                .unwrap_err(),
+            "Its not your turn"
+        );
+        assert_eq!(
+            take_tile(&mut game, CHALLENGER, coord("b2"))
+                .unwrap()
+                .unwrap_err(),
+            "That tile is already taken"
+        );
+        assert_eq!(game.moves, [coord("b2").to_string()]);
+        assert_eq!(game.state, ".../.X./...");
+    }
+
+    #[test]
+    fn the_winning_move_finishes_the_game() {
+        let mut game = record(&["a1", "b1", "a2", "b2"], &[("series", "3")]);
+        let moved = take_tile(&mut game, OPPONENT, coord("a3"))
+            .unwrap()
+            .unwrap();
+        assert!(matches!(moved.game.winning, Some(Winning::Line(_))));
+        assert!(moved.header.is_some());
+        assert!(game.finished_at.is_some());
+        assert_eq!(game.options["score"], "0-1");
+        assert_eq!(
+            take_tile(&mut game, CHALLENGER, coord("c3"))
+                .unwrap()
+                .unwrap_err(),
+            "The game is over"
+        );
+        assert_eq!(game.moves.len(), 5);
+    }
+
+    #[test]
+    fn invitations_are_not_played() {
+        let mut game = record(&[], &[("invitation", "random")]);
+        assert_eq!(
+            take_tile(&mut game, OPPONENT, coord("b2"))
+                .unwrap()
+                .unwrap_err(),
+            "The challenge has not been accepted yet"
+        );
+        let (_, seats) = accept_invitation(&mut game).unwrap().unwrap();
+        assert!(!is_invitation(&game));
+        assert_eq!(game.options["first"], seats.first.to_string());
+        // The second click on Accept finds the game started
+        assert!(accept_invitation(&mut game).unwrap().is_none());
+    }
+
+    #[test]
+    fn the_bot_answer_is_dropped_when_the_game_changed() {
+        let mut game = record(&["b2"], &[("difficulty", "medium")]);
+        assert!(answer_move(&mut game, 2, coord("a1")).unwrap().is_none());
+        assert_eq!(game.moves.len(), 1);
+        let answered = answer_move(&mut game, 1, coord("a1")).unwrap().unwrap();
+        assert_eq!(answered.moves, 2);
+        assert_eq!(answered.difficulty, Some(Difficulty::Medium));
+        assert_eq!(game.state, ".../.X./O..");
+    }
+
+    #[test]
+    fn running_out_of_time_forfeits() {
+        let mut game = record(&["b2"], &[]);
+        // A move was made since the timer started
+        assert!(forfeit(&mut game, 0).unwrap().is_none());
+        let forfeited = forfeit(&mut game, 1).unwrap().unwrap();
+        assert!(matches!(forfeited.game.winning, Some(Winning::Forfeit)));
+        assert_eq!(game.options["forfeited_by"], CHALLENGER.to_string());
+        assert!(game.finished_at.is_some());
+        assert!(forfeit(&mut game, 1).unwrap().is_none());
+    }
+
+    #[test]
+    fn rematches_need_both_players() {
+        let mut game = record(&["a1", "b1", "a2", "b2", "a3"], &[]);
+        assert!(matches!(
+            ask_rematch(&mut game, CHALLENGER).unwrap(),
+            Rematch::NotOver
+        ));
+        game.finish();
+        assert!(matches!(
+            ask_rematch(&mut game, CHALLENGER).unwrap(),
+            Rematch::Asked(_)
+        ));
+        assert!(matches!(
+            ask_rematch(&mut game, CHALLENGER).unwrap(),
+            Rematch::Waiting(OPPONENT)
+        ));
+        assert!(matches!(
+            ask_rematch(&mut game, OPPONENT).unwrap(),
+            Rematch::Agreed(_)
+        ));
+        assert!(matches!(
+            ask_rematch(&mut game, CHALLENGER).unwrap(),
+            Rematch::Started
+        ));
+    }
+
+    #[test]
+    fn undo_needs_the_consent_of_the_opponent() {
+        let mut game = record(&["b2", "a1"], &[]);
+        assert!(matches!(
+            ask_undo(&mut game, OPPONENT).unwrap(),
+            UndoRequest::Refused("Only the player who just moved can ask to take it back")
+        ));
+        assert!(matches!(
+            ask_undo(&mut game, CHALLENGER).unwrap(),
+            UndoRequest::Asked {
+                mover: CHALLENGER,
+                other: OPPONENT,
+                ..
+            }
+        ));
+        assert!(matches!(
+            ask_undo(&mut game, CHALLENGER).unwrap(),
+            UndoRequest::Waiting(OPPONENT)
+        ));
+        assert!(matches!(
+            ask_undo(&mut game, OPPONENT).unwrap(),
+            UndoRequest::Answer(CHALLENGER)
+        ));
+        assert!(matches!(
+            answer_request(&mut game, CHALLENGER, true).unwrap(),
+            UndoAnswer::NotYours
+        ));
+        let UndoAnswer::Approved(taken_back) = answer_request(&mut game, OPPONENT, true).unwrap()
+        else {
+            panic!("The request was not approved");
+        };
+        assert_eq!(taken_back.moves, 1);
+        assert_eq!(game.state, ".../.X./...");
+        // Answering twice finds the request closed
+        assert!(matches!(
+            answer_request(&mut game, OPPONENT, false).unwrap(),
+            UndoAnswer::Closed
+        ));
+    }
+
+    #[test]
+    fn rejected_undo_keeps_the_move() {
+        let mut game = record(&["b2", "a1"], &[]);
+        ask_undo(&mut game, CHALLENGER).unwrap();
+        assert!(matches!(
+            answer_request(&mut game, OPPONENT, false).unwrap(),
+            UndoAnswer::Rejected
+        ));
+        assert_eq!(game.moves.len(), 2);
+        assert!(!undo_pending(&game));
+    }
+
+    #[test]
+    fn undo_against_the_bot_takes_back_its_answer() {
+        let mut game = record(&["b2", "a1"], &[("difficulty", "medium")]);
+        game.options
+            .insert("first".to_string(), CHALLENGER.to_string());
+        let UndoRequest::TakenBack(taken_back) = ask_undo(&mut game, CHALLENGER).unwrap() else {
+            panic!("The move was not taken back");
+        };
+        assert_eq!(taken_back.moves, 0);
+        assert!(game.moves.is_empty());
+        assert_eq!(game.state, ".../.../...");
+    }
+
+    #[test]
+    fn states_round_trip_for_every_variant() {
+        for variant in Variant::ALL {
+            let tiles = [Tile::X, Tile::O, Tile::Empty, Tile::O, Tile::X];
+            let state: HashMap<_, _> = variant.coords().zip(tiles.into_iter().cycle()).collect();
+            let encoded = encode_state(variant, &state);
+            assert_eq!(
+                decode_state(variant, &encoded).unwrap(),
+                state,
+                "{}",
+                variant
+            );
+            assert_eq!(
+                decode_state(variant, &encode_state(variant, &variant.empty_state())).unwrap(),
+                variant.empty_state(),
+                "{}",
+                variant
+            );
+        }
+        assert_eq!(
+            encode_state(
+                Variant::Classic,
+                &decode_state(Variant::Classic, "X.O/.X./..O").unwrap()
+            ),
+            "X.O/.X./..O"
+        );
+    }
+
+    #[test]
+    fn malformed_states_are_rejected() {
+        for state in ["X.O/.X.", "X.O/.X./..O/...", "X.O/.X./..", "X.O/.Y./..O"] {
+            assert!(decode_state(Variant::Classic, state).is_err(), "{}", state);
+        }
+        assert!(decode_state(Variant::FourByFour, "X.O/.X./..O").is_err());
+    }
+
+    #[test]
+    fn legacy_tile_ids_match_the_new_ones() {
+        let rows = [("bottom", 0), ("middle", 1), ("top", 2)];
+        let columns = [("left", 0), ("center", 1), ("right", 2)];
+        for (row_name, row) in rows {
+            for (column_name, column) in columns {
+                let coord = Coord(Row(row), Column(column));
+                let legacy = format!("{}_{}_{}", TicTacToe::NAME, row_name, column_name);
+                let new = tile_id(Variant::Classic, coord);
+                assert_eq!(
+                    parse_tile_id(&legacy).unwrap(),
+                    parse_tile_id(&new).unwrap()
+                );
+                assert_eq!(parse_tile_id(&new).unwrap(), (Variant::Classic, coord));
+            }
+        }
+    }
+
+    #[test]
+    fn tile_ids_carry_the_variant() {
+        for variant in Variant::ALL {
+            for coord in variant.coords() {
+                assert_eq!(
+                    parse_tile_id(&tile_id(variant, coord)).unwrap(),
+                    (variant, coord)
+                );
+            }
+        }
+        assert!(parse_tile_id("TicTacToe").is_err());
+        assert!(parse_tile_id("TicTacToe_top").is_err());
+        assert!(parse_tile_id("TicTacToe_classic_top_up").is_err());
+    }
+
+    #[test]
+    fn squares_are_read_from_the_bottom_left() {
+        for square in ["a1", "A1", " a1 ", "a 1"] {
+            assert_eq!(
+                Coord::from_square(Variant::Classic, square).unwrap(),
+                Coord(Row(2), Column(0)),
+                "{}",
+                square
+            );
+        }
+        assert_eq!(coord("c3"), Coord(Row(0), Column(2)));
+        assert_eq!(
+            Coord::from_square(Variant::FourByFour, "d1").unwrap(),
+            Coord(Row(3), Column(3))
+        );
+        assert_eq!(
+            Coord::from_square(Variant::FourByFourThree, "D4").unwrap(),
+            Coord(Row(0), Column(3))
+        );
+        for variant in Variant::ALL {
+            for coord in variant.coords() {
+                assert_eq!(
+                    Coord::from_square(variant, &coord.square(variant)).unwrap(),
+                    coord
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn squares_off_the_board_are_rejected() {
+        for square in ["d1", "a4", "a0", "z9"] {
+            assert_eq!(
+                Coord::from_square(Variant::Classic, square)
+                    .unwrap_err()
+                    .to_string(),
+                format!("There is no square {} on this board", square)
+            );
+        }
+        for square in ["e1", "a5"] {
+            assert!(Coord::from_square(Variant::FourByFour, square).is_err());
+        }
+    }
+
+    #[test]
+    fn malformed_squares_are_rejected() {
+        for square in ["", "  ", "b", "2", "b2x", "b2 2", "2b", "b-1", "a300"] {
+            assert!(
+                Coord::from_square(Variant::Classic, square).is_err(),
+                "{:?}",
+                square
+            );
+        }
+    }
+
+    #[test]
+    fn squares_can_be_named() {
+        assert_eq!(coord("top left"), Coord(Row(0), Column(0)));
+        assert_eq!(coord("Bottom-Right"), Coord(Row(2), Column(2)));
+        assert_eq!(coord("middle"), Coord(Row(1), Column(1)));
+        assert_eq!(coord("top middle"), Coord(Row(0), Column(1)));
+        assert!(Coord::from_square(Variant::Classic, "top").is_err());
+        assert!(Coord::from_square(Variant::Classic, "top sideways").is_err());
+        assert!(Coord::from_square(Variant::FourByFour, "middle").is_err());
+        assert_eq!(
+            Coord::from_square(Variant::FourByFour, "bottom left").unwrap(),
+            Coord(Row(3), Column(0))
+        );
+    }
+}
@@ -0,0 +1,254 @@
+use rand::seq::IndexedRandom;
+
+use crate::engine::Outcome;
+
+use super::*;
+
+/// How well the bot plays when challenged to TicTacToe
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Difficulty {
+    /// Picks a random empty tile
+    Easy,
+    /// Takes wins and blocks the opponent, but does not plan further ahead
+    #[default]
+    Medium,
+    /// Never loses on 3×3, on larger boards it looks as far ahead as there is time for
+    Perfect,
+}
+
+/// How many moves ahead the bot looks on 4×4 boards, searching them fully takes too long
+const LARGE_BOARD_DEPTH: u32 = 6;
+
+impl Difficulty {
+    pub const ALL: [Difficulty; 3] = [Difficulty::Easy, Difficulty::Medium, Difficulty::Perfect];
+
+    /// How many moves ahead the bot looks, counting its own move
+    fn depth(&self, variant: Variant) -> Option<u32> {
+        match self {
+            Difficulty::Easy => Some(0),
+            Difficulty::Medium => Some(2),
+            Difficulty::Perfect if variant.size() > 3 => Some(LARGE_BOARD_DEPTH),
+            Difficulty::Perfect => None,
+        }
+    }
+}
+
+impl TryFrom<&str> for Difficulty {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> std::result::Result<Self, Self::Error> {
+        match value {
+            "easy" => Ok(Self::Easy),
+            "medium" => Ok(Self::Medium),
+            "perfect" => Ok(Self::Perfect),
+            _ => Err(anyhow!("Could not parse Difficulty from String: {}", value)),
+        }
+    }
+}
+
+impl Display for Difficulty {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Difficulty::Easy => "easy",
+            Difficulty::Medium => "medium",
+            Difficulty::Perfect => "perfect",
+        })
+    }
+}
+
+/// The tile the player to move should take, `None` when the game is over. The search runs on
+/// a blocking thread since looking ahead on 4×4 boards takes long enough to stall other events
+pub async fn best_move(
+    variant: Variant,
+    state: &HashMap<Coord, Tile>,
+    difficulty: Difficulty,
+) -> Result<Option<Coord>> {
+    let board = variant.board(state);
+    let depth = difficulty.depth(variant);
+    let square = tokio::task::spawn_blocking(move || search(&board, depth)).await?;
+    Ok(square.map(|square| variant.coord(square)))
+}
+
+/// The best move for the player to move looking `max_depth` moves ahead, all the way when
+/// `None`. Looking no moves ahead picks a random move
+fn search<G: Game + Clone>(game: &G, max_depth: Option<u32>) -> Option<G::Move> {
+    if max_depth == Some(0) {
+        return game.legal_moves().choose(&mut rand::rng()).copied();
+    }
+    // Pick randomly between equally good moves so games don't all look the same
+    best_moves(game, max_depth)
+        .choose(&mut rand::rng())
+        .copied()
+}
+
+/// Every move that scores best for the player to move looking `max_depth` moves ahead
+fn best_moves<G: Game + Clone>(game: &G, max_depth: Option<u32>) -> Vec<G::Move> {
+    let mut best_score = i32::MIN;
+    let mut best = Vec::new();
+    for action in game.legal_moves() {
+        let mut next = game.clone();
+        if next.apply(action).is_err() {
+            continue;
+        }
+        let score = -negamax(&next, 1, max_depth, -i32::MAX, i32::MAX);
+        if score > best_score {
+            best_score = score;
+            best.clear();
+        }
+        if score == best_score {
+            best.push(action);
+        }
+    }
+    best
+}
+
+/// Score of `game` for the player to move, higher is better and quicker wins score higher
+fn negamax<G: Game + Clone>(
+    game: &G,
+    depth: u32,
+    max_depth: Option<u32>,
+    mut alpha: i32,
+    beta: i32,
+) -> i32 {
+    match game.outcome() {
+        // The player that just moved made the line
+        Some(Outcome::Win(_)) => return depth as i32 - 100,
+        Some(Outcome::Draw) => return 0,
+        None if max_depth.is_some_and(|max_depth| depth >= max_depth) => return 0,
+        None => {}
+    }
+
+    let mut best = -i32::MAX;
+    for action in game.legal_moves() {
+        let mut next = game.clone();
+        if next.apply(action).is_err() {
+            continue;
+        }
+        let score = -negamax(&next, depth + 1, max_depth, -beta, -alpha);
+        best = best.max(score);
+        alpha = alpha.max(score);
+        if alpha >= beta {
+            break;
+        }
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::engine::tictactoe::Board;
+
+    /// Every position of a 3×3 game that can be reached from the empty board, the game over
+    /// ones included
+    fn positions() -> Vec<Board> {
+        let mut seen = HashSet::new();
+        let mut positions = Vec::new();
+        let mut stack = vec![Board::new(3, 3)];
+        while let Some(board) = stack.pop() {
+            if !seen.insert((0..9).map(|square| board.get(square)).collect::<Vec<_>>()) {
+                continue;
+            }
+            for square in board.legal_moves() {
+                let mut next = board.clone();
+                next.apply(square).unwrap();
+                stack.push(next);
+            }
+            positions.push(board);
+        }
+        positions
+    }
+
+    /// The squares where the player to move would complete a line
+    fn winning_moves(board: &Board) -> Vec<usize> {
+        board
+            .legal_moves()
+            .into_iter()
+            .filter(|&square| {
+                let mut next = board.clone();
+                next.apply(square).unwrap();
+                next.outcome()
+                    .is_some_and(|outcome| outcome != Outcome::Draw)
+            })
+            .collect()
+    }
+
+    /// Whether `bot` avoids losing from `board` whatever the other player does and whichever
+    /// of its best moves it picks
+    fn never_loses(board: &Board, bot: Player) -> bool {
+        match board.outcome() {
+            Some(Outcome::Win(winner)) => return winner == bot,
+            Some(Outcome::Draw) => return true,
+            None => {}
+        }
+        let moves = if board.to_move() == bot {
+            best_moves(board, None)
+        } else {
+            board.legal_moves()
+        };
+        moves.into_iter().all(|square| {
+            let mut next = board.clone();
+            next.apply(square).unwrap();
+            never_loses(&next, bot)
+        })
+    }
+
+    #[test]
+    fn perfect_never_loses() {
+        assert!(never_loses(&Board::new(3, 3), Player::First));
+        assert!(never_loses(&Board::new(3, 3), Player::Second));
+    }
+
+    #[test]
+    fn perfect_takes_a_win_in_one() {
+        for board in positions() {
+            let wins = winning_moves(&board);
+            if wins.is_empty() {
+                continue;
+            }
+            for square in best_moves(&board, None) {
+                assert!(wins.contains(&square), "{:?} plays {}", board, square);
+            }
+        }
+    }
+
+    #[test]
+    fn medium_blocks_a_win_in_one() {
+        let depth = Difficulty::Medium.depth(Variant::Classic);
+        for board in positions() {
+            if board.outcome().is_some() || !winning_moves(&board).is_empty() {
+                continue;
+            }
+            let blocks: Vec<_> = board
+                .legal_moves()
+                .into_iter()
+                .filter(|&square| {
+                    let mut next = board.clone();
+                    next.apply(square).unwrap();
+                    winning_moves(&next).is_empty()
+                })
+                .collect();
+            // Two threats at once cannot both be blocked
+            if blocks.is_empty() || blocks.len() == board.legal_moves().len() {
+                continue;
+            }
+            for square in best_moves(&board, depth) {
+                assert!(blocks.contains(&square), "{:?} plays {}", board, square);
+            }
+        }
+    }
+
+    #[test]
+    fn easy_only_plays_legal_moves() {
+        let depth = Difficulty::Easy.depth(Variant::Classic);
+        for board in positions() {
+            let legal = board.legal_moves();
+            match search(&board, depth) {
+                Some(square) => assert!(legal.contains(&square), "{:?} plays {}", board, square),
+                None => assert!(legal.is_empty()),
+            }
+        }
+    }
+}
@@ -0,0 +1,748 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::fs;
+use std::sync::Arc;
+use std::time::Duration;
+
+use rand::seq::{IndexedRandom, SliceRandom};
+use serde::{Deserialize, Serialize};
+
+use crate::shards::Sharding;
+use crate::store::Persisted;
+
+use super::game::{self, GameRecord, Games, Players};
+use super::*;
+
+/// How long players have to answer a question
+const ANSWER_TIME: Duration = Duration::from_secs(30);
+
+/// The answer buttons, followed by `_<index of the answer>`
+const ANSWER_ID: &str = const_format::formatcp!("{}_{}", Trivia::NAME, "_answer");
+
+/// Questions of guilds without a `trivia_questions` file
+const QUESTIONS: &str = include_str!("trivia/questions.toml");
+
+/// Most wrong answers of a question, so every answer gets a button in a single row
+const MAX_WRONG: usize = 4;
+/// Longest answer, the length of a button label
+const MAX_ANSWER_LENGTH: usize = 80;
+
+/// Players listed by `/trivia scores`
+const SCORES_LIMIT: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Difficulty {
+    Easy,
+    Medium,
+    Hard,
+}
+
+impl Difficulty {
+    const ALL: [Difficulty; 3] = [Difficulty::Easy, Difficulty::Medium, Difficulty::Hard];
+
+    /// Points for answering a question of this difficulty right
+    fn points(&self) -> u32 {
+        match self {
+            Difficulty::Easy => 1,
+            Difficulty::Medium => 2,
+            Difficulty::Hard => 3,
+        }
+    }
+}
+
+impl TryFrom<&str> for Difficulty {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> std::result::Result<Self, Self::Error> {
+        match value {
+            "easy" => Ok(Self::Easy),
+            "medium" => Ok(Self::Medium),
+            "hard" => Ok(Self::Hard),
+            _ => Err(anyhow!("Could not parse Difficulty from String: {}", value)),
+        }
+    }
+}
+
+impl Display for Difficulty {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Difficulty::Easy => write!(f, "easy"),
+            Difficulty::Medium => write!(f, "medium"),
+            Difficulty::Hard => write!(f, "hard"),
+        }
+    }
+}
+
+/// A question of a question file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Question {
+    category: String,
+    difficulty: Difficulty,
+    question: String,
+    answer: String,
+    /// 1 to [`MAX_WRONG`] wrong answers, shuffled with the answer when asked
+    wrong: Vec<String>,
+}
+
+/// A question file, in TOML or JSON when the file name ends with `.json`.
+///
+/// ```toml
+/// [[questions]]
+/// category = "Science"
+/// difficulty = "easy"
+/// question = "What is the chemical symbol for gold?"
+/// answer = "Au"
+/// wrong = ["Ag", "Gd", "Go"]
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct QuestionFile {
+    questions: Vec<Question>,
+}
+
+impl QuestionFile {
+    fn parse(text: &str, json: bool) -> Result<Vec<Question>> {
+        let file: QuestionFile = if json {
+            serde_json::from_str(text)?
+        } else {
+            toml::from_str(text)?
+        };
+        for question in &file.questions {
+            if question.wrong.is_empty() || question.wrong.len() > MAX_WRONG {
+                bail!(
+                    "'{}' needs 1 to {} wrong answers",
+                    question.question,
+                    MAX_WRONG
+                );
+            }
+            let answers = || std::iter::once(&question.answer).chain(&question.wrong);
+            if answers().any(|answer| answer.is_empty() || answer.len() > MAX_ANSWER_LENGTH) {
+                bail!(
+                    "The answers of '{}' need 1 to {} characters",
+                    question.question,
+                    MAX_ANSWER_LENGTH
+                );
+            }
+        }
+        if file.questions.is_empty() {
+            bail!("There are no questions");
+        }
+        Ok(file.questions)
+    }
+}
+
+/// The questions of the guild, read again for every question so the file can be edited freely
+async fn questions(ctx: &Context, guild_id: Option<GuildId>) -> Result<Vec<Question>> {
+    let config = Settings::get(ctx).await.current();
+    let Some(path) = config.trivia_questions(guild_id) else {
+        return QuestionFile::parse(QUESTIONS, false);
+    };
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("Could not read trivia questions {}", path.display()))?;
+    QuestionFile::parse(&text, path.extension().is_some_and(|ext| ext == "json"))
+        .with_context(|| format!("Invalid trivia questions {}", path.display()))
+}
+
+/// A question that was asked, read from its [`GameRecord`]
+struct Asked {
+    category: String,
+    difficulty: Difficulty,
+    question: String,
+    /// In the order of the buttons
+    answers: Vec<String>,
+    correct: usize,
+    /// Unix timestamp in seconds at which the answer is revealed
+    deadline: i64,
+    /// The answer of every player in the order they answered
+    picks: Vec<(UserId, usize)>,
+}
+
+impl Asked {
+    fn new(question: Question, deadline: i64) -> Self {
+        let mut answers = question.wrong;
+        answers.push(question.answer.clone());
+        answers.shuffle(&mut rand::rng());
+        let correct = answers
+            .iter()
+            .position(|answer| *answer == question.answer)
+            .unwrap_or_default();
+        Self {
+            category: question.category,
+            difficulty: question.difficulty,
+            question: question.question,
+            answers,
+            correct,
+            deadline,
+            picks: Vec::new(),
+        }
+    }
+
+    fn from_record(record: &GameRecord) -> Result<Self> {
+        let option = |key: &str| {
+            record
+                .options
+                .get(key)
+                .ok_or(anyhow!("Trivia question without {}", key))
+        };
+        let picks = record
+            .moves
+            .iter()
+            .map(|pick| {
+                let (user, answer) = pick
+                    .split_once(':')
+                    .ok_or(anyhow!("Malformed trivia answer: {}", pick))?;
+                Ok((UserId::new(user.parse()?), answer.parse()?))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            category: option("category")?.clone(),
+            difficulty: option("difficulty")?.as_str().try_into()?,
+            question: option("question")?.clone(),
+            answers: serde_json::from_str(option("answers")?)?,
+            correct: option("correct")?.parse()?,
+            deadline: option("deadline")?.parse()?,
+            picks,
+        })
+    }
+
+    fn save(&self, record: &mut GameRecord) -> Result<()> {
+        let options = [
+            ("category", self.category.clone()),
+            ("difficulty", self.difficulty.to_string()),
+            ("question", self.question.clone()),
+            ("answers", serde_json::to_string(&self.answers)?),
+            ("correct", self.correct.to_string()),
+            ("deadline", self.deadline.to_string()),
+        ];
+        for (key, value) in options {
+            record.options.insert(key.to_string(), value);
+        }
+        record.moves = self
+            .picks
+            .iter()
+            .map(|(user, answer)| format!("{}:{}", user, answer))
+            .collect();
+        Ok(())
+    }
+
+    fn answer_of(&self, user_id: UserId) -> Option<usize> {
+        self.picks
+            .iter()
+            .find(|(user, _)| *user == user_id)
+            .map(|&(_, answer)| answer)
+    }
+
+    /// Players who answered right, in the order they answered
+    fn right(&self) -> Vec<UserId> {
+        self.picks
+            .iter()
+            .filter(|&&(_, answer)| answer == self.correct)
+            .map(|&(user, _)| user)
+            .collect()
+    }
+
+    fn content(&self, revealed: bool) -> String {
+        let mut msg = MessageBuilder::default();
+        msg.push_line(format!(
+            "**Trivia**: {}, {}",
+            self.category, self.difficulty
+        ))
+        .push_line(&self.question);
+        for (i, answer) in self.answers.iter().enumerate() {
+            let mark = if revealed && i == self.correct {
+                "✅"
+            } else {
+                "▫️"
+            };
+            msg.push_line(format!("{} {}: {}", mark, answer_letter(i), answer));
+        }
+
+        if !revealed {
+            msg.push(format!(
+                "One answer per player, {} answered so far. The answer is revealed <t:{}:R>",
+                self.picks.len(),
+                self.deadline
+            ));
+            return msg.build();
+        }
+        msg.push_line(format!("The answer was **{}**", self.answers[self.correct]));
+        let right = self.right();
+        if right.is_empty() {
+            msg.push("Nobody got it right");
+        } else {
+            msg.push("Right: ");
+            for (i, user) in right.iter().enumerate() {
+                if i > 0 {
+                    msg.push(", ");
+                }
+                msg.mention(user);
+            }
+            msg.push(format!(
+                ", {} of {} players, +{} points",
+                right.len(),
+                self.picks.len(),
+                self.difficulty.points()
+            ));
+        }
+        msg.build()
+    }
+
+    /// A button for the letter of every answer, once revealed they show how many picked it
+    fn components(&self, revealed: bool) -> Vec<CreateActionRow> {
+        let buttons = self
+            .answers
+            .iter()
+            .enumerate()
+            .map(|(i, _)| {
+                let button = CreateButton::new(format!("{}_{}", ANSWER_ID, i))
+                    .label(answer_letter(i).to_string())
+                    .disabled(revealed);
+                if !revealed {
+                    return button.style(ButtonStyle::Primary);
+                }
+                let picked = self.picks.iter().filter(|&&(_, pick)| pick == i).count();
+                button
+                    .label(format!("{} ({})", answer_letter(i), picked))
+                    .style(if i == self.correct {
+                        ButtonStyle::Success
+                    } else {
+                        ButtonStyle::Secondary
+                    })
+            })
+            .collect();
+        vec![CreateActionRow::Buttons(buttons)]
+    }
+}
+
+/// What became of an answer, decided while the game store is locked
+enum Answered {
+    /// The question with the answer counted, and the text of the answer
+    Counted(Asked, String),
+    /// Why the answer does not count
+    Rejected(String),
+}
+
+/// Count `answer` of `user_id` in `record`, which stays as it is when the answer is rejected
+fn pick(record: &mut GameRecord, user_id: UserId, answer: usize) -> Result<Answered> {
+    if record.finished_at.is_some() {
+        return Ok(Answered::Rejected("The question is over".to_string()));
+    }
+    let mut asked = Asked::from_record(record)?;
+    if Timestamp::now().unix_timestamp() >= asked.deadline {
+        return Ok(Answered::Rejected(
+            "Time is up, the answer is revealed in a moment".to_string(),
+        ));
+    }
+    if let Some(picked) = asked.answer_of(user_id) {
+        return Ok(Answered::Rejected(format!(
+            "You already answered **{}**, there is one answer per player",
+            asked.answers[picked]
+        )));
+    }
+    let Some(text) = asked.answers.get(answer).cloned() else {
+        bail!("Trivia answer {} out of range", answer);
+    };
+    asked.picks.push((user_id, answer));
+    asked.save(record)?;
+    Ok(Answered::Counted(asked, text))
+}
+
+fn answer_letter(index: usize) -> char {
+    (b'A' + index as u8) as char
+}
+
+/// The game message of the question, handed to `run_job` when the answer is revealed
+#[derive(Debug, Serialize, Deserialize)]
+struct Reveal {
+    channel_id: ChannelId,
+    message_id: MessageId,
+}
+
+/// Trivia results of a player
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TriviaScore {
+    /// 1, 2 or 3 for every right answer depending on the difficulty
+    pub points: u32,
+    pub right: u32,
+    pub answered: u32,
+}
+
+type GuildScores = HashMap<UserId, TriviaScore>;
+
+/// Trivia scores per guild
+pub struct TriviaScores {
+    guilds: Mutex<HashMap<Option<GuildId>, GuildScores>>,
+    store: Persisted<GuildScores>,
+}
+
+impl TypeMapKey for TriviaScores {
+    type Value = Arc<TriviaScores>;
+}
+
+impl TriviaScores {
+    pub fn load(sharding: &Sharding) -> Self {
+        let store: Persisted<GuildScores> = Persisted::new("trivia");
+        Self {
+            guilds: Mutex::new(store.load(sharding)),
+            store,
+        }
+    }
+
+    pub async fn get(ctx: &Context) -> Arc<Self> {
+        ctx.data
+            .read()
+            .await
+            .get::<Self>()
+            .cloned()
+            .expect("TriviaScores to be inserted into the client data")
+    }
+
+    async fn guild(&self, guild_id: Option<GuildId>) -> GuildScores {
+        self.guilds
+            .lock()
+            .await
+            .get(&guild_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Count the answers to a question that was revealed
+    async fn record(&self, guild_id: Option<GuildId>, asked: &Asked) {
+        let mut guilds = self.guilds.lock().await;
+        let guild = guilds.entry(guild_id).or_default();
+        for &(user, answer) in &asked.picks {
+            let score = guild.entry(user).or_default();
+            score.answered += 1;
+            if answer == asked.correct {
+                score.right += 1;
+                score.points += asked.difficulty.points();
+            }
+        }
+        self.store.save(guild_id, guild);
+    }
+}
+
+/// Multiple choice questions for everyone in the channel, with a score table per guild
+pub struct Trivia;
+
+#[async_trait]
+impl CustomCommand for Trivia {
+    const NAME: &'static str = "trivia";
+
+    fn command() -> CreateCommand {
+        CreateCommand::new(Self::NAME)
+            .description("Answer multiple choice questions against everyone in the channel")
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "question",
+                    "Ask a question in this channel",
+                )
+                .add_sub_option(CreateCommandOption::new(
+                    CommandOptionType::String,
+                    "category",
+                    "Only ask questions of this category",
+                ))
+                .add_sub_option(Difficulty::ALL.into_iter().fold(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "difficulty",
+                        "Only ask questions of this difficulty, harder questions give more points",
+                    ),
+                    |option, difficulty| {
+                        option.add_string_choice(difficulty.to_string(), difficulty.to_string())
+                    },
+                )),
+            )
+            .add_option(CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "scores",
+                "Show the players with the most points",
+            ))
+            .to_owned()
+    }
+
+    async fn slash(ctx: Context, command: CommandInteraction) -> Result<()> {
+        let options = command.data.options();
+        let Some(ResolvedOption {
+            name: subcommand,
+            value,
+            ..
+        }) = options.first()
+        else {
+            bail!("No trivia subcommand: {:?}", command.data.options);
+        };
+        let response = match (*subcommand, value) {
+            ("question", ResolvedValue::SubCommand(sub_options)) => {
+                let mut category = None;
+                let mut difficulty = None;
+                for option in sub_options {
+                    match (option.name, &option.value) {
+                        ("category", ResolvedValue::String(value)) => category = Some(*value),
+                        ("difficulty", ResolvedValue::String(value)) => {
+                            difficulty = Some(Difficulty::try_from(*value)?)
+                        }
+                        _ => {}
+                    }
+                }
+                return ask(&ctx, &command, category, difficulty).await;
+            }
+            ("scores", _) => CreateInteractionResponseMessage::new()
+                .content(scores(
+                    TriviaScores::get(&ctx).await.guild(command.guild_id).await,
+                ))
+                .allowed_mentions(CreateAllowedMentions::new()),
+            _ => bail!("Unknown trivia subcommand: {}", subcommand),
+        };
+        command
+            .create_response(&ctx, CreateInteractionResponse::Message(response))
+            .await?;
+        Ok(())
+    }
+
+    async fn component(ctx: Context, interaction: ComponentInteraction) -> Result<()> {
+        let Some(answer) = interaction
+            .data
+            .custom_id
+            .strip_prefix(ANSWER_ID)
+            .and_then(|answer| answer.strip_prefix('_'))
+        else {
+            bail!("Unknown trivia customId: {}", interaction.data.custom_id);
+        };
+        let answer: usize = answer.parse()?;
+        let user_id = interaction.user.id;
+        let answered = Games::get(&ctx)
+            .await
+            .update(interaction.message.id, |record| {
+                pick(record, user_id, answer)
+            })
+            .await
+            .transpose()?;
+        let (asked, text) = match answered {
+            Some(Answered::Counted(asked, text)) => (asked, text),
+            Some(Answered::Rejected(reason)) => {
+                return game::reply_ephemeral(&ctx, &interaction, reason).await
+            }
+            None => return game::reply_ephemeral(&ctx, &interaction, "The question is over").await,
+        };
+        interaction
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::UpdateMessage(
+                    CreateInteractionResponseMessage::new().content(asked.content(false)),
+                ),
+            )
+            .await?;
+        interaction
+            .create_followup(
+                &ctx,
+                CreateInteractionResponseFollowup::new()
+                    .content(format!(
+                        "You answered **{}**, the answer is revealed <t:{}:R>",
+                        text, asked.deadline
+                    ))
+                    .ephemeral(true),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Reveals the answer once the time to answer is over
+    async fn run_job(ctx: Context, job: Job) -> Result<()> {
+        let reveal: Reveal = serde_json::from_str(&job.payload)?;
+        // Answered questions are not kept, they would push games out of the history
+        let Some(record) = Games::get(&ctx).await.remove(reveal.message_id).await else {
+            return Ok(());
+        };
+        let asked = Asked::from_record(&record)?;
+        TriviaScores::get(&ctx)
+            .await
+            .record(job.guild_id, &asked)
+            .await;
+        reveal
+            .channel_id
+            .edit_message(
+                &ctx,
+                reveal.message_id,
+                EditMessage::new()
+                    .content(asked.content(true))
+                    .components(asked.components(true))
+                    .allowed_mentions(CreateAllowedMentions::new()),
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+/// Post a random question of the guild that matches the filters
+async fn ask(
+    ctx: &Context,
+    command: &CommandInteraction,
+    category: Option<&str>,
+    difficulty: Option<Difficulty>,
+) -> Result<()> {
+    let reply = |content: String| {
+        CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new()
+                .content(content)
+                .ephemeral(true),
+        )
+    };
+    let games = Games::get(ctx).await;
+    if let Some(running) = games
+        .running(Trivia::NAME, command.channel_id)
+        .await
+        .first()
+    {
+        let content = MessageBuilder::default()
+            .push("Wait for the answer to the question here: ")
+            .push(
+                running
+                    .message_id
+                    .link(command.channel_id, command.guild_id),
+            )
+            .build();
+        command.create_response(ctx, reply(content)).await?;
+        return Ok(());
+    }
+
+    let questions = match questions(ctx, command.guild_id).await {
+        Ok(questions) => questions,
+        Err(err) => {
+            error!("{:?}", err);
+            let content = "The questions of this server could not be read".to_string();
+            command.create_response(ctx, reply(content)).await?;
+            return Ok(());
+        }
+    };
+    let matching: Vec<_> = questions
+        .iter()
+        .filter(|question| {
+            category.is_none_or(|category| question.category.eq_ignore_ascii_case(category))
+                && difficulty.is_none_or(|difficulty| question.difficulty == difficulty)
+        })
+        .collect();
+    let Some(question) = matching.choose(&mut rand::rng()) else {
+        let mut categories: Vec<_> = questions
+            .iter()
+            .map(|question| question.category.as_str())
+            .collect();
+        categories.sort_unstable();
+        categories.dedup();
+        let content = format!(
+            "There are no such questions, the categories are: {}",
+            categories.join(", ")
+        );
+        command.create_response(ctx, reply(content)).await?;
+        return Ok(());
+    };
+
+    let deadline = Timestamp::now().unix_timestamp() + ANSWER_TIME.as_secs() as i64;
+    let asked = Asked::new((*question).clone(), deadline);
+    command
+        .create_response(
+            ctx,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content(asked.content(false))
+                    .components(asked.components(false)),
+            ),
+        )
+        .await?;
+    let message = response_message(ctx, command).await?;
+    // Everyone may answer, the asker is only kept to fill the record
+    let players = Players {
+        challenger: command.user.id,
+        opponent: command.user.id,
+    };
+    let mut record = GameRecord::new(Trivia::NAME, &message, &players);
+    asked.save(&mut record)?;
+    games.insert(record).await;
+    let reveal = Reveal {
+        channel_id: message.channel_id,
+        message_id: message.id,
+    };
+    Scheduler::get(ctx)
+        .await
+        .add(
+            Job::once(Trivia::NAME, deadline)
+                .guild(command.guild_id)
+                .payload(serde_json::to_string(&reveal)?),
+        )
+        .await?;
+    Ok(())
+}
+
+/// The players with the most points
+fn scores(scores: GuildScores) -> String {
+    if scores.is_empty() {
+        return "Nobody has answered a trivia question yet".to_string();
+    }
+    let mut players: Vec<_> = scores.into_iter().collect();
+    players.sort_by_key(|(_, score)| std::cmp::Reverse((score.points, score.right)));
+    let mut content = MessageBuilder::default();
+    content.push_line("**Trivia scores**");
+    for (i, (user, score)) in players.into_iter().take(SCORES_LIMIT).enumerate() {
+        content
+            .push(format!("{}. ", i + 1))
+            .mention(&user)
+            .push_line(format!(
+                " {} points, {} of {} right",
+                score.points, score.right, score.answered
+            ));
+    }
+    content.build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A question file with a single question that has `wrong` wrong answers
+    fn with_wrong(wrong: usize) -> String {
+        let wrong: Vec<_> = (0..wrong).map(|i| format!("\"{}\"", i)).collect();
+        format!(
+            "[[questions]]\n\
+             category = \"Science\"\n\
+             difficulty = \"easy\"\n\
+             question = \"What is the chemical symbol for gold?\"\n\
+             answer = \"Au\"\n\
+             wrong = [{}]\n",
+            wrong.join(", ")
+        )
+    }
+
+    #[test]
+    fn bundled_questions_parse() {
+        let questions = QuestionFile::parse(QUESTIONS, false).unwrap();
+        assert!(questions.len() >= 50);
+        for difficulty in [Difficulty::Easy, Difficulty::Medium, Difficulty::Hard] {
+            assert!(questions.iter().any(|q| q.difficulty == difficulty));
+        }
+    }
+
+    #[test]
+    fn wrong_answers_are_limited() {
+        assert!(QuestionFile::parse(&with_wrong(0), false).is_err());
+        assert_eq!(QuestionFile::parse(&with_wrong(1), false).unwrap().len(), 1);
+        assert_eq!(QuestionFile::parse(&with_wrong(4), false).unwrap().len(), 1);
+        assert_eq!(
+            QuestionFile::parse(&with_wrong(5), false)
+                .unwrap_err()
+                .to_string(),
+            "'What is the chemical symbol for gold?' needs 1 to 4 wrong answers"
+        );
+    }
+
+    #[test]
+    fn json_questions_parse() {
+        let json = r#"{"questions": [{"category": "Science", "difficulty": "hard",
+            "question": "Q?", "answer": "A", "wrong": ["B"]}]}"#;
+        let questions = QuestionFile::parse(json, true).unwrap();
+        assert_eq!(questions[0].difficulty, Difficulty::Hard);
+        assert!(QuestionFile::parse(r#"{"questions": []}"#, true).is_err());
+    }
+}
@@ -0,0 +1,440 @@
+use std::time::Duration;
+
+use crate::engine::ultimate::Ultimate;
+use crate::engine::{Game, Outcome, Player};
+
+use super::game::{self, GameRecord, Games, Players, Position};
+use super::*;
+
+/// How long a game may go without a move before the board is disabled
+const GAME_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+const REMOVE_ID: &str = const_format::formatcp!("{}_{}", UltimateTicTacToe::NAME, "_remove");
+const EXPORT_ID: &str = const_format::formatcp!("{}_{}", UltimateTicTacToe::NAME, "_export");
+/// The select menu that picks the board shown as buttons
+const BOARD_ID: &str = const_format::formatcp!("{}_{}", UltimateTicTacToe::NAME, "_board");
+
+const X_EMOJI: &str = "❌";
+const O_EMOJI: &str = "⭕";
+const EMPTY_EMOJI: &str = "⬛";
+
+/// Names of the small boards, row by row from the top left
+const BOARD_NAMES: [&str; 9] = [
+    "top left",
+    "top",
+    "top right",
+    "left",
+    "middle",
+    "right",
+    "bottom left",
+    "bottom",
+    "bottom right",
+];
+
+/// TicTacToe on nine small boards, where every move decides the board the opponent plays on
+pub struct UltimateTicTacToe;
+
+#[async_trait]
+impl CustomCommand for UltimateTicTacToe {
+    const NAME: &'static str = "UltimateTicTacToe";
+
+    fn command() -> CreateCommand {
+        CreateCommand::new(Self::NAME)
+            .kind(CommandType::User)
+            .to_owned()
+    }
+
+    async fn slash(ctx: Context, command: CommandInteraction) -> Result<()> {
+        let Some(ResolvedTarget::User(target, _)) = command.data.target() else {
+            bail!("No user for the user command UltimateTicTacToe");
+        };
+        if target.bot {
+            command
+                .create_response(
+                    &ctx,
+                    CreateInteractionResponse::Message(
+                        CreateInteractionResponseMessage::new()
+                            .content("You cannot challenge a bot to Ultimate TicTacToe")
+                            .ephemeral(true),
+                    ),
+                )
+                .await?;
+            return Ok(());
+        }
+
+        let players = Players {
+            challenger: command.user.id,
+            opponent: target.id,
+        };
+        let game = Ultimate::default();
+        let header = MessageBuilder::default()
+            .mention(&target.id)
+            .push(" has been challenged to Ultimate TicTacToe!")
+            .build();
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content(content(&header, &game, &players))
+                        .components(create_components(&game, default_board(&game, None))),
+                ),
+            )
+            .await?;
+        let message = response_message(&ctx, &command).await?;
+        let mut record = GameRecord::new(Self::NAME, &message, &players);
+        record.state = encode_state(&game);
+        Games::get(&ctx).await.insert(record).await;
+        Collectors::get(&ctx)
+            .await
+            .track(Self::NAME, &message, GAME_TTL)
+            .await;
+        Ok(())
+    }
+
+    async fn component(ctx: Context, interaction: ComponentInteraction) -> Result<()> {
+        let games = Games::get(&ctx).await;
+        let Some(mut record) = games.find(interaction.message.id).await else {
+            return game::reply_ephemeral(&ctx, &interaction, "The game is no longer recorded")
+                .await;
+        };
+        let players = record.players();
+        // Anyone may export the moves
+        if interaction.data.custom_id == EXPORT_ID {
+            let content = MessageBuilder::default()
+                .push_codeblock_safe(notation(&record)?, None)
+                .build();
+            return game::reply_ephemeral(&ctx, &interaction, content).await;
+        }
+        if !players.contains(interaction.user.id) {
+            return game::reply_ephemeral(&ctx, &interaction, "You are not part of this game")
+                .await;
+        }
+        if interaction.data.custom_id == REMOVE_ID {
+            return game::remove(&ctx, &interaction).await;
+        }
+
+        let mut game = position(&record, record.moves.len())?;
+        if interaction.user.id != user(&players, game.to_move()) {
+            return game::reply_ephemeral(&ctx, &interaction, "Its not your turn").await;
+        }
+
+        // Show another board without making a move
+        if interaction.data.custom_id == BOARD_ID {
+            let ComponentInteractionDataKind::StringSelect { values } = &interaction.data.kind
+            else {
+                bail!("The board picker is not a string select menu");
+            };
+            let board = values.first().ok_or(anyhow!("No board picked"))?.parse()?;
+            if !game.playable().contains(&board) {
+                return game::reply_ephemeral(&ctx, &interaction, "That board cannot be played")
+                    .await;
+            }
+            interaction
+                .create_response(
+                    &ctx,
+                    CreateInteractionResponse::UpdateMessage(
+                        CreateInteractionResponseMessage::new()
+                            .components(create_components(&game, board)),
+                    ),
+                )
+                .await?;
+            return Ok(());
+        }
+
+        let action = parse_move(
+            interaction
+                .data
+                .custom_id
+                .split_once("_")
+                .ok_or(anyhow!("Invalid customId, does not have a '_'"))?
+                .1,
+        )?;
+        if game.apply(action).is_err() {
+            return game::reply_ephemeral(&ctx, &interaction, "That square cannot be taken").await;
+        }
+        record.moves.push(format!("{}_{}", action.0, action.1));
+        record.state = encode_state(&game);
+        let finished = game.outcome().is_some();
+        if finished {
+            games.finish(record).await;
+        } else {
+            games.insert(record).await;
+        }
+
+        // Preserve first line
+        let header = interaction
+            .message
+            .content
+            .split_once("\n")
+            .unwrap_or_default()
+            .0;
+        interaction
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::UpdateMessage(
+                    CreateInteractionResponseMessage::new()
+                        .content(content(header, &game, &players))
+                        .components(create_components(
+                            &game,
+                            default_board(&game, Some(action.0)),
+                        )),
+                ),
+            )
+            .await?;
+        let collectors = Collectors::get(&ctx).await;
+        if finished {
+            collectors.untrack(interaction.message.id).await;
+        } else {
+            collectors
+                .track(Self::NAME, &interaction.message, GAME_TTL)
+                .await;
+        }
+        Ok(())
+    }
+
+    async fn on_expire(ctx: Context, collector: Collector) -> Result<()> {
+        let mut message = collector
+            .channel_id
+            .message(&ctx, collector.message_id)
+            .await?;
+        // Keep the header and the board
+        let board = message
+            .content
+            .rsplit_once("\n")
+            .map_or(message.content.as_str(), |(board, _)| board);
+        let content = MessageBuilder::default()
+            .push_line(board)
+            .push("The game has expired")
+            .build();
+        let components = collectors::disabled_components(&message.components);
+        message
+            .edit(
+                &ctx,
+                EditMessage::new().content(content).components(components),
+            )
+            .await?;
+        Games::get(&ctx).await.remove(collector.message_id).await;
+        Ok(())
+    }
+}
+
+/// The opponent moves first, as in the other games
+fn user(players: &Players, player: Player) -> UserId {
+    match player {
+        Player::First => players.opponent,
+        Player::Second => players.challenger,
+    }
+}
+
+fn emoji(player: Player) -> &'static str {
+    match player {
+        Player::First => X_EMOJI,
+        Player::Second => O_EMOJI,
+    }
+}
+
+/// The header, the large board and whose turn it is
+fn content(header: &str, game: &Ultimate, players: &Players) -> String {
+    let mut msg = MessageBuilder::default();
+    msg.push_line(header).push_codeblock(meta_board(game), None);
+    match game.outcome() {
+        Some(Outcome::Draw) => msg.push("The game is a tie"),
+        Some(Outcome::Win(player)) => msg
+            .push(emoji(player))
+            .mention(&user(players, player))
+            .push(" is the winner!"),
+        None => {
+            let player = game.to_move();
+            msg.push(emoji(player))
+                .mention(&user(players, player))
+                .push("'s turn");
+            match game.playable().as_slice() {
+                [board] => msg.push(format!(" on the {} board", BOARD_NAMES[*board])),
+                _ => msg.push(", pick any open board"),
+            }
+        }
+    };
+    msg.build()
+}
+
+/// All nine boards as text. A won board is filled with the mark of its winner, and only the
+/// empty squares that can be taken next are dotted
+fn meta_board(game: &Ultimate) -> String {
+    let playable = game.playable();
+    let mut text = String::new();
+    for row in 0..9 {
+        if row > 0 && row % 3 == 0 {
+            text.push_str("------+-------+------\n");
+        }
+        let groups: Vec<String> = (0..3)
+            .map(|column| {
+                let board = row / 3 * 3 + column;
+                (0..3)
+                    .map(|i| {
+                        let square = row % 3 * 3 + i;
+                        let mark = match game.board(board).outcome() {
+                            Some(Outcome::Win(player)) => Some(player),
+                            _ => game.board(board).get(square),
+                        };
+                        match mark {
+                            Some(Player::First) => "X",
+                            Some(Player::Second) => "O",
+                            None if playable.contains(&board) => ".",
+                            None => " ",
+                        }
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect();
+        text.push_str(&groups.join(" | "));
+        text.push('\n');
+    }
+    text
+}
+
+/// The board shown as buttons after a move on `last`, the board that has to be played on or
+/// the first open board when the player may pick
+fn default_board(game: &Ultimate, last: Option<usize>) -> usize {
+    game.playable()
+        .first()
+        .copied()
+        .or(last)
+        .unwrap_or(BOARD_NAMES.len() / 2)
+}
+
+/// A select menu for the board, its squares as buttons and a row for Export and Remove
+fn create_components(game: &Ultimate, board: usize) -> Vec<CreateActionRow> {
+    let playable = game.playable();
+    let options = (0..BOARD_NAMES.len())
+        .filter(|&i| i == board || playable.contains(&i))
+        .map(|i| {
+            CreateSelectMenuOption::new(format!("The {} board", BOARD_NAMES[i]), i.to_string())
+                .default_selection(i == board)
+        })
+        .collect();
+    let select = CreateSelectMenu::new(BOARD_ID, CreateSelectMenuKind::String { options })
+        .placeholder("Pick a board")
+        .disabled(playable.len() <= 1);
+
+    let small = game.board(board);
+    let line = small.winning_line().unwrap_or_default();
+    let tiles = (0..3).map(|row| {
+        CreateActionRow::Buttons(
+            (0..3)
+                .map(|column| {
+                    let square = row * 3 + column;
+                    let mark = small.get(square);
+                    CreateButton::new(format!("{}_{}_{}", UltimateTicTacToe::NAME, board, square))
+                        .emoji(ReactionType::Unicode(
+                            mark.map_or(EMPTY_EMOJI, emoji).to_string(),
+                        ))
+                        .disabled(mark.is_some() || !playable.contains(&board))
+                        .style(if line.contains(&square) {
+                            ButtonStyle::Success
+                        } else {
+                            ButtonStyle::Secondary
+                        })
+                })
+                .collect(),
+        )
+    });
+
+    std::iter::once(CreateActionRow::SelectMenu(select))
+        .chain(tiles)
+        .chain(std::iter::once(CreateActionRow::Buttons(vec![
+            CreateButton::new(EXPORT_ID)
+                .label("Export")
+                .style(ButtonStyle::Secondary),
+            CreateButton::new(REMOVE_ID)
+                .label("Remove")
+                .style(ButtonStyle::Danger),
+        ])))
+        .collect()
+}
+
+/// A move like `4_0`, the board and the square on it
+fn parse_move(value: &str) -> Result<(usize, usize)> {
+    let (board, square) = value
+        .split_once('_')
+        .ok_or(anyhow!("Malformed Ultimate TicTacToe move: {}", value))?;
+    Ok((board.parse()?, square.parse()?))
+}
+
+/// The boards row by row from the top left separated by `/`, each like the state of
+/// [`super::tictactoe`]
+fn encode_state(game: &Ultimate) -> String {
+    (0..BOARD_NAMES.len())
+        .map(|board| {
+            (0..9)
+                .map(|square| match game.board(board).get(square) {
+                    Some(Player::First) => 'X',
+                    Some(Player::Second) => 'O',
+                    None => '.',
+                })
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// The game after the first `moves` moves of the record
+fn position(record: &GameRecord, moves: usize) -> Result<Ultimate> {
+    let mut game = Ultimate::default();
+    for action in record.moves.iter().take(moves) {
+        game.apply(parse_move(action)?)?;
+    }
+    Ok(game)
+}
+
+/// The square on the large board, like `e5` with columns from the left and rows from the
+/// bottom
+fn square((board, square): (usize, usize)) -> String {
+    let column = board % 3 * 3 + square % 3;
+    let row = board / 3 * 3 + square / 3;
+    format!("{}{}", (b'a' + column as u8) as char, 9 - row)
+}
+
+/// The moves of a game as squares of the large board, see [`game::notation`]
+pub(super) fn notation(record: &GameRecord) -> Result<String> {
+    let game = position(record, record.moves.len())?;
+    let moves = record
+        .moves
+        .iter()
+        .map(|action| Ok(square(parse_move(action)?)))
+        .collect::<Result<Vec<_>>>()?;
+    let result = match game.outcome() {
+        None => "*",
+        Some(Outcome::Draw) => "1/2-1/2",
+        Some(Outcome::Win(Player::First)) => "1-0",
+        Some(Outcome::Win(Player::Second)) => "0-1",
+    };
+    Ok(game::notation(UltimateTicTacToe::NAME, &moves, result))
+}
+
+/// The large board after the first `moves` moves of the record for `/replay`
+pub(super) fn replay(record: &GameRecord, moves: usize) -> Result<Position> {
+    let game = position(record, moves)?;
+    let mut content = MessageBuilder::default();
+    content.push_bold_line("Ultimate TicTacToe");
+    match moves.checked_sub(1).and_then(|i| record.moves.get(i)) {
+        Some(action) => content
+            .push(emoji(game.to_move().other()))
+            .push(" ")
+            .push(square(parse_move(action)?)),
+        None => content.push("Start"),
+    };
+    match game.outcome() {
+        Some(Outcome::Draw) => content.push(", the game is a tie"),
+        Some(Outcome::Win(player)) => content.push(", ").push(emoji(player)).push(" wins"),
+        None => &mut content,
+    };
+    content
+        .push_line("")
+        .push_codeblock(meta_board(&game), None);
+    Ok(Position {
+        content: content.build(),
+        components: Vec::new(),
+    })
+}
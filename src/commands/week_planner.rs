@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use super::*;
 
 pub struct WeekPlanner;
@@ -36,16 +38,39 @@ impl CustomCommand for WeekPlanner {
             "Saturday",
             "Sunday",
         ] {
-            let follow_up = command
+            let mut follow_up = command
                 .channel_id
                 .send_message(&ctx, CreateMessage::new().content(day))
                 .await?;
+            follow_up.guild_id = command.guild_id;
             for reaction in &msg.reactions {
                 follow_up
                     .react(&ctx, reaction.reaction_type.clone())
                     .await?;
             }
+            Collectors::get(&ctx)
+                .await
+                .track(Self::NAME, &follow_up, PLANNER_TTL)
+                .await;
         }
         Ok(())
     }
+
+    async fn on_expire(ctx: Context, collector: Collector) -> Result<()> {
+        let mut message = collector
+            .channel_id
+            .message(&ctx, collector.message_id)
+            .await?;
+        let content = MessageBuilder::default()
+            .push_strike(&message.content)
+            .push(" (closed)")
+            .build();
+        message
+            .edit(&ctx, EditMessage::new().content(content))
+            .await?;
+        Ok(())
+    }
 }
+
+/// A planner covers the coming week, after that the days are marked as closed
+const PLANNER_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
@@ -0,0 +1,388 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, LazyLock};
+
+use serde::{Deserialize, Serialize};
+
+use crate::messages::MessageHandler;
+use crate::shards::Sharding;
+use crate::store::Persisted;
+
+use super::*;
+
+/// Players listed by `/wordchain scores`
+const SCORES_LIMIT: usize = 10;
+
+/// Words that can be played, one per line
+static DICTIONARY: LazyLock<HashSet<&'static str>> =
+    LazyLock::new(|| include_str!("wordchain/words.txt").lines().collect());
+
+/// A channel game where every word has to start with the last letter of the word before it
+pub struct WordChain;
+
+/// The game of a channel and its records
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Chain {
+    /// Whether messages in the channel are played
+    pub active: bool,
+    /// The words of the current chain in the order they were played
+    pub words: Vec<String>,
+    /// Who played the last word of the current chain
+    pub last_player: Option<UserId>,
+    /// Length of the longest chain
+    pub best: usize,
+    /// Words every player added to chains of the channel
+    pub scores: HashMap<UserId, u32>,
+}
+
+impl Chain {
+    /// The letter the next word has to start with
+    fn next_letter(&self) -> Option<char> {
+        self.words.last().and_then(|word| word.chars().last())
+    }
+
+    /// Why `word` of `user` cannot be the next word, if it cannot
+    fn check(&self, user: UserId, word: &str) -> Option<String> {
+        if self.last_player == Some(user) {
+            return Some("You cannot play two words in a row".to_string());
+        }
+        if let Some(letter) = self.next_letter() {
+            if !word.starts_with(letter) {
+                return Some(format!("**{}** does not start with **{}**", word, letter));
+            }
+        }
+        if !DICTIONARY.contains(word) {
+            return Some(format!("**{}** is not in my dictionary", word));
+        }
+        if self.words.iter().any(|played| played == word) {
+            return Some(format!("**{}** was already played in this chain", word));
+        }
+        None
+    }
+
+    /// Add `word` of `user` to the chain, or break the chain and return why together with the
+    /// number of words it had
+    fn play(&mut self, user: UserId, word: &str) -> std::result::Result<(), (String, usize)> {
+        match self.check(user, word) {
+            None => {
+                self.words.push(word.to_string());
+                self.last_player = Some(user);
+                self.best = self.best.max(self.words.len());
+                *self.scores.entry(user).or_default() += 1;
+                Ok(())
+            }
+            // Any mistake breaks the chain
+            Some(reason) => {
+                let length = self.words.len();
+                self.words.clear();
+                self.last_player = None;
+                Err((reason, length))
+            }
+        }
+    }
+}
+
+/// Word chains of every channel of a guild
+type GuildChains = HashMap<ChannelId, Chain>;
+
+/// Word chains per guild
+pub struct WordChains {
+    guilds: Mutex<HashMap<Option<GuildId>, GuildChains>>,
+    store: Persisted<GuildChains>,
+}
+
+impl TypeMapKey for WordChains {
+    type Value = Arc<WordChains>;
+}
+
+impl WordChains {
+    pub fn load(sharding: &Sharding) -> Self {
+        let store: Persisted<GuildChains> = Persisted::new("wordchains");
+        Self {
+            guilds: Mutex::new(store.load(sharding)),
+            store,
+        }
+    }
+
+    pub async fn get(ctx: &Context) -> Arc<Self> {
+        ctx.data
+            .read()
+            .await
+            .get::<Self>()
+            .cloned()
+            .expect("WordChains to be inserted into the client data")
+    }
+
+    async fn chain(&self, guild_id: Option<GuildId>, channel_id: ChannelId) -> Option<Chain> {
+        self.guilds
+            .lock()
+            .await
+            .get(&guild_id)?
+            .get(&channel_id)
+            .cloned()
+    }
+
+    /// Change the chain of a channel and save it
+    async fn update<T>(
+        &self,
+        guild_id: Option<GuildId>,
+        channel_id: ChannelId,
+        change: impl FnOnce(&mut Chain) -> T,
+    ) -> T {
+        let mut guilds = self.guilds.lock().await;
+        let guild = guilds.entry(guild_id).or_default();
+        let result = change(guild.entry(channel_id).or_default());
+        self.store.save(guild_id, guild);
+        result
+    }
+}
+
+#[async_trait]
+impl CustomCommand for WordChain {
+    const NAME: &'static str = "wordchain";
+
+    fn command() -> CreateCommand {
+        CreateCommand::new(Self::NAME)
+            .description("Play words that start with the last letter of the word before")
+            .add_option(CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "start",
+                "Play every single word message in this channel, needs Manage Channels",
+            ))
+            .add_option(CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "stop",
+                "Stop playing in this channel, needs Manage Channels",
+            ))
+            .add_option(CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "scores",
+                "Show the current chain, the longest chain and the best players",
+            ))
+            .to_owned()
+    }
+
+    async fn slash(ctx: Context, command: CommandInteraction) -> Result<()> {
+        let options = command.data.options();
+        let Some(ResolvedOption {
+            name: subcommand, ..
+        }) = options.first()
+        else {
+            bail!("No wordchain subcommand: {:?}", command.data.options);
+        };
+        let chains = WordChains::get(&ctx).await;
+        let (guild_id, channel_id) = (command.guild_id, command.channel_id);
+        // Starting and stopping changes what happens to every message in the channel
+        let manages_channel = command.guild_id.is_none()
+            || command
+                .member
+                .as_ref()
+                .and_then(|member| member.permissions)
+                .is_some_and(|permissions| permissions.manage_channels());
+
+        let (content, ephemeral) = match *subcommand {
+            "start" | "stop" if !manages_channel => (
+                "You need the Manage Channels permission for that".to_string(),
+                true,
+            ),
+            "start" => {
+                let letter = chains
+                    .update(guild_id, channel_id, |chain| {
+                        chain.active = true;
+                        chain.next_letter()
+                    })
+                    .await;
+                let content = match letter {
+                    Some(letter) => format!(
+                        "The word chain continues, the next word starts with **{}**",
+                        letter
+                    ),
+                    None => "The word chain has started, send any word to begin. Every word \
+                             has to start with the last letter of the word before it"
+                        .to_string(),
+                };
+                (content, false)
+            }
+            "stop" => {
+                chains
+                    .update(guild_id, channel_id, |chain| chain.active = false)
+                    .await;
+                (
+                    "The word chain has stopped, the records are kept".to_string(),
+                    false,
+                )
+            }
+            "scores" => (
+                scores(chains.chain(guild_id, channel_id).await.unwrap_or_default()),
+                false,
+            ),
+            _ => bail!("Unknown wordchain subcommand: {}", subcommand),
+        };
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content(content)
+                        .ephemeral(ephemeral)
+                        .allowed_mentions(CreateAllowedMentions::new()),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl MessageHandler for WordChain {
+    /// A message of a single word is the next word of the chain of its channel
+    async fn message(ctx: &Context, message: &Message) -> Result<()> {
+        let word = message.content.trim().to_lowercase();
+        if word.len() < 2 || !word.chars().all(|c| c.is_ascii_lowercase()) {
+            return Ok(());
+        }
+        let chains = WordChains::get(ctx).await;
+        let (guild_id, channel_id) = (message.guild_id, message.channel_id);
+        if !chains
+            .chain(guild_id, channel_id)
+            .await
+            .is_some_and(|chain| chain.active)
+        {
+            return Ok(());
+        }
+
+        let user = message.author.id;
+        let verdict = chains
+            .update(guild_id, channel_id, |chain| {
+                chain
+                    .play(user, &word)
+                    .map_err(|(reason, length)| (reason, length, chain.best))
+            })
+            .await;
+        let Err((reason, length, best)) = verdict else {
+            message.react(ctx, '✅').await?;
+            return Ok(());
+        };
+
+        message.react(ctx, '❌').await?;
+        let mut content = MessageBuilder::default();
+        content.push(reason);
+        if length > 0 {
+            content.push(format!(
+                ". The chain ended after {} words, the longest chain here has {}",
+                length, best
+            ));
+            content.push(". Start a new chain with any word");
+        }
+        message.reply(ctx, content.build()).await?;
+        Ok(())
+    }
+}
+
+/// The current chain, the longest chain and the players with the most words
+fn scores(chain: Chain) -> String {
+    let mut content = MessageBuilder::default();
+    match chain.next_letter() {
+        Some(letter) => content.push_line(format!(
+            "The current chain has {} words, the next word starts with **{}**",
+            chain.words.len(),
+            letter
+        )),
+        None => content.push_line("There is no chain yet"),
+    };
+    content.push_line(format!("The longest chain had {} words", chain.best));
+
+    let mut players: Vec<_> = chain.scores.into_iter().collect();
+    players.sort_by_key(|&(_, words)| std::cmp::Reverse(words));
+    for (i, (user, words)) in players.into_iter().take(SCORES_LIMIT).enumerate() {
+        content
+            .push(format!("{}. ", i + 1))
+            .mention(&user)
+            .push_line(format!(" {} words", words));
+    }
+    content.build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALICE: UserId = UserId::new(1);
+    const BOB: UserId = UserId::new(2);
+
+    /// An active chain of `words`, played in turns starting with Alice
+    fn chain(words: &[&str]) -> Chain {
+        let mut chain = Chain {
+            active: true,
+            ..Default::default()
+        };
+        for (i, word) in words.iter().enumerate() {
+            let user = if i % 2 == 0 { ALICE } else { BOB };
+            chain.play(user, word).unwrap();
+        }
+        chain
+    }
+
+    #[test]
+    fn words_start_with_the_last_letter() {
+        let chain = chain(&["apple"]);
+        assert_eq!(chain.next_letter(), Some('e'));
+        assert_eq!(
+            chain.check(BOB, "tiger").unwrap(),
+            "**tiger** does not start with **e**"
+        );
+        assert_eq!(chain.check(BOB, "eagle"), None);
+    }
+
+    #[test]
+    fn words_come_from_the_dictionary() {
+        assert_eq!(
+            chain(&[]).check(ALICE, "qzxv").unwrap(),
+            "**qzxv** is not in my dictionary"
+        );
+        assert_eq!(chain(&[]).check(ALICE, "table"), None);
+    }
+
+    #[test]
+    fn words_are_not_repeated() {
+        let chain = chain(&["eagle", "elephant", "tiger", "rabbit", "table"]);
+        assert_eq!(
+            chain.check(BOB, "elephant").unwrap(),
+            "**elephant** was already played in this chain"
+        );
+        assert_eq!(chain.check(BOB, "egg"), None);
+    }
+
+    #[test]
+    fn players_take_turns() {
+        let chain = chain(&["eagle"]);
+        assert_eq!(
+            chain.check(ALICE, "egg").unwrap(),
+            "You cannot play two words in a row"
+        );
+        assert_eq!(chain.check(BOB, "egg"), None);
+    }
+
+    #[test]
+    fn mistakes_break_the_chain_but_keep_the_best() {
+        let mut chain = chain(&["apple", "eagle", "egg"]);
+        assert_eq!(chain.best, 3);
+        assert_eq!(chain.scores[&ALICE], 2);
+        assert_eq!(chain.scores[&BOB], 1);
+
+        let (_, length) = chain.play(BOB, "tiger").unwrap_err();
+        assert_eq!(length, 3);
+        assert!(chain.words.is_empty());
+        assert_eq!(chain.last_player, None);
+        assert_eq!(chain.best, 3);
+
+        // Any word starts a new chain, and the best only grows past the old one
+        for (user, word) in [(ALICE, "tiger"), (BOB, "rabbit"), (ALICE, "table")] {
+            chain.play(user, word).unwrap();
+            assert_eq!(chain.best, 3);
+        }
+        chain.play(BOB, "elephant").unwrap();
+        assert_eq!(chain.best, 4);
+        assert_eq!(chain.scores[&ALICE], 4);
+    }
+}
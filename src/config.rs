@@ -0,0 +1,292 @@
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+
+use anyhow::{bail, Context as AnyhowContext, Result};
+use serde::Deserialize;
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+use tracing::{error, info};
+
+use crate::commands;
+use crate::shards::Sharding;
+
+/// How often the config file is checked for changes
+const WATCH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The contents of the config file, `config.toml` unless `CONFIG_FILE` says otherwise.
+///
+/// ```toml
+/// owners = [123456789012345678]
+///
+/// [guilds.123456789012345678]
+/// commands = ["hello", "TicTacToe"]
+/// turn_time_limit = 600
+/// board_images = true
+/// trivia_questions = "trivia.toml"
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Users that may use the owner only commands
+    pub owners: Vec<UserId>,
+    pub guilds: HashMap<GuildId, GuildConfig>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GuildConfig {
+    /// The `NAME`s of the commands enabled in the guild, all commands when left out
+    pub commands: Option<Vec<String>>,
+    /// Seconds a player has for a move in a game, no limit when left out or 0
+    pub turn_time_limit: Option<u64>,
+    /// Attach game boards as images, only in builds with the `images` feature
+    pub board_images: bool,
+    /// A TOML or JSON file with the questions of `/trivia`, the bundled questions when left out
+    pub trivia_questions: Option<PathBuf>,
+}
+
+impl Config {
+    fn parse(toml: &str) -> Result<Self> {
+        let config: Config = toml::from_str(toml)?;
+        let known: HashSet<_> = commands::command_names().into_iter().collect();
+        for (guild_id, guild) in &config.guilds {
+            for name in guild.commands.iter().flatten() {
+                if !known.contains(name) {
+                    bail!("Unknown command '{}' enabled in guild {}", name, guild_id);
+                }
+            }
+        }
+        Ok(config)
+    }
+
+    pub fn is_owner(&self, user_id: UserId) -> bool {
+        self.owners.contains(&user_id)
+    }
+
+    pub fn command_enabled(&self, guild_id: Option<GuildId>, name: &str) -> bool {
+        guild_id
+            .and_then(|guild_id| self.guilds.get(&guild_id))
+            .and_then(|guild| guild.commands.as_ref())
+            .is_none_or(|commands| commands.iter().any(|command| command == name))
+    }
+
+    /// `None` when moves may take as long as the player likes
+    pub fn turn_time_limit(&self, guild_id: Option<GuildId>) -> Option<Duration> {
+        guild_id
+            .and_then(|guild_id| self.guilds.get(&guild_id))
+            .and_then(|guild| guild.turn_time_limit)
+            .filter(|&seconds| seconds > 0)
+            .map(Duration::from_secs)
+    }
+
+    pub fn board_images(&self, guild_id: Option<GuildId>) -> bool {
+        guild_id
+            .and_then(|guild_id| self.guilds.get(&guild_id))
+            .is_some_and(|guild| guild.board_images)
+    }
+
+    pub fn trivia_questions(&self, guild_id: Option<GuildId>) -> Option<&Path> {
+        guild_id
+            .and_then(|guild_id| self.guilds.get(&guild_id))
+            .and_then(|guild| guild.trivia_questions.as_deref())
+    }
+
+    fn guild_commands(&self, guild_id: GuildId) -> Option<&Vec<String>> {
+        self.guilds
+            .get(&guild_id)
+            .and_then(|guild| guild.commands.as_ref())
+    }
+}
+
+/// The live [`Config`] together with where it came from
+pub struct Settings {
+    path: PathBuf,
+    current: RwLock<Arc<Config>>,
+    modified: Mutex<Option<SystemTime>>,
+    /// Guilds commands are registered in, set with `DISCORD_GUILD_ID`. Globally when `None`
+    pub dev_guild_ids: Option<Vec<GuildId>>,
+}
+
+impl TypeMapKey for Settings {
+    type Value = Arc<Settings>;
+}
+
+impl Settings {
+    pub fn load(dev_guild_ids: Option<Vec<GuildId>>) -> Result<Self> {
+        let path = PathBuf::from(env::var("CONFIG_FILE").unwrap_or("config.toml".to_string()));
+        let config = read(&path)?;
+        Ok(Self {
+            modified: Mutex::new(modified(&path)),
+            current: RwLock::new(Arc::new(config)),
+            path,
+            dev_guild_ids,
+        })
+    }
+
+    pub async fn get(ctx: &Context) -> Arc<Self> {
+        ctx.data
+            .read()
+            .await
+            .get::<Self>()
+            .cloned()
+            .expect("Settings to be inserted into the client data")
+    }
+
+    pub fn current(&self) -> Arc<Config> {
+        self.current.read().expect("Config lock poisoned").clone()
+    }
+
+    /// Read the config file again and apply it when it is valid and the commands of the
+    /// guilds whose command list changed could be registered again.
+    ///
+    /// Returns the guilds whose commands were registered again.
+    pub async fn reload(&self, ctx: &Context) -> Result<Vec<GuildId>> {
+        // Remember the new file even when it is invalid, so it is not read again until it changes
+        *self.modified.lock().await = modified(&self.path);
+        let config = Arc::new(read(&self.path)?);
+        let registered = self.register(ctx, &config).await?;
+        *self.current.write().expect("Config lock poisoned") = config;
+        info!("Reloaded config from {}", self.path.display());
+        Ok(registered)
+    }
+
+    /// Register the commands of the guilds in `DISCORD_GUILD_ID` whose command list differs
+    /// between the current config and `config`
+    async fn register(&self, ctx: &Context, config: &Config) -> Result<Vec<GuildId>> {
+        let Some(dev_guild_ids) = &self.dev_guild_ids else {
+            info!("Commands are registered globally, guild command lists only apply to new interactions");
+            return Ok(Vec::new());
+        };
+        let old = self.current();
+        let sharding = ctx
+            .data
+            .read()
+            .await
+            .get::<Sharding>()
+            .cloned()
+            .expect("Sharding to be inserted into the client data");
+        let mut registered = Vec::new();
+        for &guild_id in dev_guild_ids {
+            if sharding.owns(Some(guild_id))
+                && old.guild_commands(guild_id) != config.guild_commands(guild_id)
+            {
+                commands::register_guild(ctx, guild_id, config).await?;
+                registered.push(guild_id);
+            }
+        }
+        Ok(registered)
+    }
+
+    /// Whether the file changed since it was last read
+    async fn changed(&self) -> bool {
+        *self.modified.lock().await != modified(&self.path)
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// A missing file is the default config, so the bot runs without one
+fn read(path: &Path) -> Result<Config> {
+    if !path.exists() {
+        return Ok(Config::default());
+    }
+    let toml = fs::read_to_string(path)
+        .with_context(|| format!("Could not read config {}", path.display()))?;
+    Config::parse(&toml).with_context(|| format!("Invalid config {}", path.display()))
+}
+
+/// Reload the config when the file changes or the process receives SIGHUP
+pub fn spawn_watcher(ctx: Context) {
+    tokio::spawn(async move {
+        let settings = Settings::get(&ctx).await;
+        let mut interval = tokio::time::interval(WATCH_INTERVAL);
+        #[cfg(unix)]
+        let mut hangup =
+            tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()).ok();
+        loop {
+            #[cfg(unix)]
+            let signalled = tokio::select! {
+                _ = interval.tick() => false,
+                Some(_) = async { hangup.as_mut()?.recv().await } => true,
+            };
+            #[cfg(not(unix))]
+            let signalled = {
+                interval.tick().await;
+                false
+            };
+            if signalled || settings.changed().await {
+                if let Err(err) = settings.reload(&ctx).await {
+                    error!("Keeping the current config: {:?}", err);
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GUILD: GuildId = GuildId::new(123456789012345678);
+
+    #[test]
+    fn empty_file_is_the_default() {
+        let config = Config::parse("").unwrap();
+        assert_eq!(config, Config::default());
+        assert!(config.command_enabled(Some(GUILD), "hello"));
+        assert_eq!(config.turn_time_limit(Some(GUILD)), None);
+        assert!(!config.board_images(Some(GUILD)));
+    }
+
+    #[test]
+    fn guild_settings_are_parsed() {
+        let config = Config::parse(
+            "owners = [1]\n\
+             [guilds.123456789012345678]\n\
+             commands = [\"hello\"]\n\
+             turn_time_limit = 600\n",
+        )
+        .unwrap();
+        assert!(config.is_owner(UserId::new(1)));
+        assert!(config.command_enabled(Some(GUILD), "hello"));
+        assert!(!config.command_enabled(Some(GUILD), "TicTacToe"));
+        // Other guilds and direct messages keep every command
+        assert!(config.command_enabled(Some(GuildId::new(1)), "TicTacToe"));
+        assert!(config.command_enabled(None, "TicTacToe"));
+        assert_eq!(
+            config.turn_time_limit(Some(GUILD)),
+            Some(Duration::from_secs(600))
+        );
+        assert_eq!(config.turn_time_limit(None), None);
+    }
+
+    #[test]
+    fn a_limit_of_0_is_no_limit() {
+        let config = Config::parse("[guilds.123456789012345678]\nturn_time_limit = 0\n").unwrap();
+        assert_eq!(config.turn_time_limit(Some(GUILD)), None);
+    }
+
+    #[test]
+    fn invalid_files_are_rejected() {
+        // Not TOML, unknown keys, wrong types and unknown commands
+        assert!(Config::parse("owners = [").is_err());
+        assert!(Config::parse("admins = [1]").is_err());
+        assert!(Config::parse("[guilds.123456789012345678]\ncolor = \"red\"\n").is_err());
+        assert!(Config::parse("owners = \"me\"").is_err());
+        assert!(Config::parse("[guilds.123456789012345678]\nturn_time_limit = -5\n").is_err());
+        assert!(Config::parse("[guilds.not_a_guild]\n").is_err());
+        let unknown = Config::parse("[guilds.123456789012345678]\ncommands = [\"nope\"]\n");
+        assert_eq!(
+            unknown.unwrap_err().to_string(),
+            "Unknown command 'nope' enabled in guild 123456789012345678"
+        );
+    }
+}
@@ -0,0 +1,51 @@
+//! Rules of the games, without anything Discord specific so they can be tested on their own
+//! and shared between the game commands. The turn based games for two players implement
+//! [`Game`].
+
+pub mod connect_four;
+pub mod dice;
+pub mod minesweeper;
+pub mod tictactoe;
+pub mod ultimate;
+
+use anyhow::Result;
+
+/// The two sides of a game, `First` makes the first move
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Player {
+    First,
+    Second,
+}
+
+impl Player {
+    pub fn other(self) -> Self {
+        match self {
+            Player::First => Player::Second,
+            Player::Second => Player::First,
+        }
+    }
+}
+
+/// How a finished game ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Win(Player),
+    Draw,
+}
+
+/// The position of a game, changed one move at a time
+pub trait Game {
+    type Move: Copy;
+
+    /// The player whose turn it is, also after the game has ended
+    fn to_move(&self) -> Player;
+
+    /// Every move the player to move may make, none once the game has ended
+    fn legal_moves(&self) -> Vec<Self::Move>;
+
+    /// Make a move for the player to move, fails for moves that are not legal
+    fn apply(&mut self, action: Self::Move) -> Result<()>;
+
+    /// `None` while the game is being played
+    fn outcome(&self) -> Option<Outcome>;
+}
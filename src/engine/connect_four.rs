@@ -0,0 +1,218 @@
+use anyhow::{bail, Result};
+
+use super::{Game, Outcome, Player};
+
+pub const ROWS: usize = 6;
+pub const COLUMNS: usize = 7;
+const WIN_LENGTH: usize = 4;
+
+/// A board of [`ROWS`] by [`COLUMNS`] where discs drop to the lowest empty cell of a column
+/// and `4` discs in a row, column or diagonal win.
+///
+/// Rows are numbered from the top, a move is the number of a column.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Board {
+    cells: [[Option<Player>; COLUMNS]; ROWS],
+}
+
+impl Board {
+    /// A board with the given discs, for positions that were stored. Discs are not checked
+    /// for floating or for whose turn it is
+    pub fn with_cells(cells: [[Option<Player>; COLUMNS]; ROWS]) -> Self {
+        Self { cells }
+    }
+
+    pub fn get(&self, row: usize, column: usize) -> Option<Player> {
+        self.cells[row][column]
+    }
+
+    pub fn column_full(&self, column: usize) -> bool {
+        self.cells[0][column].is_some()
+    }
+
+    /// `(row, column)` of the discs of the first line filled by a single player
+    pub fn winning_line(&self) -> Option<Vec<(usize, usize)>> {
+        for row in 0..ROWS {
+            for column in 0..COLUMNS {
+                let Some(disc) = self.cells[row][column] else {
+                    continue;
+                };
+                for (row_step, column_step) in [(0, 1), (1, 0), (1, 1), (1, -1)] {
+                    let line: Option<Vec<_>> = (0..WIN_LENGTH as isize)
+                        .map(|i| {
+                            let row = row.checked_add_signed(row_step * i)?;
+                            let column = column.checked_add_signed(column_step * i)?;
+                            (row < ROWS
+                                && column < COLUMNS
+                                && self.cells[row][column] == Some(disc))
+                            .then_some((row, column))
+                        })
+                        .collect();
+                    if line.is_some() {
+                        return line;
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+impl Game for Board {
+    type Move = usize;
+
+    fn to_move(&self) -> Player {
+        if self
+            .cells
+            .iter()
+            .flatten()
+            .flatten()
+            .count()
+            .is_multiple_of(2)
+        {
+            Player::First
+        } else {
+            Player::Second
+        }
+    }
+
+    fn legal_moves(&self) -> Vec<usize> {
+        if self.outcome().is_some() {
+            return Vec::new();
+        }
+        (0..COLUMNS)
+            .filter(|&column| !self.column_full(column))
+            .collect()
+    }
+
+    fn apply(&mut self, column: usize) -> Result<()> {
+        if self.outcome().is_some() {
+            bail!("The game is over");
+        }
+        if column >= COLUMNS {
+            bail!("There is no column {}", column);
+        }
+        let Some(row) = (0..ROWS)
+            .rev()
+            .find(|&row| self.cells[row][column].is_none())
+        else {
+            bail!("Column {} is full", column);
+        };
+        self.cells[row][column] = Some(self.to_move());
+        Ok(())
+    }
+
+    /// A line wins even when it fills the last empty cell
+    fn outcome(&self) -> Option<Outcome> {
+        if let Some(line) = self.winning_line() {
+            let (row, column) = line[0];
+            return self.cells[row][column].map(Outcome::Win);
+        }
+        if (0..COLUMNS).all(|column| self.column_full(column)) {
+            return Some(Outcome::Draw);
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn play(columns: &[usize]) -> Board {
+        let mut board = Board::default();
+        for &column in columns {
+            board.apply(column).unwrap();
+        }
+        board
+    }
+
+    #[test]
+    fn discs_drop_to_the_bottom() {
+        let board = play(&[3, 3, 4]);
+        assert_eq!(board.get(5, 3), Some(Player::First));
+        assert_eq!(board.get(4, 3), Some(Player::Second));
+        assert_eq!(board.get(5, 4), Some(Player::First));
+        assert_eq!(board.get(3, 3), None);
+        assert_eq!(board.to_move(), Player::Second);
+    }
+
+    #[test]
+    fn horizontal_line_wins() {
+        let mut board = play(&[0, 0, 1, 1, 2, 2]);
+        assert_eq!(board.outcome(), None);
+        board.apply(3).unwrap();
+        assert_eq!(board.outcome(), Some(Outcome::Win(Player::First)));
+        assert_eq!(
+            board.winning_line().unwrap(),
+            [(5, 0), (5, 1), (5, 2), (5, 3)]
+        );
+        assert!(board.legal_moves().is_empty());
+        assert!(board.apply(4).is_err());
+    }
+
+    #[test]
+    fn vertical_line_wins() {
+        let board = play(&[0, 1, 2, 1, 0, 1, 0, 1]);
+        assert_eq!(board.outcome(), Some(Outcome::Win(Player::Second)));
+        assert_eq!(
+            board.winning_line().unwrap(),
+            [(2, 1), (3, 1), (4, 1), (5, 1)]
+        );
+    }
+
+    #[test]
+    fn rising_diagonal_wins() {
+        // First climbs from the bottom left, the other discs fill the columns below
+        let board = play(&[0, 1, 1, 2, 2, 3, 2, 3, 3, 6, 3]);
+        assert_eq!(board.outcome(), Some(Outcome::Win(Player::First)));
+        assert_eq!(
+            board.winning_line().unwrap(),
+            [(2, 3), (3, 2), (4, 1), (5, 0)]
+        );
+    }
+
+    #[test]
+    fn falling_diagonal_wins() {
+        let board = play(&[6, 5, 5, 4, 4, 3, 4, 3, 3, 0, 3]);
+        assert_eq!(board.outcome(), Some(Outcome::Win(Player::First)));
+        assert_eq!(
+            board.winning_line().unwrap(),
+            [(2, 3), (3, 4), (4, 5), (5, 6)]
+        );
+    }
+
+    #[test]
+    fn full_columns_take_no_more_discs() {
+        let mut board = play(&[2, 2, 2, 2, 2, 2]);
+        assert!(board.column_full(2));
+        assert!(!board.legal_moves().contains(&2));
+        assert_eq!(board.legal_moves().len(), COLUMNS - 1);
+        let before = board.clone();
+        assert_eq!(board.apply(2).unwrap_err().to_string(), "Column 2 is full");
+        assert!(board.apply(COLUMNS).is_err());
+        assert_eq!(board, before);
+    }
+
+    #[test]
+    fn full_board_without_a_line_is_a_draw() {
+        // Columns are filled in pairs so that no four discs of a player ever line up
+        let mut columns = Vec::new();
+        for pair in [[0, 1], [2, 3], [4, 5]] {
+            for _ in 0..3 {
+                columns.extend(pair);
+            }
+            for _ in 0..3 {
+                columns.extend([pair[1], pair[0]]);
+            }
+        }
+        columns.extend([6; ROWS]);
+        let mut board = Board::default();
+        for (i, &column) in columns.iter().enumerate() {
+            assert_eq!(board.outcome(), None, "after {} moves", i);
+            board.apply(column).unwrap();
+        }
+        assert_eq!(board.outcome(), Some(Outcome::Draw));
+        assert!(board.legal_moves().is_empty());
+    }
+}
@@ -0,0 +1,547 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Result};
+use rand::Rng;
+
+/// Expressions in a single notation, separated by commas
+const MAX_EXPRESSIONS: usize = 10;
+/// Dice and numbers added up in a single expression
+const MAX_TERMS: usize = 20;
+/// Dice of a single term before explosions
+const MAX_COUNT: u32 = 100;
+const MAX_SIDES: u32 = 1000;
+/// Dice a single expression rolls, explosions stop once it is reached
+const MAX_ROLLED: usize = 500;
+
+/// Which dice of a term count towards its value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Keep {
+    Highest(u32),
+    Lowest(u32),
+}
+
+/// Dice rolled together, like `4d6kh3`
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Dice {
+    count: u32,
+    sides: u32,
+    /// Every die that rolls its highest side adds another die
+    explode: bool,
+    keep: Option<Keep>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Term {
+    Dice(Dice),
+    Number(u32),
+}
+
+/// A term with its sign and how it was written
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Signed {
+    negative: bool,
+    text: String,
+    term: Term,
+}
+
+/// Dice and numbers that are added up, like `2d20kh1+5`.
+///
+/// Dice are written `<count>d<sides>`, the count may be left out for a single die and `d%`
+/// is a d100. They can be followed by:
+/// - `kh<n>` or `k<n>` to keep the highest and `kl<n>` to keep the lowest dice,
+/// - `dl<n>` to drop the lowest and `dh<n>` to drop the highest dice,
+/// - `!` to roll another die for every die that shows its highest side,
+/// - `adv` or `dis` to roll a single die twice and keep the higher or lower roll.
+///
+/// `adv` and `dis` on their own roll a d20 with advantage or disadvantage.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Expression {
+    text: String,
+    terms: Vec<Signed>,
+}
+
+/// Parse expressions separated by commas, like `4d6kh3, 2d20+5`
+pub fn parse(notation: &str) -> Result<Vec<Expression>> {
+    let expressions = notation
+        .split([',', ';'])
+        .map(str::parse)
+        .collect::<Result<Vec<Expression>>>()?;
+    if expressions.len() > MAX_EXPRESSIONS {
+        bail!("Roll at most {} expressions at once", MAX_EXPRESSIONS);
+    }
+    Ok(expressions)
+}
+
+impl FromStr for Expression {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let text: String = s
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect::<String>()
+            .to_lowercase();
+        if text.is_empty() {
+            bail!("There is nothing to roll");
+        }
+        let mut parser = Parser {
+            text: &text,
+            position: 0,
+        };
+        let mut terms = Vec::new();
+        while !parser.done() {
+            let negative = if parser.eat("-") {
+                true
+            } else if parser.eat("+") || terms.is_empty() {
+                false
+            } else {
+                bail!("Expected + or - after {}", &text[..parser.position]);
+            };
+            let start = parser.position;
+            let term = parser.term()?;
+            terms.push(Signed {
+                negative,
+                text: text[start..parser.position].to_string(),
+                term,
+            });
+        }
+        if terms.len() > MAX_TERMS {
+            bail!("Add up at most {} dice and numbers", MAX_TERMS);
+        }
+        Ok(Self { text, terms })
+    }
+}
+
+impl Display for Expression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn done(&self) -> bool {
+        self.position >= self.text.len()
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.text[self.position..]
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        let found = self.rest().starts_with(token);
+        if found {
+            self.position += token.len();
+        }
+        found
+    }
+
+    fn number(&mut self) -> Result<Option<u32>> {
+        let digits = self.rest().chars().take_while(char::is_ascii_digit).count();
+        if digits == 0 {
+            return Ok(None);
+        }
+        let number = &self.rest()[..digits];
+        self.position += digits;
+        number
+            .parse()
+            .map(Some)
+            .map_err(|_| anyhow!("{} is too large", number))
+    }
+
+    fn term(&mut self) -> Result<Term> {
+        if self.eat("adv") {
+            return Ok(Term::Dice(Dice::advantage(Keep::Highest(1))));
+        }
+        if self.eat("dis") {
+            return Ok(Term::Dice(Dice::advantage(Keep::Lowest(1))));
+        }
+        let count = self.number()?;
+        if !self.eat("d") {
+            return match count {
+                Some(number) => Ok(Term::Number(number)),
+                None => bail!("Expected dice or a number at '{}'", self.rest()),
+            };
+        }
+        let sides = if self.eat("%") {
+            100
+        } else {
+            self.number()?
+                .ok_or(anyhow!("Expected the number of sides after 'd'"))?
+        };
+        let mut dice = Dice {
+            count: count.unwrap_or(1),
+            sides,
+            explode: false,
+            keep: None,
+        };
+        self.modifiers(&mut dice)?;
+        dice.check()?;
+        Ok(Term::Dice(dice))
+    }
+
+    fn modifiers(&mut self, dice: &mut Dice) -> Result<()> {
+        loop {
+            if self.eat("!") {
+                dice.explode = true;
+                continue;
+            }
+            let keep = if self.eat("adv") || self.eat("dis") {
+                if dice.count != 1 {
+                    bail!("Advantage and disadvantage roll a single die, like d20adv");
+                }
+                let highest = self.text[..self.position].ends_with("adv");
+                dice.count = 2;
+                Some(if highest {
+                    Keep::Highest(1)
+                } else {
+                    Keep::Lowest(1)
+                })
+            } else if self.eat("kl") {
+                Some(Keep::Lowest(self.number()?.unwrap_or(1)))
+            } else if self.eat("kh") || self.eat("k") {
+                Some(Keep::Highest(self.number()?.unwrap_or(1)))
+            } else if self.eat("dh") {
+                let drop = self.number()?.unwrap_or(1);
+                Some(Keep::Lowest(dice.count.saturating_sub(drop)))
+            } else if self.eat("dl") {
+                let drop = self.number()?.unwrap_or(1);
+                Some(Keep::Highest(dice.count.saturating_sub(drop)))
+            } else {
+                return Ok(());
+            };
+            if dice.keep.is_some() {
+                bail!("Dice can only be kept or dropped once");
+            }
+            dice.keep = keep;
+        }
+    }
+}
+
+impl Dice {
+    fn advantage(keep: Keep) -> Self {
+        Self {
+            count: 2,
+            sides: 20,
+            explode: false,
+            keep: Some(keep),
+        }
+    }
+
+    fn check(&self) -> Result<()> {
+        if self.count == 0 || self.count > MAX_COUNT {
+            bail!("Roll 1 to {} dice at once", MAX_COUNT);
+        }
+        if self.sides == 0 || self.sides > MAX_SIDES {
+            bail!("Dice have 1 to {} sides", MAX_SIDES);
+        }
+        if self.explode && self.sides == 1 {
+            bail!("A die with a single side would explode forever");
+        }
+        match self.keep {
+            Some(Keep::Highest(keep) | Keep::Lowest(keep)) if keep == 0 || keep > self.count => {
+                bail!("Keep 1 to {} of the {} dice", self.count, self.count)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// A single die that was rolled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Die {
+    pub value: u32,
+    /// Whether the die counts towards the total
+    pub kept: bool,
+    /// Whether the die showed its highest side and added another die
+    pub exploded: bool,
+}
+
+/// A rolled term, numbers have no dice
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TermRoll {
+    pub negative: bool,
+    pub text: String,
+    pub dice: Vec<Die>,
+    /// Without the sign
+    pub value: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Roll {
+    pub expression: String,
+    pub terms: Vec<TermRoll>,
+    pub total: i64,
+}
+
+impl Expression {
+    pub fn roll(&self, rng: &mut impl Rng) -> Roll {
+        let mut rolled = 0;
+        let terms: Vec<TermRoll> = self
+            .terms
+            .iter()
+            .map(|signed| {
+                let (dice, value) = match &signed.term {
+                    Term::Number(number) => (Vec::new(), *number as i64),
+                    Term::Dice(dice) => {
+                        let dice = dice.roll(rng, &mut rolled);
+                        let value = dice
+                            .iter()
+                            .filter(|die| die.kept)
+                            .map(|die| die.value as i64)
+                            .sum();
+                        (dice, value)
+                    }
+                };
+                TermRoll {
+                    negative: signed.negative,
+                    text: signed.text.clone(),
+                    dice,
+                    value,
+                }
+            })
+            .collect();
+        let total = terms
+            .iter()
+            .map(|term| {
+                if term.negative {
+                    -term.value
+                } else {
+                    term.value
+                }
+            })
+            .sum();
+        Roll {
+            expression: self.text.clone(),
+            terms,
+            total,
+        }
+    }
+}
+
+impl Dice {
+    /// Roll the dice in order, explosions right after the die that exploded.
+    ///
+    /// `rolled` counts the dice of the whole expression to limit explosions.
+    fn roll(&self, rng: &mut impl Rng, rolled: &mut usize) -> Vec<Die> {
+        let mut dice = Vec::new();
+        for _ in 0..self.count {
+            loop {
+                let value = rng.random_range(1..=self.sides);
+                *rolled += 1;
+                let exploded = self.explode && value == self.sides && *rolled < MAX_ROLLED;
+                dice.push(Die {
+                    value,
+                    kept: true,
+                    exploded,
+                });
+                if !exploded {
+                    break;
+                }
+            }
+        }
+
+        let Some(keep) = self.keep else {
+            return dice;
+        };
+        // Dice in the order they are kept, ties keep the earlier die
+        let mut order: Vec<usize> = (0..dice.len()).collect();
+        let count = match keep {
+            Keep::Highest(count) => {
+                order.sort_by_key(|&i| std::cmp::Reverse(dice[i].value));
+                count
+            }
+            Keep::Lowest(count) => {
+                order.sort_by_key(|&i| dice[i].value);
+                count
+            }
+        };
+        for &i in order.iter().skip(count as usize) {
+            dice[i].kept = false;
+        }
+        dice
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+
+    fn dice(count: u32, sides: u32, explode: bool, keep: Option<Keep>) -> Term {
+        Term::Dice(Dice {
+            count,
+            sides,
+            explode,
+            keep,
+        })
+    }
+
+    fn terms(notation: &str) -> Vec<(bool, Term)> {
+        notation
+            .parse::<Expression>()
+            .unwrap()
+            .terms
+            .into_iter()
+            .map(|signed| (signed.negative, signed.term))
+            .collect()
+    }
+
+    #[test]
+    fn parses_notation() {
+        assert_eq!(
+            terms("4d6kh3"),
+            vec![(false, dice(4, 6, false, Some(Keep::Highest(3))))]
+        );
+        assert_eq!(
+            terms("2d20 + 5 - d%"),
+            vec![
+                (false, dice(2, 20, false, None)),
+                (false, Term::Number(5)),
+                (true, dice(1, 100, false, None)),
+            ]
+        );
+        assert_eq!(
+            terms("D20ADV+3"),
+            vec![
+                (false, dice(2, 20, false, Some(Keep::Highest(1)))),
+                (false, Term::Number(3)),
+            ]
+        );
+        assert_eq!(
+            terms("dis"),
+            vec![(false, dice(2, 20, false, Some(Keep::Lowest(1))))]
+        );
+        assert_eq!(
+            terms("5d10!dl2"),
+            vec![(false, dice(5, 10, true, Some(Keep::Highest(3))))]
+        );
+        assert_eq!(
+            terms("3d8kl"),
+            vec![(false, dice(3, 8, false, Some(Keep::Lowest(1))))]
+        );
+    }
+
+    #[test]
+    fn parses_several_expressions() {
+        let expressions = parse("4d6kh3, 2d20+5; adv").unwrap();
+        let texts: Vec<_> = expressions.iter().map(|e| e.to_string()).collect();
+        assert_eq!(texts, vec!["4d6kh3", "2d20+5", "adv"]);
+        assert!(parse(&["d6"; 11].join(",")).is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_notation() {
+        for notation in [
+            "",
+            "d",
+            "4d",
+            "2d6+",
+            "abc",
+            "3d6kh4",
+            "3d6kh0",
+            "0d6",
+            "101d6",
+            "d1001",
+            "d1!",
+            "2d20adv",
+            "4d6kh3kl1",
+            "2d6 d",
+            "99999999999d6",
+        ] {
+            assert!(
+                notation.parse::<Expression>().is_err(),
+                "{} should not parse",
+                notation
+            );
+        }
+    }
+
+    #[test]
+    fn adds_up_terms() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let roll = "3d1 + 5 - 2".parse::<Expression>().unwrap().roll(&mut rng);
+        assert_eq!(roll.total, 6);
+        assert_eq!(roll.terms[0].dice.len(), 3);
+        assert!(roll.terms[2].negative);
+        assert_eq!(roll.terms[2].value, 2);
+    }
+
+    #[test]
+    fn keeps_the_highest_dice() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let expression: Expression = "4d6kh3".parse().unwrap();
+        for _ in 0..100 {
+            let roll = expression.roll(&mut rng);
+            let dice = &roll.terms[0].dice;
+            assert_eq!(dice.len(), 4);
+            assert_eq!(dice.iter().filter(|die| die.kept).count(), 3);
+            let dropped = dice.iter().find(|die| !die.kept).unwrap();
+            assert!(dice.iter().all(|die| die.value >= dropped.value));
+            let kept: i64 = dice
+                .iter()
+                .filter(|die| die.kept)
+                .map(|die| die.value as i64)
+                .sum();
+            assert_eq!(roll.total, kept);
+        }
+    }
+
+    #[test]
+    fn advantage_keeps_the_better_roll() {
+        let mut rng = StdRng::seed_from_u64(3);
+        for (notation, better) in [("adv", true), ("d20dis", false)] {
+            let expression: Expression = notation.parse().unwrap();
+            for _ in 0..50 {
+                let roll = expression.roll(&mut rng);
+                let dice = &roll.terms[0].dice;
+                let (high, low) = (
+                    dice.iter().map(|die| die.value).max().unwrap(),
+                    dice.iter().map(|die| die.value).min().unwrap(),
+                );
+                assert_eq!(roll.total, if better { high } else { low } as i64);
+            }
+        }
+    }
+
+    #[test]
+    fn exploding_dice_add_dice() {
+        let mut rng = StdRng::seed_from_u64(4);
+        let roll = "10d2!".parse::<Expression>().unwrap().roll(&mut rng);
+        let dice = &roll.terms[0].dice;
+        let exploded = dice.iter().filter(|die| die.exploded).count();
+        assert!(exploded > 0);
+        assert_eq!(dice.len(), 10 + exploded);
+        assert!(dice
+            .iter()
+            .all(|die| die.exploded == (die.value == 2) && die.kept));
+    }
+
+    #[test]
+    fn explosions_are_limited() {
+        let mut rng = StdRng::seed_from_u64(5);
+        let roll = "100d2!".parse::<Expression>().unwrap().roll(&mut rng);
+        assert!(roll.terms[0].dice.len() <= MAX_ROLLED + 100);
+    }
+
+    #[test]
+    fn the_same_seed_rolls_the_same() {
+        let expressions = parse("4d6kh3, 2d20+5, 6d10!").unwrap();
+        let rolls = |seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            expressions
+                .iter()
+                .map(|expression| expression.roll(&mut rng))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(rolls(42), rolls(42));
+        assert_ne!(rolls(42), rolls(43));
+    }
+}
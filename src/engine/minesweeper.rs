@@ -0,0 +1,299 @@
+use anyhow::{bail, Result};
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+/// What a player sees of a cell
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cell {
+    Hidden,
+    Flagged,
+    /// Opened, with the number of mines around it
+    Open(u8),
+    /// Shown once the game is over
+    Mine,
+    /// The mine that ended the game
+    Exploded,
+    /// A flag on a cell without a mine, shown once the game is over
+    WrongFlag,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Playing,
+    /// Every cell without a mine is open
+    Won,
+    Lost,
+}
+
+/// A minesweeper board for a single player.
+///
+/// Cells are numbered row by row from the top left. Opening a cell without mines around it
+/// opens its neighbours as well, until the open area is surrounded by numbers.
+#[derive(Debug, Clone)]
+pub struct Minefield {
+    width: usize,
+    height: usize,
+    mines: Vec<bool>,
+    open: Vec<bool>,
+    flagged: Vec<bool>,
+    exploded: Option<usize>,
+}
+
+impl Minefield {
+    /// A board with mines on the given cells
+    pub fn new(width: usize, height: usize, mines: &[usize]) -> Result<Self> {
+        let cells = width * height;
+        if cells == 0 {
+            bail!("A minefield needs at least one cell");
+        }
+        let mut field = Self {
+            width,
+            height,
+            mines: vec![false; cells],
+            open: vec![false; cells],
+            flagged: vec![false; cells],
+            exploded: None,
+        };
+        for &mine in mines {
+            if mine >= cells {
+                bail!("Mine {} is outside of the {}×{} board", mine, width, height);
+            }
+            field.mines[mine] = true;
+        }
+        Ok(field)
+    }
+
+    /// A board with `count` mines in random cells, none of them on or next to `safe`
+    pub fn random(
+        width: usize,
+        height: usize,
+        count: usize,
+        safe: Option<usize>,
+        rng: &mut impl Rng,
+    ) -> Result<Self> {
+        let empty = Self::new(width, height, &[])?;
+        let kept: Vec<usize> = safe
+            .map(|safe| {
+                std::iter::once(safe)
+                    .chain(empty.neighbours(safe))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let mut candidates: Vec<usize> = (0..width * height)
+            .filter(|cell| !kept.contains(cell))
+            .collect();
+        if count > candidates.len() {
+            bail!("{} mines do not fit on a {}×{} board", count, width, height);
+        }
+        candidates.shuffle(rng);
+        candidates.truncate(count);
+        Self::new(width, height, &candidates)
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn index(&self, row: usize, column: usize) -> usize {
+        row * self.width + column
+    }
+
+    /// The cells with a mine, in order
+    pub fn mines(&self) -> Vec<usize> {
+        (0..self.mines.len())
+            .filter(|&cell| self.mines[cell])
+            .collect()
+    }
+
+    pub fn mine_count(&self) -> usize {
+        self.mines.iter().filter(|&&mine| mine).count()
+    }
+
+    pub fn flag_count(&self) -> usize {
+        self.flagged.iter().filter(|&&flag| flag).count()
+    }
+
+    fn neighbours(&self, cell: usize) -> impl Iterator<Item = usize> + '_ {
+        let (row, column) = (cell / self.width, cell % self.width);
+        (row.saturating_sub(1)..=(row + 1).min(self.height - 1)).flat_map(move |r| {
+            (column.saturating_sub(1)..=(column + 1).min(self.width - 1))
+                .filter(move |&c| (r, c) != (row, column))
+                .map(move |c| self.index(r, c))
+        })
+    }
+
+    /// Mines next to `cell`
+    pub fn adjacent(&self, cell: usize) -> u8 {
+        self.neighbours(cell)
+            .filter(|&neighbour| self.mines[neighbour])
+            .count() as u8
+    }
+
+    pub fn status(&self) -> Status {
+        if self.exploded.is_some() {
+            Status::Lost
+        } else if (0..self.mines.len()).all(|cell| self.mines[cell] || self.open[cell]) {
+            Status::Won
+        } else {
+            Status::Playing
+        }
+    }
+
+    pub fn cell(&self, cell: usize) -> Cell {
+        let over = self.status() != Status::Playing;
+        if self.exploded == Some(cell) {
+            Cell::Exploded
+        } else if self.open[cell] {
+            Cell::Open(self.adjacent(cell))
+        } else if self.flagged[cell] {
+            if over && !self.mines[cell] {
+                Cell::WrongFlag
+            } else {
+                Cell::Flagged
+            }
+        } else if over && self.mines[cell] {
+            Cell::Mine
+        } else {
+            Cell::Hidden
+        }
+    }
+
+    /// Open a cell, and the area around it when no mines are next to it.
+    ///
+    /// Returns the number of cells that were opened.
+    pub fn reveal(&mut self, cell: usize) -> Result<usize> {
+        self.check(cell)?;
+        if self.open[cell] {
+            bail!("The cell is already open");
+        }
+        if self.flagged[cell] {
+            bail!("Remove the flag before opening the cell");
+        }
+        if self.mines[cell] {
+            self.exploded = Some(cell);
+            return Ok(0);
+        }
+
+        let mut opened = 0;
+        let mut pending = vec![cell];
+        while let Some(cell) = pending.pop() {
+            if self.open[cell] || self.flagged[cell] {
+                continue;
+            }
+            self.open[cell] = true;
+            opened += 1;
+            if self.adjacent(cell) == 0 {
+                pending.extend(self.neighbours(cell));
+            }
+        }
+        Ok(opened)
+    }
+
+    /// Put a flag on a hidden cell or take it away, returns whether the cell is flagged now
+    pub fn toggle_flag(&mut self, cell: usize) -> Result<bool> {
+        self.check(cell)?;
+        if self.open[cell] {
+            bail!("Open cells cannot be flagged");
+        }
+        self.flagged[cell] = !self.flagged[cell];
+        Ok(self.flagged[cell])
+    }
+
+    fn check(&self, cell: usize) -> Result<()> {
+        if cell >= self.mines.len() {
+            bail!("Cell {} is outside of the board", cell);
+        }
+        if self.status() != Status::Playing {
+            bail!("The game is over");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+
+    /// A 4×4 board with a single mine in the bottom right corner
+    fn corner() -> Minefield {
+        Minefield::new(4, 4, &[15]).unwrap()
+    }
+
+    #[test]
+    fn counts_adjacent_mines() {
+        let field = Minefield::new(3, 3, &[0, 2, 8]).unwrap();
+        assert_eq!(field.adjacent(4), 3);
+        assert_eq!(field.adjacent(1), 2);
+        assert_eq!(field.adjacent(6), 0);
+        assert_eq!(field.adjacent(5), 2);
+    }
+
+    #[test]
+    fn flood_fill_opens_the_area_around_empty_cells() {
+        let mut field = corner();
+        assert_eq!(field.reveal(0).unwrap(), 15);
+        assert_eq!(field.cell(0), Cell::Open(0));
+        assert_eq!(field.cell(10), Cell::Open(1));
+        assert_eq!(field.status(), Status::Won);
+    }
+
+    #[test]
+    fn numbers_stop_the_flood_fill() {
+        let mut field = Minefield::new(5, 1, &[2]).unwrap();
+        assert_eq!(field.reveal(0).unwrap(), 2);
+        assert_eq!(field.cell(1), Cell::Open(1));
+        assert_eq!(field.cell(3), Cell::Hidden);
+        assert_eq!(field.status(), Status::Playing);
+    }
+
+    #[test]
+    fn flags_are_not_opened() {
+        let mut field = corner();
+        assert!(field.toggle_flag(5).unwrap());
+        assert!(field.reveal(5).is_err());
+        field.reveal(0).unwrap();
+        assert_eq!(field.cell(5), Cell::Flagged);
+        assert_eq!(field.status(), Status::Playing);
+        assert!(!field.toggle_flag(5).unwrap());
+        assert_eq!(field.reveal(5).unwrap(), 1);
+        assert_eq!(field.status(), Status::Won);
+    }
+
+    #[test]
+    fn a_mine_ends_the_game() {
+        let mut field = Minefield::new(3, 3, &[0, 4]).unwrap();
+        field.toggle_flag(8).unwrap();
+        field.reveal(4).unwrap();
+        assert_eq!(field.status(), Status::Lost);
+        assert_eq!(field.cell(4), Cell::Exploded);
+        assert_eq!(field.cell(0), Cell::Mine);
+        assert_eq!(field.cell(8), Cell::WrongFlag);
+        assert!(field.reveal(1).is_err());
+        assert!(field.toggle_flag(1).is_err());
+    }
+
+    #[test]
+    fn random_boards_keep_the_first_cell_safe() {
+        let mut rng = StdRng::seed_from_u64(7);
+        for _ in 0..50 {
+            let field = Minefield::random(8, 8, 10, Some(9), &mut rng).unwrap();
+            assert_eq!(field.mine_count(), 10);
+            assert_eq!(field.adjacent(9), 0);
+            assert!(!field.mines().contains(&9));
+        }
+        assert!(Minefield::random(3, 3, 1, Some(4), &mut rng).is_err());
+        assert_eq!(
+            Minefield::random(3, 3, 8, Some(0), &mut rng)
+                .unwrap_err()
+                .to_string(),
+            "8 mines do not fit on a 3×3 board"
+        );
+    }
+}
@@ -0,0 +1,260 @@
+use std::sync::Arc;
+
+use anyhow::{bail, Result};
+
+use super::{Game, Outcome, Player};
+
+/// A square board where `win_length` marks in a row, column or diagonal win.
+///
+/// Squares are numbered row by row from the top left, a move is the number of a square.
+#[derive(Debug, Clone)]
+pub struct Board {
+    squares: Vec<Option<Player>>,
+    /// The squares of every line that wins, shared between copies of the board
+    lines: Arc<[Vec<usize>]>,
+}
+
+impl Board {
+    pub fn new(size: u8, win_length: u8) -> Self {
+        let size = size as usize;
+        let length = win_length as isize;
+        let mut lines = Vec::new();
+        for row in 0..size as isize {
+            for col in 0..size as isize {
+                for (row_step, col_step) in [(0, 1), (1, 0), (1, 1), (1, -1)] {
+                    let line: Option<Vec<_>> = (0..length)
+                        .map(|i| {
+                            let row = row + row_step * i;
+                            let col = col + col_step * i;
+                            let inside = (0..size as isize).contains(&row)
+                                && (0..size as isize).contains(&col);
+                            inside.then(|| row as usize * size + col as usize)
+                        })
+                        .collect();
+                    lines.extend(line);
+                }
+            }
+        }
+        Self {
+            squares: vec![None; size * size],
+            lines: lines.into(),
+        }
+    }
+
+    pub fn get(&self, square: usize) -> Option<Player> {
+        self.squares.get(square).copied().flatten()
+    }
+
+    /// Put a mark without checking whose turn it is, for positions that were stored or
+    /// to take a move back while searching
+    pub fn set(&mut self, square: usize, mark: Option<Player>) {
+        self.squares[square] = mark;
+    }
+
+    pub fn empty_squares(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.squares.len()).filter(|&square| self.squares[square].is_none())
+    }
+
+    /// The first line filled by a single player
+    pub fn winning_line(&self) -> Option<&[usize]> {
+        self.lines
+            .iter()
+            .find(|line| {
+                let first = self.squares[line[0]];
+                first.is_some() && line.iter().all(|&square| self.squares[square] == first)
+            })
+            .map(Vec::as_slice)
+    }
+}
+
+impl Game for Board {
+    type Move = usize;
+
+    fn to_move(&self) -> Player {
+        if self.squares.iter().flatten().count().is_multiple_of(2) {
+            Player::First
+        } else {
+            Player::Second
+        }
+    }
+
+    fn legal_moves(&self) -> Vec<usize> {
+        if self.outcome().is_some() {
+            return Vec::new();
+        }
+        self.empty_squares().collect()
+    }
+
+    fn apply(&mut self, square: usize) -> Result<()> {
+        if self.outcome().is_some() {
+            bail!("The game is over");
+        }
+        match self.squares.get(square) {
+            None => bail!("There is no square {}", square),
+            Some(Some(_)) => bail!("Square {} is already taken", square),
+            Some(None) => {}
+        }
+        self.squares[square] = Some(self.to_move());
+        Ok(())
+    }
+
+    /// A line wins even when it fills the last empty square
+    fn outcome(&self) -> Option<Outcome> {
+        if let Some(line) = self.winning_line() {
+            return self.squares[line[0]].map(Outcome::Win);
+        }
+        if self.empty_squares().next().is_none() {
+            return Some(Outcome::Draw);
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    /// The eight lines of a 3×3 board, written out instead of generated
+    const LINES: [[usize; 3]; 8] = [
+        [0, 1, 2],
+        [3, 4, 5],
+        [6, 7, 8],
+        [0, 3, 6],
+        [1, 4, 7],
+        [2, 5, 8],
+        [0, 4, 8],
+        [2, 4, 6],
+    ];
+
+    /// Every position reachable from the empty 3×3 board by legal moves
+    fn reachable() -> HashMap<Vec<Option<Player>>, Board> {
+        let mut seen = HashMap::new();
+        let mut stack = vec![Board::new(3, 3)];
+        while let Some(board) = stack.pop() {
+            if seen.contains_key(&board.squares) {
+                continue;
+            }
+            for square in board.legal_moves() {
+                let mut next = board.clone();
+                next.apply(square).unwrap();
+                stack.push(next);
+            }
+            seen.insert(board.squares.clone(), board);
+        }
+        seen
+    }
+
+    fn line_owner(squares: &[Option<Player>]) -> Option<Player> {
+        LINES.iter().find_map(|line| {
+            let first = squares[line[0]]?;
+            line.iter()
+                .all(|&square| squares[square] == Some(first))
+                .then_some(first)
+        })
+    }
+
+    #[test]
+    fn counts_every_reachable_position() {
+        let positions = reachable();
+        assert_eq!(positions.len(), 5478);
+
+        let mut first_wins = 0;
+        let mut second_wins = 0;
+        let mut draws = 0;
+        for board in positions.values() {
+            match board.outcome() {
+                Some(Outcome::Win(Player::First)) => first_wins += 1,
+                Some(Outcome::Win(Player::Second)) => second_wins += 1,
+                Some(Outcome::Draw) => draws += 1,
+                None => {}
+            }
+        }
+        assert_eq!((first_wins, second_wins, draws), (626, 316, 16));
+    }
+
+    #[test]
+    fn outcome_matches_the_lines_in_every_position() {
+        for board in reachable().values() {
+            let owner = line_owner(&board.squares);
+            let full = board.squares.iter().all(Option::is_some);
+            let expected = match owner {
+                Some(player) => Some(Outcome::Win(player)),
+                None if full => Some(Outcome::Draw),
+                None => None,
+            };
+            assert_eq!(board.outcome(), expected, "{:?}", board.squares);
+        }
+    }
+
+    #[test]
+    fn full_board_with_a_line_is_a_win() {
+        let full_wins = reachable()
+            .values()
+            .filter(|board| board.squares.iter().all(Option::is_some))
+            .filter(|board| matches!(board.outcome(), Some(Outcome::Win(_))))
+            .count();
+        assert!(full_wins > 0);
+
+        // X O O / O X X / O X X, X fills the board and the diagonal with the last move
+        let mut board = Board::new(3, 3);
+        for square in [0, 1, 5, 2, 7, 3, 8, 6] {
+            board.apply(square).unwrap();
+        }
+        assert_eq!(board.outcome(), None);
+        board.apply(4).unwrap();
+        assert_eq!(board.outcome(), Some(Outcome::Win(Player::First)));
+    }
+
+    #[test]
+    fn only_the_player_that_moved_last_can_have_won() {
+        for board in reachable().values() {
+            if let Some(Outcome::Win(player)) = board.outcome() {
+                assert_eq!(player, board.to_move().other(), "{:?}", board.squares);
+            }
+        }
+    }
+
+    #[test]
+    fn legal_moves_are_the_empty_squares_of_unfinished_games() {
+        for board in reachable().values() {
+            let empty: Vec<_> = board.empty_squares().collect();
+            match board.outcome() {
+                Some(_) => assert!(board.legal_moves().is_empty()),
+                None => assert_eq!(board.legal_moves(), empty),
+            }
+            let marks = board.squares.iter().flatten().count();
+            let expected = if marks.is_multiple_of(2) {
+                Player::First
+            } else {
+                Player::Second
+            };
+            assert_eq!(board.to_move(), expected);
+        }
+    }
+
+    #[test]
+    fn rejects_illegal_moves_in_every_position() {
+        for board in reachable().values() {
+            let finished = board.outcome().is_some();
+            for square in 0..10 {
+                let legal = !finished && square < 9 && board.squares[square].is_none();
+                let mut next = board.clone();
+                assert_eq!(next.apply(square).is_ok(), legal, "{:?}", board.squares);
+                if !legal {
+                    assert_eq!(next.squares, board.squares);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn lines_on_larger_boards() {
+        // Rows, columns and both diagonals
+        assert_eq!(Board::new(3, 3).lines.len(), 8);
+        assert_eq!(Board::new(4, 4).lines.len(), 10);
+        // Two per row and column, eight diagonals
+        assert_eq!(Board::new(4, 3).lines.len(), 24);
+    }
+}
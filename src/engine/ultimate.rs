@@ -0,0 +1,215 @@
+use anyhow::{bail, Result};
+
+use super::tictactoe::Board;
+use super::{Game, Outcome, Player};
+
+/// Nine 3×3 boards making up one large board. Winning a small board claims its square of the
+/// large board, and every move sends the opponent to the small board in the same position as
+/// the square that was taken. A board that has been won or filled sends them anywhere.
+///
+/// Boards and squares are numbered row by row from the top left, a move is a board and a
+/// square on it.
+#[derive(Debug, Clone)]
+pub struct Ultimate {
+    boards: Vec<Board>,
+    /// The board the player to move has to play on, any open board when `None`
+    active: Option<usize>,
+    to_move: Player,
+}
+
+impl Default for Ultimate {
+    fn default() -> Self {
+        Self {
+            boards: vec![Board::new(3, 3); 9],
+            active: None,
+            to_move: Player::First,
+        }
+    }
+}
+
+impl Ultimate {
+    pub fn board(&self, board: usize) -> &Board {
+        &self.boards[board]
+    }
+
+    /// Boards that can be played on by the player to move
+    pub fn playable(&self) -> Vec<usize> {
+        if self.outcome().is_some() {
+            return Vec::new();
+        }
+        match self.active {
+            Some(board) => vec![board],
+            None => (0..self.boards.len())
+                .filter(|&board| self.boards[board].outcome().is_none())
+                .collect(),
+        }
+    }
+
+    /// The large board, with the winner of every small board in its square
+    pub fn meta(&self) -> Board {
+        let mut meta = Board::new(3, 3);
+        for (square, board) in self.boards.iter().enumerate() {
+            if let Some(Outcome::Win(player)) = board.outcome() {
+                meta.set(square, Some(player));
+            }
+        }
+        meta
+    }
+}
+
+impl Game for Ultimate {
+    type Move = (usize, usize);
+
+    fn to_move(&self) -> Player {
+        self.to_move
+    }
+
+    fn legal_moves(&self) -> Vec<(usize, usize)> {
+        self.playable()
+            .into_iter()
+            .flat_map(|board| {
+                self.boards[board]
+                    .empty_squares()
+                    .map(move |square| (board, square))
+            })
+            .collect()
+    }
+
+    fn apply(&mut self, (board, square): (usize, usize)) -> Result<()> {
+        if !self.legal_moves().contains(&(board, square)) {
+            bail!("Square {} of board {} cannot be taken", square, board);
+        }
+        self.boards[board].set(square, Some(self.to_move));
+        self.active = self.boards[square].outcome().is_none().then_some(square);
+        self.to_move = self.to_move.other();
+        Ok(())
+    }
+
+    /// A line on the large board wins, the game is a draw once every small board is decided
+    /// without one
+    fn outcome(&self) -> Option<Outcome> {
+        match self.meta().outcome() {
+            Some(Outcome::Win(player)) => Some(Outcome::Win(player)),
+            _ if self.boards.iter().all(|board| board.outcome().is_some()) => Some(Outcome::Draw),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::seq::IndexedRandom;
+
+    use super::*;
+
+    fn play(moves: &[(usize, usize)]) -> Ultimate {
+        let mut game = Ultimate::default();
+        for &action in moves {
+            game.apply(action).unwrap();
+        }
+        game
+    }
+
+    #[test]
+    fn sends_the_opponent_to_the_matching_board() {
+        let mut game = Ultimate::default();
+        assert_eq!(game.legal_moves().len(), 81);
+        game.apply((4, 2)).unwrap();
+        assert_eq!(game.playable(), vec![2]);
+        assert!(game.legal_moves().iter().all(|&(board, _)| board == 2));
+        assert!(game.apply((4, 0)).is_err());
+        assert_eq!(game.to_move(), Player::Second);
+    }
+
+    #[test]
+    fn a_decided_board_sends_the_opponent_anywhere() {
+        // First takes the top row of board 0 while Second keeps sending them back
+        let game = play(&[(0, 1), (1, 0), (0, 2), (2, 0), (0, 0)]);
+        assert_eq!(game.board(0).outcome(), Some(Outcome::Win(Player::First)));
+        assert_eq!(game.meta().get(0), Some(Player::First));
+        // The last move points at board 0, which is won
+        assert_eq!(game.playable(), (1..9).collect::<Vec<_>>());
+        let mut next = game.clone();
+        assert!(next.apply((0, 4)).is_err());
+        assert!(next.apply((5, 4)).is_ok());
+    }
+
+    /// A position where `player` took the top row of each of the boards
+    fn claim(game: &mut Ultimate, player: Player, boards: &[usize]) {
+        for &board in boards {
+            for square in 0..3 {
+                game.boards[board].set(square, Some(player));
+            }
+        }
+    }
+
+    #[test]
+    fn a_line_of_boards_wins() {
+        let mut game = Ultimate::default();
+        claim(&mut game, Player::First, &[0, 4]);
+        assert_eq!(game.outcome(), None);
+        claim(&mut game, Player::First, &[8]);
+        assert_eq!(game.outcome(), Some(Outcome::Win(Player::First)));
+        assert!(game.legal_moves().is_empty());
+    }
+
+    #[test]
+    fn decided_boards_without_a_line_draw() {
+        let mut game = Ultimate::default();
+        // X X O / O O X / X X O on the large board
+        claim(&mut game, Player::First, &[0, 1, 5, 6, 7]);
+        claim(&mut game, Player::Second, &[2, 3, 4, 8]);
+        assert_eq!(game.outcome(), Some(Outcome::Draw));
+
+        // A filled board without a line counts as decided but claims nothing
+        let mut game = Ultimate::default();
+        claim(&mut game, Player::First, &[0, 1, 5, 6, 7]);
+        claim(&mut game, Player::Second, &[2, 3, 8]);
+        assert_eq!(game.outcome(), None);
+        // X O X / X O O / O X X
+        for (square, mark) in "XOXXOOOXX".chars().enumerate() {
+            let player = match mark {
+                'X' => Player::First,
+                _ => Player::Second,
+            };
+            game.boards[4].set(square, Some(player));
+        }
+        assert_eq!(game.board(4).outcome(), Some(Outcome::Draw));
+        assert_eq!(game.outcome(), Some(Outcome::Draw));
+    }
+
+    #[test]
+    fn random_games_keep_to_the_rules() {
+        let mut rng = rand::rng();
+        for _ in 0..200 {
+            let mut game = Ultimate::default();
+            let mut moves = 0;
+            while let Some(&action) = game.legal_moves().choose(&mut rng) {
+                let (_, square) = action;
+                let player = game.to_move();
+                game.apply(action).unwrap();
+                moves += 1;
+                assert_eq!(game.to_move(), player.other());
+                // Sent to the matching board unless it is decided
+                match game.board(square).outcome() {
+                    None if game.outcome().is_none() => assert_eq!(game.playable(), vec![square]),
+                    _ => assert!(game.playable().iter().all(|&board| board != square)),
+                }
+            }
+            assert!(moves <= 81);
+            let meta = game.meta();
+            match game.outcome() {
+                Some(Outcome::Win(player)) => {
+                    let line = meta.winning_line().unwrap();
+                    assert!(line.iter().all(|&square| meta.get(square) == Some(player)));
+                    assert_eq!(player, game.to_move().other());
+                }
+                Some(Outcome::Draw) => {
+                    assert!(meta.winning_line().is_none());
+                    assert!((0..9).all(|board| game.board(board).outcome().is_some()));
+                }
+                None => panic!("A game without legal moves has to be over"),
+            }
+        }
+    }
+}
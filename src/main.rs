@@ -1,12 +1,25 @@
 use std::env;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
+use serenity::gateway::ShardStageUpdateEvent;
 use serenity::model::prelude::*;
 use serenity::prelude::*;
 use serenity::{async_trait, model::prelude::GuildId};
-use tracing::{error, info};
+use tracing::info;
 
+mod collectors;
 mod commands;
+mod config;
+mod engine;
+mod messages;
 mod reactions;
+#[cfg(feature = "images")]
+mod render;
+mod scheduler;
+mod shards;
+mod status;
+mod store;
 
 // struct Bot;
 
@@ -26,39 +39,53 @@ mod reactions;
 // }
 
 struct Handler {
-    dev_guild_ids: Option<Vec<u64>>,
+    /// Set once the background tasks have been spawned, `ready` fires again on reconnects
+    started: AtomicBool,
 }
 
 #[async_trait]
 impl EventHandler for Handler {
     async fn ready(&self, ctx: Context, ready: Ready) {
-        info!("Ready event triggered");
-        match &self.dev_guild_ids {
+        // Every shard gets its own ready event
+        let shard = ready.shard.unwrap_or(ShardInfo {
+            id: ShardId(0),
+            total: 1,
+        });
+        info!(
+            shard = shard.id.0,
+            total = shard.total,
+            guilds = ready.guilds.len(),
+            "Ready event triggered"
+        );
+        status::Status::get(&ctx)
+            .await
+            .add_guilds(ready.guilds.iter().map(|guild| guild.id))
+            .await;
+        if !self.started.swap(true, Ordering::SeqCst) {
+            collectors::spawn(ctx.clone());
+            scheduler::spawn(ctx.clone());
+            config::spawn_watcher(ctx.clone());
+        }
+        let settings = config::Settings::get(&ctx).await;
+        match &settings.dev_guild_ids {
             Some(guildids) => {
-                for guild in guildids {
-                    GuildId::new(*guild)
-                        .set_commands(&ctx.http, commands::command_list())
+                // Each guild is registered by the shard that receives its events
+                for guild in guildids
+                    .iter()
+                    .filter(|guild| guild.shard_id(shard.total) == shard.id.0)
+                {
+                    commands::register_guild(&ctx, *guild, &settings.current())
                         .await
                         .expect("Could not set commands");
                 }
 
                 info!("{} is online in test env!", ready.user.name);
             }
+            None if shard.id.0 != 0 => {
+                info!("{} shard {} is online!", ready.user.name, shard.id);
+            }
             None => {
-                let current_commands = ctx
-                    .http
-                    .get_global_commands()
-                    .await
-                    .expect("Could not fetch current commands");
-
-                for current in current_commands {
-                    if let Err(err) = ctx.http.delete_global_command(current.id).await {
-                        error!("{}", anyhow::format_err!(err))
-                    }
-                }
-
-                ctx.http
-                    .create_global_commands(&commands::command_list())
+                commands::register_global(&ctx)
                     .await
                     .expect("Could not set global applications commands");
 
@@ -67,23 +94,44 @@ impl EventHandler for Handler {
         }
     }
 
+    async fn shard_stage_update(&self, _: Context, event: ShardStageUpdateEvent) {
+        info!(
+            shard = event.shard_id.0,
+            "Shard stage changed from {} to {}", event.old, event.new
+        );
+    }
+
     async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
         info!("interaction_create: {:?}", interaction);
         let id = interaction.id();
+        let status = status::Status::get(&ctx).await;
         match commands::handle_interaction(ctx, interaction).await {
-            Err(err) => error!("Error handling interaciton {:?}:\n{:?}", id, err),
+            Err(err) => {
+                status
+                    .record_error(format!("interaction {:?}", id), &err)
+                    .await
+            }
             Ok(_) => info!("Handled interaction {:?}", id),
         };
     }
 
-    // async fn message(&self, ctx: Context, message: Message) {
-    //     for handler in [wordgame::WordGame::message] {
-    //         match handler(&ctx, &message).await {
-    //             Err(err) => eprintln!("{}", err),
-    //             _ => {}
-    //         };
-    //     }
-    // }
+    async fn guild_create(&self, ctx: Context, guild: Guild, _: Option<bool>) {
+        status::Status::get(&ctx).await.add_guilds([guild.id]).await;
+    }
+
+    async fn guild_delete(&self, ctx: Context, incomplete: UnavailableGuild, _: Option<Guild>) {
+        // Unavailable guilds are still joined, they are just having an outage
+        if !incomplete.unavailable {
+            status::Status::get(&ctx)
+                .await
+                .remove_guild(incomplete.id)
+                .await;
+        }
+    }
+
+    async fn message(&self, ctx: Context, message: Message) {
+        messages::handle_message(ctx, message).await;
+    }
 
     async fn reaction_add(&self, ctx: Context, reaction: Reaction) {
         info!("reaction_add create: {:?}", reaction);
@@ -92,7 +140,7 @@ impl EventHandler for Handler {
 }
 
 #[tokio::main]
-async fn main() -> () {
+async fn main() {
     // Get the discord token set in `Secrets.toml`
     let token = env::var("DISCORD_TOKEN").expect("'DISCORD_TOKEN' was not found");
     let dev_guild_ids = env::var("DISCORD_GUILD_ID")
@@ -100,9 +148,13 @@ async fn main() -> () {
             guilds
                 .split_terminator(',')
                 .filter_map(|id| id.parse::<u64>().ok())
+                .map(GuildId::new)
                 .collect::<Vec<_>>()
         })
         .ok();
+    let settings = config::Settings::load(dev_guild_ids).expect("Invalid config");
+
+    let sharding = shards::Sharding::from_env().expect("Invalid shard configuration");
 
     // Set gateway intents, which decides what events the bot will be notified about
     let intents = GatewayIntents::GUILDS
@@ -111,11 +163,37 @@ async fn main() -> () {
         | GatewayIntents::MESSAGE_CONTENT;
 
     let mut client = Client::builder(&token, intents)
-        .event_handler(Handler { dev_guild_ids })
+        .event_handler(Handler {
+            started: AtomicBool::new(false),
+        })
+        .type_map_insert::<config::Settings>(Arc::new(settings))
+        .type_map_insert::<collectors::Collectors>(Arc::new(collectors::Collectors::load(
+            &sharding,
+        )))
+        .type_map_insert::<commands::game::Games>(Arc::new(commands::game::Games::load(&sharding)))
+        .type_map_insert::<scheduler::Scheduler>(Arc::new(scheduler::Scheduler::load(&sharding)))
+        .type_map_insert::<commands::stats::Stats>(Arc::new(commands::stats::Stats::load(
+            &sharding,
+        )))
+        .type_map_insert::<commands::trivia::TriviaScores>(Arc::new(
+            commands::trivia::TriviaScores::load(&sharding),
+        ))
+        .type_map_insert::<commands::wordchain::WordChains>(Arc::new(
+            commands::wordchain::WordChains::load(&sharding),
+        ))
+        .type_map_insert::<shards::Sharding>(sharding.clone())
+        .type_map_insert::<status::Status>(Arc::new(status::Status::new()))
         .await
         .expect("Err creating client");
 
-    if let Err(why) = client.start().await {
+    client
+        .data
+        .write()
+        .await
+        .insert::<shards::ShardManagerKey>(client.shard_manager.clone());
+    shards::spawn_status(client.shard_manager.clone());
+
+    if let Err(why) = sharding.start(&mut client).await {
         println!("Err with client: {:?}", why);
     }
 }
@@ -0,0 +1,24 @@
+use anyhow::Result;
+use serenity::async_trait;
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+use tracing::error;
+
+use crate::commands::{hangman, wordchain};
+
+/// Commands that read the messages sent in a channel, like guesses in a game
+#[async_trait]
+pub trait MessageHandler {
+    async fn message(ctx: &Context, message: &Message) -> Result<()>;
+}
+
+pub async fn handle_message(ctx: Context, message: Message) {
+    if message.author.bot {
+        return;
+    }
+    for handler in [hangman::Hangman::message, wordchain::WordChain::message] {
+        if let Err(err) = handler(&ctx, &message).await {
+            error!("{}", err)
+        };
+    }
+}
@@ -0,0 +1,214 @@
+//! Game boards drawn as PNG images, which look the same in every client and fit boards
+//! that are too large for emoji grids
+
+use std::f32::consts::SQRT_2;
+
+use anyhow::Result;
+
+/// Pixels along each side of a cell
+const CELL: u32 = 96;
+/// Pixels between cells and around the board
+const GAP: u32 = 8;
+/// Samples per pixel along each axis, to smooth the edges of pieces
+const SAMPLES: u32 = 4;
+
+const BACKGROUND: [u8; 3] = [0x31, 0x33, 0x38];
+/// The colors of `ButtonStyle::Secondary` and `ButtonStyle::Success` buttons
+const CELL_COLOR: [u8; 3] = [0x4e, 0x50, 0x58];
+const HIGHLIGHT_COLOR: [u8; 3] = [0x24, 0x80, 0x46];
+/// The red of the ❌ and ⭕ emojis
+const MARK_COLOR: [u8; 3] = [0xdd, 0x2e, 0x44];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Piece {
+    Cross,
+    Ring,
+}
+
+/// A grid of square cells that each hold at most one piece
+pub struct Picture {
+    rows: u32,
+    columns: u32,
+    pieces: Vec<Option<Piece>>,
+    highlighted: Vec<bool>,
+}
+
+impl Picture {
+    pub fn new(rows: u32, columns: u32) -> Self {
+        let cells = (rows * columns) as usize;
+        Self {
+            rows,
+            columns,
+            pieces: vec![None; cells],
+            highlighted: vec![false; cells],
+        }
+    }
+
+    fn index(&self, row: u32, column: u32) -> usize {
+        (row * self.columns + column) as usize
+    }
+
+    /// Rows are counted from the top
+    pub fn set(&mut self, row: u32, column: u32, piece: Option<Piece>) {
+        let index = self.index(row, column);
+        self.pieces[index] = piece;
+    }
+
+    /// Draw the cell in the color of a winning button
+    pub fn highlight(&mut self, row: u32, column: u32) {
+        let index = self.index(row, column);
+        self.highlighted[index] = true;
+    }
+
+    pub fn png(&self) -> Result<Vec<u8>> {
+        let width = self.columns * (CELL + GAP) + GAP;
+        let height = self.rows * (CELL + GAP) + GAP;
+        let pixels: Vec<u8> = (0..height)
+            .flat_map(|y| (0..width).flat_map(move |x| self.pixel(x, y)))
+            .collect();
+
+        let mut png = Vec::new();
+        let mut encoder = png::Encoder::new(&mut png, width, height);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header()?.write_image_data(&pixels)?;
+        Ok(png)
+    }
+
+    fn pixel(&self, x: u32, y: u32) -> [u8; 3] {
+        let (column, x) = (x / (CELL + GAP), x % (CELL + GAP));
+        let (row, y) = (y / (CELL + GAP), y % (CELL + GAP));
+        if x < GAP || y < GAP || column >= self.columns || row >= self.rows {
+            return BACKGROUND;
+        }
+        let index = self.index(row, column);
+        let base = if self.highlighted[index] {
+            HIGHLIGHT_COLOR
+        } else {
+            CELL_COLOR
+        };
+        match self.pieces[index] {
+            Some(piece) => blend(base, MARK_COLOR, coverage(piece, x - GAP, y - GAP)),
+            None => base,
+        }
+    }
+}
+
+/// How much of the pixel at `x`, `y` of a cell the piece covers, from 0 to 1
+fn coverage(piece: Piece, x: u32, y: u32) -> f32 {
+    let center = CELL as f32 / 2.0;
+    let step = 1.0 / SAMPLES as f32;
+    let hits = (0..SAMPLES)
+        .flat_map(|sy| (0..SAMPLES).map(move |sx| (sx, sy)))
+        .filter(|&(sx, sy)| {
+            let x = x as f32 + (sx as f32 + 0.5) * step - center;
+            let y = y as f32 + (sy as f32 + 0.5) * step - center;
+            covers(piece, x, y)
+        })
+        .count();
+    hits as f32 / (SAMPLES * SAMPLES) as f32
+}
+
+/// Whether a point relative to the center of the cell is part of the piece
+fn covers(piece: Piece, x: f32, y: f32) -> bool {
+    let reach = CELL as f32 * 0.3;
+    let half_width = CELL as f32 * 0.07;
+    match piece {
+        Piece::Cross => {
+            x.abs() <= reach
+                && y.abs() <= reach
+                && ((x - y).abs() / SQRT_2 <= half_width || (x + y).abs() / SQRT_2 <= half_width)
+        }
+        Piece::Ring => ((x * x + y * y).sqrt() - reach).abs() <= half_width,
+    }
+}
+
+fn blend(from: [u8; 3], to: [u8; 3], amount: f32) -> [u8; 3] {
+    [0, 1, 2].map(|i| (from[i] as f32 + (to[i] as f32 - from[i] as f32) * amount).round() as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Width, height and RGB pixels of a PNG
+    fn decode(png: &[u8]) -> (u32, u32, Vec<u8>) {
+        let mut reader = png::Decoder::new(png).read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut pixels).unwrap();
+        assert_eq!(info.color_type, png::ColorType::Rgb);
+        pixels.truncate(info.buffer_size());
+        (info.width, info.height, pixels)
+    }
+
+    /// The center pixel of a cell
+    fn center(row: u32, column: u32) -> (u32, u32) {
+        (
+            column * (CELL + GAP) + GAP + CELL / 2,
+            row * (CELL + GAP) + GAP + CELL / 2,
+        )
+    }
+
+    #[test]
+    fn png_has_the_board_size_and_colors() {
+        let mut picture = Picture::new(2, 3);
+        picture.set(1, 0, Some(Piece::Cross));
+        picture.set(1, 2, Some(Piece::Ring));
+        picture.highlight(0, 2);
+        let (width, height, pixels) = decode(&picture.png().unwrap());
+        assert_eq!((width, height), (3 * 104 + 8, 2 * 104 + 8));
+        assert_eq!(pixels.len(), (width * height * 3) as usize);
+
+        let color = |(x, y): (u32, u32)| {
+            let i = ((y * width + x) * 3) as usize;
+            [pixels[i], pixels[i + 1], pixels[i + 2]]
+        };
+        assert_eq!(color((0, 0)), BACKGROUND);
+        assert_eq!(color((width - 1, height - 1)), BACKGROUND);
+        // Between the first two cells
+        assert_eq!(color((CELL + GAP + GAP / 2, GAP + 1)), BACKGROUND);
+        assert_eq!(color(center(0, 0)), CELL_COLOR);
+        assert_eq!(color((GAP, GAP)), CELL_COLOR);
+        assert_eq!(color(center(0, 2)), HIGHLIGHT_COLOR);
+        assert_eq!(color(center(1, 0)), MARK_COLOR);
+        // A ring is hollow
+        assert_eq!(color(center(1, 2)), CELL_COLOR);
+    }
+
+    #[test]
+    fn pieces_cover_their_shape() {
+        let reach = CELL as f32 * 0.3;
+        assert!(covers(Piece::Cross, 0.0, 0.0));
+        assert!(covers(Piece::Cross, 20.0, 20.0));
+        assert!(covers(Piece::Cross, -20.0, 20.0));
+        assert!(!covers(Piece::Cross, 20.0, 0.0));
+        assert!(!covers(Piece::Cross, reach + 1.0, reach + 1.0));
+
+        assert!(!covers(Piece::Ring, 0.0, 0.0));
+        assert!(covers(Piece::Ring, reach, 0.0));
+        assert!(covers(Piece::Ring, 0.0, -reach));
+        assert!(!covers(Piece::Ring, reach * 2.0, 0.0));
+    }
+
+    #[test]
+    fn coverage_smooths_the_edges() {
+        let middle = CELL / 2;
+        assert_eq!(coverage(Piece::Cross, middle, middle), 1.0);
+        assert_eq!(coverage(Piece::Cross, 0, 0), 0.0);
+        assert_eq!(coverage(Piece::Ring, middle, middle), 0.0);
+        // The outer edge of the ring on the horizontal through the center
+        let edge = (0..CELL)
+            .map(|x| coverage(Piece::Ring, x, middle))
+            .find(|&amount| amount > 0.0 && amount < 1.0);
+        assert!(edge.is_some());
+    }
+
+    #[test]
+    fn blend_mixes_colors() {
+        let (black, white) = ([0, 0, 0], [255, 255, 255]);
+        assert_eq!(blend(CELL_COLOR, MARK_COLOR, 0.0), CELL_COLOR);
+        assert_eq!(blend(CELL_COLOR, MARK_COLOR, 1.0), MARK_COLOR);
+        assert_eq!(blend(black, white, 0.5), [128, 128, 128]);
+        assert_eq!(blend(white, black, 0.25), [191, 191, 191]);
+    }
+}
@@ -0,0 +1,496 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+use time::{Date, Month, OffsetDateTime, Time};
+use tracing::{error, info};
+
+use crate::commands;
+use crate::shards::Sharding;
+use crate::status::Status;
+use crate::store::Persisted;
+
+/// How often due jobs are looked for
+const TICK: Duration = Duration::from_secs(5);
+
+/// A job that is due for longer than this was missed, most likely because the bot was down
+const GRACE: i64 = 60;
+
+/// Upper bound for the runs [`Missed::RunAll`] catches up on
+const MAX_CATCH_UP: usize = 100;
+
+/// Work that a [`CustomCommand`](crate::commands::CustomCommand) wants done later.
+///
+/// When due, the job is handed to `run_job` of the command named by `command`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Job {
+    /// Assigned by [`Scheduler::add`]
+    pub id: u64,
+    pub command: String,
+    pub guild_id: Option<GuildId>,
+    /// Free form data for the command, usually JSON
+    pub payload: String,
+    pub schedule: Schedule,
+    pub missed: Missed,
+    /// Unix timestamp in seconds
+    pub next_run: i64,
+}
+
+impl Job {
+    /// A job that runs once at the unix timestamp `at`
+    pub fn once(command: &str, at: i64) -> Self {
+        Self {
+            id: 0,
+            command: command.to_string(),
+            guild_id: None,
+            payload: String::new(),
+            schedule: Schedule::Once,
+            missed: Missed::RunOnce,
+            next_run: at,
+        }
+    }
+
+    pub fn guild(mut self, guild_id: Option<GuildId>) -> Self {
+        self.guild_id = guild_id;
+        self
+    }
+
+    pub fn payload(mut self, payload: impl Into<String>) -> Self {
+        self.payload = payload.into();
+        self
+    }
+
+    pub fn missed(mut self, missed: Missed) -> Self {
+        self.missed = missed;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Schedule {
+    Once,
+    /// Every given number of seconds
+    Every(u64),
+    /// A five field cron expression (`minute hour day-of-month month day-of-week`) in UTC
+    Cron(String),
+}
+
+impl Schedule {
+    fn next_after(&self, after: i64) -> Result<Option<i64>> {
+        Ok(match self {
+            Schedule::Once => None,
+            Schedule::Every(0) => bail!("A job cannot run every 0 seconds"),
+            Schedule::Every(secs) => Some(after + *secs as i64),
+            Schedule::Cron(expression) => Cron::parse(expression)?.next_after(after),
+        })
+    }
+}
+
+/// What to do with runs that were due while the bot was down.
+///
+/// A run counts as missed once it is more than a minute overdue. One-shot jobs run
+/// for both [`Missed::RunOnce`] and [`Missed::RunAll`]. Recurring jobs always continue
+/// with their next occurrence in the future afterwards.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Missed {
+    /// Drop missed runs
+    Skip,
+    /// Run once no matter how many runs were missed
+    #[default]
+    RunOnce,
+    /// Run once for every missed run, up to a limit
+    RunAll,
+}
+
+/// Persisted one-shot and recurring jobs
+pub struct Scheduler {
+    jobs: Mutex<HashMap<u64, Job>>,
+    /// The id of the next job, ids are not reused so a finished job cannot be mistaken for it
+    next_id: AtomicU64,
+    store: Persisted<Vec<Job>>,
+    /// The last id handed out in every guild, which outlives the job when it finishes
+    last_ids: Persisted<u64>,
+}
+
+impl TypeMapKey for Scheduler {
+    type Value = Arc<Scheduler>;
+}
+
+impl Scheduler {
+    pub fn load(sharding: &Sharding) -> Self {
+        let store: Persisted<Vec<Job>> = Persisted::new("jobs");
+        let jobs = store
+            .load(sharding)
+            .into_values()
+            .flatten()
+            .map(|job| (job.id, job))
+            .collect::<HashMap<_, _>>();
+        let last_ids: Persisted<u64> = Persisted::new("job_ids");
+        let next_id = jobs
+            .keys()
+            .copied()
+            .chain(last_ids.load(sharding).into_values())
+            .max()
+            .map_or(1, |id| id + 1);
+        Self {
+            jobs: Mutex::new(jobs),
+            next_id: AtomicU64::new(next_id),
+            store,
+            last_ids,
+        }
+    }
+
+    pub async fn get(ctx: &Context) -> Arc<Self> {
+        ctx.data
+            .read()
+            .await
+            .get::<Self>()
+            .cloned()
+            .expect("Scheduler to be inserted into the client data")
+    }
+
+    fn save(&self, jobs: &HashMap<u64, Job>, guild_id: Option<GuildId>) {
+        self.store.save(
+            guild_id,
+            &jobs
+                .values()
+                .filter(|job| job.guild_id == guild_id)
+                .cloned()
+                .collect(),
+        );
+    }
+
+    /// Schedule a job and return its id
+    pub async fn add(&self, mut job: Job) -> Result<u64> {
+        if let Schedule::Cron(expression) = &job.schedule {
+            Cron::parse(expression)?;
+        }
+        let mut jobs = self.jobs.lock().await;
+        job.id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (id, guild_id) = (job.id, job.guild_id);
+        jobs.insert(id, job);
+        self.save(&jobs, guild_id);
+        self.last_ids.save(guild_id, &id);
+        Ok(id)
+    }
+
+    /// Cancel all jobs of a command that match `filter`, e.g. by payload
+    pub async fn cancel_where(&self, command: &str, filter: impl Fn(&Job) -> bool) {
+        let mut jobs = self.jobs.lock().await;
+        let mut guilds = HashSet::new();
+        jobs.retain(|_, job| {
+            let keep = job.command != command || !filter(job);
+            if !keep {
+                guilds.insert(job.guild_id);
+            }
+            keep
+        });
+        for guild_id in guilds {
+            self.save(&jobs, guild_id);
+        }
+    }
+
+    /// Take the runs that are due and move every job on to its next run
+    async fn take_due(&self) -> Vec<Job> {
+        let now = Timestamp::now().unix_timestamp();
+        let mut jobs = self.jobs.lock().await;
+        let mut due = Vec::new();
+        let mut finished = Vec::new();
+        let mut guilds = HashSet::new();
+        for job in jobs.values_mut().filter(|job| job.next_run <= now) {
+            guilds.insert(job.guild_id);
+            let missed = now - job.next_run > GRACE;
+            let mut runs = Vec::new();
+            let mut next = Some(job.next_run);
+            while let Some(run) = next.filter(|&run| run <= now) {
+                runs.push(run);
+                next = match job.schedule.next_after(run) {
+                    Ok(next) => next,
+                    Err(err) => {
+                        error!("Invalid schedule for job {}: {}", job.id, err);
+                        None
+                    }
+                };
+                // Bound the walk through a long downtime
+                if runs.len() > MAX_CATCH_UP {
+                    next = next.and_then(|_| job.schedule.next_after(now).ok().flatten());
+                }
+            }
+            let count = match (missed, job.missed) {
+                (false, _) => 1,
+                (true, Missed::Skip) => 0,
+                (true, Missed::RunOnce) => 1,
+                (true, Missed::RunAll) => runs.len().min(MAX_CATCH_UP),
+            };
+            if missed {
+                info!(
+                    "Job {} for {} missed {} runs, catching up on {}",
+                    job.id,
+                    job.command,
+                    runs.len(),
+                    count
+                );
+            }
+            due.extend(std::iter::repeat_n(job.clone(), count));
+            match next {
+                Some(next) => job.next_run = next,
+                None => finished.push(job.id),
+            }
+        }
+        for id in finished {
+            jobs.remove(&id);
+        }
+        for guild_id in guilds {
+            self.save(&jobs, guild_id);
+        }
+        due
+    }
+}
+
+/// Run due jobs in the background for as long as the bot runs
+pub fn spawn(ctx: Context) {
+    tokio::spawn(async move {
+        let scheduler = Scheduler::get(&ctx).await;
+        let mut interval = tokio::time::interval(TICK);
+        loop {
+            interval.tick().await;
+            for job in scheduler.take_due().await {
+                let ctx = ctx.clone();
+                tokio::spawn(async move {
+                    let (id, command) = (job.id, job.command.clone());
+                    if let Err(err) = commands::handle_job(ctx.clone(), job).await {
+                        Status::get(&ctx)
+                            .await
+                            .record_error(format!("job {} for {}", id, command), &err)
+                            .await;
+                    }
+                });
+            }
+        }
+    });
+}
+
+/// A parsed cron expression, each field is a bit set of the values it matches
+struct Cron {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// Cron matches either day field when both are restricted
+    any_day: bool,
+}
+
+impl Cron {
+    fn parse(expression: &str) -> Result<Self> {
+        let fields: Vec<_> = expression.split_whitespace().collect();
+        let [minutes, hours, days, months, weekdays] = fields[..] else {
+            bail!("Cron expression needs 5 fields: {}", expression);
+        };
+        // Sunday may be written as both 0 and 7
+        let weekday_set = parse_field(weekdays, 0, 7)?;
+        Ok(Self {
+            minutes: parse_field(minutes, 0, 59)?,
+            hours: parse_field(hours, 0, 23)?,
+            days: parse_field(days, 1, 31)?,
+            months: parse_field(months, 1, 12)?,
+            weekdays: (weekday_set | weekday_set >> 7) & 0x7f,
+            any_day: days != "*" && weekdays != "*",
+        })
+    }
+
+    fn day_matches(&self, date: Date) -> bool {
+        let day = self.days & (1 << date.day()) != 0;
+        let weekday = self.weekdays & (1 << date.weekday().number_days_from_sunday()) != 0;
+        if self.any_day {
+            day || weekday
+        } else {
+            day && weekday
+        }
+    }
+
+    /// The first matching minute after the unix timestamp `after`
+    fn next_after(&self, after: i64) -> Option<i64> {
+        let limit = after + 5 * 366 * 24 * 60 * 60;
+        let mut time =
+            OffsetDateTime::from_unix_timestamp(after - after.rem_euclid(60) + 60).ok()?;
+        while time.unix_timestamp() < limit {
+            if self.months & (1 << time.month() as u8) == 0 {
+                let (year, month) = match time.month() {
+                    Month::December => (time.year() + 1, Month::January),
+                    month => (time.year(), month.next()),
+                };
+                time = Date::from_calendar_date(year, month, 1)
+                    .ok()?
+                    .midnight()
+                    .assume_utc();
+            } else if !self.day_matches(time.date()) {
+                time = time.date().next_day()?.midnight().assume_utc();
+            } else if self.hours & (1 << time.hour()) == 0 {
+                time = time.replace_time(Time::from_hms(time.hour(), 0, 0).ok()?)
+                    + time::Duration::HOUR;
+            } else if self.minutes & (1 << time.minute()) == 0 {
+                time += time::Duration::MINUTE;
+            } else {
+                return Some(time.unix_timestamp());
+            }
+        }
+        None
+    }
+}
+
+/// Parse a comma separated list of `*`, `n`, `a-b` with an optional `/step` into a bit set
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64> {
+    let mut set = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>()?),
+            None => (part, 1),
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            range => match range.split_once('-') {
+                Some((start, end)) => (start.parse()?, end.parse()?),
+                // `n/step` runs from n to the end of the range
+                None if step > 1 => (range.parse()?, max),
+                None => (range.parse()?, range.parse()?),
+            },
+        };
+        if start < min || end > max || start > end || step == 0 {
+            bail!("Invalid cron field '{}', values are {}-{}", field, min, max);
+        }
+        for value in (start..=end).step_by(step as usize) {
+            set |= 1 << value;
+        }
+    }
+    Ok(set)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Unix timestamp of a UTC date and time
+    fn at(year: i32, month: Month, day: u8, hour: u8, minute: u8) -> i64 {
+        Date::from_calendar_date(year, month, day)
+            .unwrap()
+            .with_hms(hour, minute, 0)
+            .unwrap()
+            .assume_utc()
+            .unix_timestamp()
+    }
+
+    fn values(set: u64) -> Vec<u32> {
+        (0..64).filter(|value| set & (1 << value) != 0).collect()
+    }
+
+    #[test]
+    fn fields_parse_ranges_steps_and_lists() {
+        assert_eq!(
+            values(parse_field("*", 1, 12).unwrap()),
+            (1..=12).collect::<Vec<_>>()
+        );
+        assert_eq!(values(parse_field("3-6", 0, 59).unwrap()), [3, 4, 5, 6]);
+        assert_eq!(values(parse_field("*/15", 0, 59).unwrap()), [0, 15, 30, 45]);
+        assert_eq!(values(parse_field("10/20", 0, 59).unwrap()), [10, 30, 50]);
+        assert_eq!(values(parse_field("1-10/4", 0, 59).unwrap()), [1, 5, 9]);
+        assert_eq!(
+            values(parse_field("1,3,5-7", 0, 23).unwrap()),
+            [1, 3, 5, 6, 7]
+        );
+        assert_eq!(values(parse_field("7", 7, 7).unwrap()), [7]);
+    }
+
+    #[test]
+    fn fields_out_of_range_are_rejected() {
+        assert!(parse_field("60", 0, 59).is_err());
+        assert!(parse_field("0", 1, 31).is_err());
+        assert!(parse_field("13", 1, 12).is_err());
+        assert!(parse_field("0-24", 0, 23).is_err());
+        assert!(parse_field("6-2", 0, 23).is_err());
+        assert!(parse_field("*/0", 0, 59).is_err());
+        assert!(parse_field("1,,2", 0, 59).is_err());
+        assert!(parse_field("mon", 0, 7).is_err());
+        assert!(Cron::parse("0 0 * * 8").is_err());
+        assert!(Cron::parse("0 0 * *").is_err());
+        assert!(Cron::parse("0 0 * * * *").is_err());
+    }
+
+    #[test]
+    fn sunday_is_both_0_and_7() {
+        assert_eq!(
+            Cron::parse("0 0 * * 0").unwrap().weekdays,
+            Cron::parse("0 0 * * 7").unwrap().weekdays
+        );
+        assert_eq!(
+            values(Cron::parse("0 0 * * 5-7").unwrap().weekdays),
+            [0, 5, 6]
+        );
+    }
+
+    #[test]
+    fn next_run_is_the_next_matching_minute() {
+        let daily = Cron::parse("30 9 * * *").unwrap();
+        let start = at(2024, Month::January, 1, 10, 0);
+        assert_eq!(
+            daily.next_after(start),
+            Some(at(2024, Month::January, 2, 9, 30))
+        );
+        // Strictly after, even when `after` matches itself
+        let quarterly = Cron::parse("*/15 * * * *").unwrap();
+        let run = at(2024, Month::January, 1, 10, 15);
+        assert_eq!(
+            quarterly.next_after(run),
+            Some(at(2024, Month::January, 1, 10, 30))
+        );
+        assert_eq!(quarterly.next_after(run - 1), Some(run));
+
+        let new_year = Cron::parse("0 0 1 1 *").unwrap();
+        let summer = at(2024, Month::June, 15, 12, 0);
+        assert_eq!(
+            new_year.next_after(summer),
+            Some(at(2025, Month::January, 1, 0, 0))
+        );
+
+        assert_eq!(Cron::parse("0 12 31 2 *").unwrap().next_after(summer), None);
+    }
+
+    #[test]
+    fn restricted_day_fields_match_either_day() {
+        // 2024-01-01 is a Monday
+        let monday = at(2024, Month::January, 1, 0, 0);
+        let mondays = Cron::parse("0 0 * * 1").unwrap();
+        assert_eq!(
+            mondays.next_after(monday),
+            Some(at(2024, Month::January, 8, 0, 0))
+        );
+
+        let fridays_or_13th = Cron::parse("0 0 13 * 5").unwrap();
+        let friday = at(2024, Month::January, 5, 0, 0);
+        assert_eq!(fridays_or_13th.next_after(monday), Some(friday));
+        let next_friday = at(2024, Month::January, 12, 0, 0);
+        assert_eq!(fridays_or_13th.next_after(friday), Some(next_friday));
+        assert_eq!(
+            fridays_or_13th.next_after(next_friday),
+            Some(at(2024, Month::January, 13, 0, 0))
+        );
+
+        let thirteenth_in_march = Cron::parse("0 0 13 3 *").unwrap();
+        assert_eq!(
+            thirteenth_in_march.next_after(monday),
+            Some(at(2024, Month::March, 13, 0, 0))
+        );
+    }
+
+    #[test]
+    fn every_needs_an_interval() {
+        assert!(Schedule::Every(0).next_after(0).is_err());
+        assert_eq!(Schedule::Every(90).next_after(10).unwrap(), Some(100));
+        assert_eq!(Schedule::Once.next_after(10).unwrap(), None);
+    }
+}
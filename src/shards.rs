@@ -0,0 +1,195 @@
+use std::env;
+use std::ops::Range;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Result};
+use serenity::gateway::ShardManager;
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+use tracing::info;
+
+/// How often the status of every shard is logged
+const STATUS_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShardCount {
+    /// Run a single shard, enough for bots in fewer than 2500 guilds
+    Single,
+    /// Let Discord recommend the number of shards, all run by this process
+    Auto,
+    Fixed(u32),
+}
+
+/// Which shards this process runs, configured with `SHARD_COUNT` and `SHARD_RANGE`
+#[derive(Debug, Clone)]
+pub struct Sharding {
+    pub count: ShardCount,
+    /// Shards run by this process when the shards are split over several processes
+    pub range: Option<Range<u32>>,
+}
+
+impl TypeMapKey for Sharding {
+    type Value = Sharding;
+}
+
+pub struct ShardManagerKey;
+
+impl TypeMapKey for ShardManagerKey {
+    type Value = Arc<ShardManager>;
+}
+
+impl Sharding {
+    /// `SHARD_COUNT` is `auto` or a number, `SHARD_RANGE` is an inclusive range like `0-3`
+    pub fn from_env() -> Result<Self> {
+        Self::parse(
+            env::var("SHARD_COUNT").ok().as_deref(),
+            env::var("SHARD_RANGE").ok().as_deref(),
+        )
+    }
+
+    fn parse(count: Option<&str>, range: Option<&str>) -> Result<Self> {
+        let count = match count {
+            None => ShardCount::Single,
+            Some("auto") => ShardCount::Auto,
+            Some(count) => ShardCount::Fixed(
+                count
+                    .parse()
+                    .map_err(|_| anyhow!("Invalid SHARD_COUNT: {}", count))?,
+            ),
+        };
+        let range = range
+            .map(|range| {
+                let (first, last) = range
+                    .split_once('-')
+                    .ok_or(anyhow!("SHARD_RANGE must look like 0-3: {}", range))?;
+                Ok::<_, anyhow::Error>(first.trim().parse()?..last.trim().parse::<u32>()? + 1)
+            })
+            .transpose()?;
+        match (count, &range) {
+            (ShardCount::Fixed(0), _) => bail!("SHARD_COUNT must be at least 1"),
+            (ShardCount::Fixed(total), Some(range)) if range.is_empty() || range.end > total => {
+                bail!("SHARD_RANGE {:?} does not fit {} shards", range, total)
+            }
+            (ShardCount::Single | ShardCount::Auto, Some(_)) => {
+                bail!("SHARD_RANGE needs a numeric SHARD_COUNT")
+            }
+            _ => {}
+        }
+        Ok(Self { count, range })
+    }
+
+    /// Whether events for the guild are received by this process, direct messages go to shard 0
+    pub fn owns(&self, guild_id: Option<GuildId>) -> bool {
+        match (self.count, &self.range) {
+            (ShardCount::Fixed(total), Some(range)) => {
+                range.contains(&guild_id.map_or(0, |guild_id| guild_id.shard_id(total)))
+            }
+            _ => true,
+        }
+    }
+
+    pub async fn start(&self, client: &mut Client) -> serenity::Result<()> {
+        info!("Starting shards: {:?}", self);
+        match (self.count, &self.range) {
+            (ShardCount::Single, _) => client.start().await,
+            (ShardCount::Auto, _) => client.start_autosharded().await,
+            (ShardCount::Fixed(total), None) => client.start_shards(total).await,
+            (ShardCount::Fixed(total), Some(range)) => {
+                client.start_shard_range(range.clone(), total).await
+            }
+        }
+    }
+}
+
+/// Log the stage and latency of every running shard at a fixed interval
+pub fn spawn_status(shard_manager: Arc<ShardManager>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(STATUS_INTERVAL);
+        loop {
+            interval.tick().await;
+            let runners = shard_manager.runners.lock().await;
+            for (id, runner) in runners.iter() {
+                info!(
+                    shard = id.0,
+                    stage = %runner.stage,
+                    latency_ms = runner.latency.map(|latency| latency.as_millis() as u64),
+                    "Shard status"
+                );
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A guild id that lands on `shard` of `total` shards
+    fn guild_on(shard: u32, total: u32) -> GuildId {
+        GuildId::new(((shard as u64) << 22) + (total as u64) * (1 << 22))
+    }
+
+    #[test]
+    fn counts_are_parsed() {
+        assert_eq!(
+            Sharding::parse(None, None).unwrap().count,
+            ShardCount::Single
+        );
+        assert_eq!(
+            Sharding::parse(Some("auto"), None).unwrap().count,
+            ShardCount::Auto
+        );
+        let fixed = Sharding::parse(Some("4"), None).unwrap();
+        assert_eq!(fixed.count, ShardCount::Fixed(4));
+        assert_eq!(fixed.range, None);
+        assert!(Sharding::parse(Some("0"), None).is_err());
+        assert!(Sharding::parse(Some("-1"), None).is_err());
+        assert!(Sharding::parse(Some("many"), None).is_err());
+    }
+
+    #[test]
+    fn ranges_are_inclusive() {
+        let sharding = Sharding::parse(Some("8"), Some("2 - 5")).unwrap();
+        assert_eq!(sharding.range, Some(2..6));
+        assert_eq!(
+            Sharding::parse(Some("8"), Some("7-7")).unwrap().range,
+            Some(7..8)
+        );
+    }
+
+    #[test]
+    fn invalid_ranges_are_rejected() {
+        for range in ["3", "a-b", "1-", "-2", "1-2-3"] {
+            assert!(
+                Sharding::parse(Some("8"), Some(range)).is_err(),
+                "{}",
+                range
+            );
+        }
+        // Reversed and too large for the count
+        assert!(Sharding::parse(Some("8"), Some("5-2")).is_err());
+        assert!(Sharding::parse(Some("8"), Some("4-8")).is_err());
+        // Only a numeric count can be split
+        assert!(Sharding::parse(None, Some("0-1")).is_err());
+        assert!(Sharding::parse(Some("auto"), Some("0-1")).is_err());
+    }
+
+    #[test]
+    fn owns_the_guilds_of_its_range() {
+        let sharding = Sharding::parse(Some("8"), Some("2-5")).unwrap();
+        assert!(!sharding.owns(Some(guild_on(1, 8))));
+        assert!(sharding.owns(Some(guild_on(2, 8))));
+        assert!(sharding.owns(Some(guild_on(5, 8))));
+        assert!(!sharding.owns(Some(guild_on(6, 8))));
+        // Direct messages go to shard 0
+        assert!(!sharding.owns(None));
+        assert!(Sharding::parse(Some("8"), Some("0-1")).unwrap().owns(None));
+
+        let all = Sharding::parse(Some("8"), None).unwrap();
+        assert!(all.owns(Some(guild_on(7, 8))) && all.owns(None));
+        assert!(Sharding::parse(None, None)
+            .unwrap()
+            .owns(Some(guild_on(3, 8))));
+    }
+}
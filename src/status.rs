@@ -0,0 +1,91 @@
+use std::collections::{HashSet, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use serenity::model::prelude::*;
+use serenity::prelude::*;
+use tracing::error;
+
+/// How many handler errors are kept for `/admin errors`
+const MAX_ERRORS: usize = 20;
+
+/// Runtime information about the bot shown to the owners
+pub struct Status {
+    pub started_at: Timestamp,
+    maintenance: AtomicBool,
+    guilds: Mutex<HashSet<GuildId>>,
+    errors: Mutex<VecDeque<HandlerError>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct HandlerError {
+    pub at: Timestamp,
+    pub source: String,
+    pub message: String,
+}
+
+impl TypeMapKey for Status {
+    type Value = Arc<Status>;
+}
+
+impl Status {
+    pub fn new() -> Self {
+        Self {
+            started_at: Timestamp::now(),
+            maintenance: AtomicBool::new(false),
+            guilds: Mutex::new(HashSet::new()),
+            errors: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    pub async fn get(ctx: &Context) -> Arc<Self> {
+        ctx.data
+            .read()
+            .await
+            .get::<Self>()
+            .cloned()
+            .expect("Status to be inserted into the client data")
+    }
+
+    /// While in maintenance new interactions are rejected with a notice
+    pub fn maintenance(&self) -> bool {
+        self.maintenance.load(Ordering::SeqCst)
+    }
+
+    pub fn set_maintenance(&self, enabled: bool) {
+        self.maintenance.store(enabled, Ordering::SeqCst);
+    }
+
+    pub async fn add_guilds(&self, guilds: impl IntoIterator<Item = GuildId>) {
+        self.guilds.lock().await.extend(guilds);
+    }
+
+    pub async fn remove_guild(&self, guild_id: GuildId) {
+        self.guilds.lock().await.remove(&guild_id);
+    }
+
+    /// Guilds on the shards of this process
+    pub async fn guild_count(&self) -> usize {
+        self.guilds.lock().await.len()
+    }
+
+    /// Log an error and keep it around for `/admin errors`
+    pub async fn record_error(&self, source: impl Into<String>, err: &anyhow::Error) {
+        let source = source.into();
+        error!("Error in {}: {:?}", source, err);
+        let mut errors = self.errors.lock().await;
+        if errors.len() == MAX_ERRORS {
+            errors.pop_front();
+        }
+        errors.push_back(HandlerError {
+            at: Timestamp::now(),
+            source,
+            message: format!("{:#}", err),
+        });
+    }
+
+    /// The most recent errors, newest first
+    pub async fn errors(&self) -> Vec<HandlerError> {
+        self.errors.lock().await.iter().rev().cloned().collect()
+    }
+}
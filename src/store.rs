@@ -0,0 +1,91 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::marker::PhantomData;
+use std::path::PathBuf;
+
+use serde::{de::DeserializeOwned, Serialize};
+use serenity::model::prelude::*;
+use tracing::{error, info};
+
+use crate::shards::Sharding;
+
+/// Per guild values that are saved as JSON in `DATA_DIR` when that variable is set.
+///
+/// Every guild gets its own file, `DATA_DIR/<name>/<guild id>.json` or `dm.json` for
+/// direct messages, so processes that run different shards never write the same file.
+/// Without `DATA_DIR` nothing is written and [`Persisted::load`] finds nothing.
+pub struct Persisted<T> {
+    dir: Option<PathBuf>,
+    _value: PhantomData<T>,
+}
+
+impl<T: Serialize + DeserializeOwned> Persisted<T> {
+    pub fn new(name: &str) -> Self {
+        Self {
+            dir: env::var("DATA_DIR")
+                .ok()
+                .map(|dir| PathBuf::from(dir).join(name)),
+            _value: PhantomData,
+        }
+    }
+
+    fn path(&self, guild_id: Option<GuildId>) -> Option<PathBuf> {
+        self.dir.as_ref().map(|dir| match guild_id {
+            Some(guild_id) => dir.join(format!("{}.json", guild_id)),
+            None => dir.join("dm.json"),
+        })
+    }
+
+    /// Load the values of every guild that is handled by this process
+    pub fn load(&self, sharding: &Sharding) -> HashMap<Option<GuildId>, T> {
+        let Some(dir) = &self.dir else {
+            return HashMap::new();
+        };
+        let Ok(files) = fs::read_dir(dir) else {
+            info!("Nothing persisted at {} yet", dir.display());
+            return HashMap::new();
+        };
+        files
+            .filter_map(|file| {
+                let path = file.ok()?.path();
+                let guild_id = match path.file_stem()?.to_str()? {
+                    "dm" => None,
+                    stem => Some(GuildId::new(stem.parse().ok()?)),
+                };
+                if !sharding.owns(guild_id) {
+                    return None;
+                }
+                let json = fs::read_to_string(&path).ok()?;
+                match serde_json::from_str(&json) {
+                    Ok(value) => Some((guild_id, value)),
+                    Err(err) => {
+                        error!("Could not parse {}: {}", path.display(), err);
+                        None
+                    }
+                }
+            })
+            .collect()
+    }
+
+    pub fn save(&self, guild_id: Option<GuildId>, value: &T) {
+        let Some(path) = self.path(guild_id) else {
+            return;
+        };
+        // Write to a temporary file first so a crash never leaves half a file behind
+        let tmp = path.with_extension("json.tmp");
+        let result = serde_json::to_string(value)
+            .map_err(anyhow::Error::from)
+            .and_then(|json| {
+                if let Some(dir) = path.parent() {
+                    fs::create_dir_all(dir)?;
+                }
+                fs::write(&tmp, json)?;
+                fs::rename(&tmp, &path)?;
+                Ok(())
+            });
+        if let Err(err) = result {
+            error!("Could not save {}: {}", path.display(), err);
+        }
+    }
+}